@@ -0,0 +1,25 @@
+import type { UpdateResult } from "../index.js";
+
+/**
+ * An update moving `name` from `current` to `newRange`, as callers of the
+ * native writers build them. `requiresManifestChange` is left out, which counts
+ * as true.
+ */
+export function update(
+  name: string,
+  current: string,
+  newRange: string,
+  fields: Partial<UpdateResult> = {},
+): UpdateResult {
+  const version = (range: string) => range.replace(/^[^\d]*/, "");
+  return {
+    name,
+    current,
+    currentVersion: version(current),
+    latest: version(newRange),
+    newRange,
+    updateType: "minor",
+    depType: "prod",
+    ...fields,
+  };
+}
@@ -121,6 +121,83 @@ describe("checkUpdates", () => {
     const result = await native.checkUpdates(packages, options);
     expect(result.cacheHits).toBeGreaterThanOrEqual(1);
   });
+
+  it("should report progress for every package", async () => {
+    const packages = [
+      { name: "lodash", versionRange: "^4.0.0", depType: "prod" },
+      { name: "chalk", versionRange: "^4.0.0", depType: "prod" },
+    ];
+
+    const events: { kind: string; name: string; completed: number; total: number }[] = [];
+    await native.checkUpdates(
+      packages,
+      {
+        target: "latest",
+        concurrency: 24,
+        timeoutMs: 30000,
+        cacheTtlSeconds: 600,
+        retries: 3,
+      },
+      (event: { kind: string; name: string; completed: number; total: number }) => {
+        events.push(event);
+      },
+    );
+
+    const terminal = events.filter((e) => ["cache-hit", "success", "failure"].includes(e.kind));
+    expect(terminal.map((e) => e.name).sort()).toEqual(["chalk", "lodash"]);
+    expect(events.every((e) => e.total === 2)).toBe(true);
+  });
+});
+
+describe("cancellation", () => {
+  it("should resolve with a cancelled result when aborted", async () => {
+    const controller = new AbortController();
+    controller.abort();
+
+    const result = await native.checkUpdates(
+      [{ name: "turbo-ncu-never-cached", versionRange: "^1.0.0", depType: "prod" }],
+      {
+        target: "latest",
+        concurrency: 24,
+        timeoutMs: 30000,
+        cacheTtlSeconds: 600,
+        retries: 3,
+      },
+      undefined,
+      controller.signal,
+    );
+
+    expect(result.cancelled).toBe(true);
+    expect(result.updates).toEqual([]);
+  });
+});
+
+describe("streamUpdates", () => {
+  it("should emit each update before resolving", async () => {
+    const packages = [
+      { name: "lodash", versionRange: "^4.0.0", depType: "prod" },
+      { name: "chalk", versionRange: "^4.0.0", depType: "prod" },
+    ];
+
+    const streamed: { name: string }[] = [];
+    const result = await native.streamUpdates(
+      packages,
+      {
+        target: "latest",
+        concurrency: 24,
+        timeoutMs: 30000,
+        cacheTtlSeconds: 600,
+        retries: 3,
+      },
+      (update: { name: string }) => {
+        streamed.push(update);
+      },
+    );
+
+    expect(streamed.map((u) => u.name).sort()).toEqual(
+      result.updates.map((u: { name: string }) => u.name).sort(),
+    );
+  });
 });
 
 describe("clearCache", () => {
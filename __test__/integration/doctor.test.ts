@@ -0,0 +1,57 @@
+import { describe, it, expect, afterEach } from "vitest";
+import { createRequire } from "node:module";
+import * as fs from "node:fs";
+import * as os from "node:os";
+import * as path from "node:path";
+
+import { update } from "../helpers.js";
+
+const require = createRequire(import.meta.url);
+const native = require("../../index.cjs");
+
+describe.skipIf(process.platform === "win32")("doctor", () => {
+  let dir: string;
+
+  afterEach(() => {
+    fs.rmSync(dir, { recursive: true, force: true });
+  });
+
+  it("should keep the upgrades the tests pass with", async () => {
+    dir = fs.mkdtempSync(path.join(os.tmpdir(), "turbo-ncu-doctor-"));
+    const file = path.join(dir, "package.json");
+    fs.writeFileSync(
+      file,
+      JSON.stringify({ dependencies: { a: "^1.0.0", broken: "^1.0.0" } }, null, 2),
+    );
+    const steps: string[] = [];
+
+    const report = await native.doctor(
+      file,
+      [update("a", "^1.0.0", "^2.0.0"), update("broken", "^1.0.0", "^2.0.0")],
+      {
+        installCommand: "true",
+        testCommand: `! grep -q '"broken": "\\^2' package.json`,
+      },
+      (step: { kind: string; passed?: boolean }) => {
+        if (step.passed != null) steps.push(`${step.kind}:${step.passed}`);
+      },
+    );
+
+    // Step callbacks are queued on the event loop and may trail the result
+    await new Promise((resolve) => setImmediate(resolve));
+
+    expect(report.passed.map((u: { name: string }) => u.name)).toEqual(["a"]);
+    expect(report.failed.map((f: { update: { name: string } }) => f.update.name)).toEqual([
+      "broken",
+    ]);
+    expect(steps).toEqual([
+      "baseline:true",
+      "all:false",
+      "single:true",
+      "single:false",
+      "restore:true",
+    ]);
+    const manifest = JSON.parse(fs.readFileSync(file, "utf-8"));
+    expect(manifest.dependencies).toEqual({ a: "^2.0.0", broken: "^1.0.0" });
+  });
+});
@@ -5,6 +5,7 @@ import { fileURLToPath } from "node:url";
 const __dirname = path.dirname(fileURLToPath(import.meta.url));
 
 const readerModule = await import("../../dist/src/package-reader.js");
+const formatterModule = await import("../../dist/src/formatter.js");
 
 describe("package-reader", () => {
   const fixtureDir = path.resolve(__dirname, "../fixtures/basic");
@@ -37,6 +38,73 @@ describe("package-reader", () => {
     expect(packages[0].name).toBe("typescript");
   });
 
+  it("should skip specifiers that are not registry ranges", () => {
+    const packages = readerModule.extractPackages(
+      {
+        dependencies: {
+          lodash: "^4.17.21",
+          "strip-ansi-cjs": "npm:strip-ansi@^6.0.1",
+          local: "file:../local",
+          fork: "user/repo#main",
+          internal: "workspace:*",
+          react: "catalog:",
+          next: "canary",
+        },
+      },
+      ["prod"],
+    );
+    expect(packages.map((p) => p.name)).toEqual(["lodash", "strip-ansi-cjs"]);
+    expect(packages[1].versionRange).toBe("npm:strip-ansi@^6.0.1");
+  });
+
+  it("should extract overrides only when requested", () => {
+    const pkg = {
+      dependencies: { foo: "^1.0.0" },
+      overrides: { foo: { bar: "1.0.0" } },
+      pnpm: { overrides: { "baz@^2": "2.1.0" } },
+    };
+    expect(readerModule.extractPackages(pkg, ["prod"]).length).toBe(1);
+    const overrides = readerModule.extractPackages(pkg, ["override"]);
+    expect(overrides.map((p) => [p.name, p.pointer])).toEqual([
+      ["foo>bar", "/overrides/foo/bar"],
+      ["baz@^2", "/pnpm/overrides/baz@^2"],
+    ]);
+    expect(readerModule.parseDepTypes(["prod,override"])).toEqual(["prod", "override"]);
+  });
+
+  it("should report the workspaces consuming each catalog entry", () => {
+    const catalogDir = path.resolve(__dirname, "../fixtures/pnpm-catalog");
+    const manifests = ["package.json", "packages/app/package.json", "packages/legacy/package.json"].map(
+      (file) => path.resolve(catalogDir, file),
+    );
+    const { packages, consumers } = readerModule.readCatalogPackages(
+      path.resolve(catalogDir, "pnpm-workspace.yaml"),
+      manifests,
+    );
+    expect(packages.map((p) => [p.name, p.pointer])).toEqual([
+      ["react", "/catalog/react"],
+      ["lodash", "/catalog/lodash"],
+      ["react", "/catalogs/react17/react"],
+    ]);
+    expect(consumers.get("/catalog/react")).toEqual(["app"]);
+    expect(consumers.get("/catalog/lodash")).toEqual(["catalog-root", "app"]);
+    expect(consumers.get("/catalogs/react17/react")).toEqual(["legacy"]);
+
+    const table = formatterModule.formatTable(
+      [
+        {
+          name: "lodash",
+          current: "^4.17.0",
+          newRange: "^4.17.21",
+          updateType: "patch",
+          pointer: "/catalog/lodash",
+        },
+      ],
+      consumers,
+    );
+    expect(table).toContain("used by catalog-root, app");
+  });
+
   it("should parse dep types correctly", () => {
     expect(readerModule.parseDepTypes([])).toEqual(["prod", "dev", "peer", "optional"]);
     expect(readerModule.parseDepTypes(["prod"])).toEqual(["prod"]);
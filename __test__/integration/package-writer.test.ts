@@ -0,0 +1,58 @@
+import { describe, it, expect, afterEach } from "vitest";
+import { createRequire } from "node:module";
+import * as fs from "node:fs";
+import * as os from "node:os";
+import * as path from "node:path";
+
+import { update } from "../helpers.js";
+
+const require = createRequire(import.meta.url);
+const native = require("../../index.cjs");
+
+describe("applyUpdates", () => {
+  let dir: string;
+
+  afterEach(() => {
+    fs.rmSync(dir, { recursive: true, force: true });
+  });
+
+  it("should change only the version strings", () => {
+    dir = fs.mkdtempSync(path.join(os.tmpdir(), "turbo-ncu-writer-"));
+    const file = path.join(dir, "package.json");
+    const original = [
+      "{",
+      '\t"name": "caf\\u00e9",',
+      '\t"dependencies": {',
+      '\t\t"lodash":  "^4.0.0",',
+      '\t\t"@types/node": "^18.0.0"',
+      "\t},",
+      '  "devDependencies": { "vitest": "~1.0.0" }',
+      "}",
+    ].join("\r\n");
+    fs.writeFileSync(file, original);
+
+    const count = native.applyUpdates(file, [
+      update("lodash", "^4.0.0", "^4.17.21"),
+      update("@types/node", "^18.0.0", "^20.11.0"),
+      update("vitest", "~1.0.0", "~1.6.0", { depType: "dev" }),
+      update("not-listed", "^0.1.0", "^1.0.0"),
+    ]);
+
+    expect(count).toBe(3);
+    expect(fs.readFileSync(file, "utf-8")).toBe(
+      original
+        .replace('"^4.0.0"', '"^4.17.21"')
+        .replace('"^18.0.0"', '"^20.11.0"')
+        .replace('"~1.0.0"', '"~1.6.0"'),
+    );
+  });
+
+  it("should reject invalid JSON without touching the file", () => {
+    dir = fs.mkdtempSync(path.join(os.tmpdir(), "turbo-ncu-writer-"));
+    const file = path.join(dir, "package.json");
+    fs.writeFileSync(file, '{ "dependencies": { "a": "1", } }');
+
+    expect(() => native.applyUpdates(file, [update("a", "1", "2")])).toThrow(/parse/);
+    expect(fs.readFileSync(file, "utf-8")).toBe('{ "dependencies": { "a": "1", } }');
+  });
+});
@@ -0,0 +1,53 @@
+import { describe, it, expect, afterEach } from "vitest";
+import { createRequire } from "node:module";
+import * as fs from "node:fs";
+import * as os from "node:os";
+import * as path from "node:path";
+
+import { update } from "../helpers.js";
+
+const require = createRequire(import.meta.url);
+const native = require("../../index.cjs");
+
+describe("update plan", () => {
+  let dir: string;
+
+  afterEach(() => {
+    fs.rmSync(dir, { recursive: true, force: true });
+  });
+
+  it("should export batches and apply them once", () => {
+    dir = fs.mkdtempSync(path.join(os.tmpdir(), "turbo-ncu-plan-"));
+    fs.mkdirSync(path.join(dir, "web"));
+    const file = path.join(dir, "web", "package.json");
+    fs.writeFileSync(
+      file,
+      JSON.stringify({ dependencies: { "@babel/core": "^7.23.0", lodash: "^4.17.20" } }, null, 2),
+    );
+    const core = update("@babel/core", "^7.23.0", "^7.24.0", { workspace: "web" });
+    const lodash = update("lodash", "^4.17.20", "^4.17.21", { workspace: "web" });
+
+    const text = native.exportPlan({
+      updates: [core, lodash],
+      groups: [{ name: "@babel", source: "scope", version: "7.24.0", fellBack: false, updates: [core] }],
+    });
+    const plan = JSON.parse(text);
+    expect(plan.version).toBe(1);
+    expect(plan.batches.map((b: { id: string }) => b.id)).toEqual(["group-babel", "lodash"]);
+    expect(plan.batches[0].branch).toBe("turbo-ncu/group-babel-7.24.0");
+    expect(plan.batches[1].edits).toEqual([
+      { path: "web/package.json", pointer: "/dependencies/lodash", oldValue: "^4.17.20", newValue: "^4.17.21" },
+    ]);
+
+    expect(native.applyPlan(text, { root: dir, batches: ["lodash"] })).toEqual({
+      applied: 1,
+      unchanged: 0,
+      conflicts: [],
+    });
+    expect(native.applyPlan(text, { root: dir })).toEqual({ applied: 1, unchanged: 1, conflicts: [] });
+    expect(JSON.parse(fs.readFileSync(file, "utf-8")).dependencies).toEqual({
+      "@babel/core": "^7.24.0",
+      lodash: "^4.17.21",
+    });
+  });
+});
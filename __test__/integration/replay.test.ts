@@ -0,0 +1,48 @@
+import { describe, it, expect } from "vitest";
+import { createRequire } from "node:module";
+import * as path from "node:path";
+import { fileURLToPath } from "node:url";
+
+const require = createRequire(import.meta.url);
+const native = require("../../index.cjs");
+
+const __dirname = path.dirname(fileURLToPath(import.meta.url));
+const fixtureDir = path.resolve(__dirname, "../fixtures/registry");
+
+const options = {
+  registry: "https://registry.test",
+  target: "latest",
+  retries: 0,
+  fixtureMode: "replay",
+  fixtureDir,
+};
+
+describe("replay fixtures", () => {
+  it("should resolve updates without touching the network", async () => {
+    const packages = [
+      { name: "lodash", versionRange: "^4.0.0", depType: "prod" },
+      { name: "@types/node", versionRange: "^18.0.0", depType: "dev" },
+    ];
+
+    const result = await native.checkUpdates(packages, options);
+
+    expect(result.updates).toEqual([
+      expect.objectContaining({ name: "lodash", newRange: "^4.17.21", updateType: "minor" }),
+      expect.objectContaining({ name: "@types/node", newRange: "^20.11.0", updateType: "major" }),
+    ]);
+    expect(result.cacheHits).toBe(0);
+  });
+
+  it("should skip packages without a recorded response", async () => {
+    const packages = [{ name: "not-recorded", versionRange: "^1.0.0", depType: "prod" }];
+
+    const result = await native.checkUpdates(packages, options);
+    expect(result.updates).toEqual([]);
+  });
+
+  it("should reject a fixture mode without a directory", async () => {
+    await expect(
+      native.checkUpdates([], { ...options, fixtureDir: undefined }),
+    ).rejects.toThrow(/fixtureDir/);
+  });
+});
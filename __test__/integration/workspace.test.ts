@@ -0,0 +1,27 @@
+import { describe, it, expect } from "vitest";
+import { createRequire } from "node:module";
+import * as path from "node:path";
+import { fileURLToPath } from "node:url";
+
+const require = createRequire(import.meta.url);
+const native = require("../../index.cjs");
+
+const __dirname = path.dirname(fileURLToPath(import.meta.url));
+const fixtureDir = path.resolve(__dirname, "../fixtures/workspace-root");
+
+describe("discoverWorkspaces", () => {
+  it("should list the root and its workspaces", () => {
+    const graph = native.discoverWorkspaces(fixtureDir);
+
+    expect(graph.sources).toEqual(["npm"]);
+    expect(graph.packages).toEqual([
+      expect.objectContaining({ name: "test-workspace", path: "", version: "1.0.0" }),
+      expect.objectContaining({
+        name: "pkg-a",
+        path: "packages/pkg-a",
+        manifestPath: path.join(fixtureDir, "packages/pkg-a/package.json"),
+      }),
+    ]);
+    expect(graph.edges).toEqual([]);
+  });
+});
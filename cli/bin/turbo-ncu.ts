@@ -19,10 +19,33 @@ async function main() {
     cacheTtl: parseInt(opts.cacheTtl, 10),
     concurrency: parseInt(opts.concurrency, 10),
     registry: opts.registry,
+    proxy: opts.proxy,
+    httpsProxy: opts.httpsProxy,
+    noProxy: opts.noProxy,
+    caFile: opts.caFile,
+    strictSsl: opts.strictSsl,
+    certFile: opts.certFile,
+    keyFile: opts.keyFile,
+    fallbackRegistries: opts.fallbackRegistries,
+    failoverOn: opts.failoverOn,
+    fullMetadata: opts.fullMetadata,
+    allowedLicenses: opts.allowedLicenses,
+    checkAdvisories: opts.checkAdvisories,
     pre: opts.pre,
     workspaces: opts.workspaces,
     workspace: opts.workspace,
     root: opts.root,
+    sync: opts.sync,
+    transitive: opts.transitive,
+    installed: opts.installed,
+    group: opts.group,
+    doctor: opts.doctor,
+    doctorInstall: opts.doctorInstall,
+    doctorTest: opts.doctorTest,
+    doctorStrategy: opts.doctorStrategy,
+    plan: opts.plan,
+    applyPlan: opts.applyPlan,
+    batch: opts.batch,
     global: opts.global,
     json: opts.json,
     jsonAll: opts.jsonAll,
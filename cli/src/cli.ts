@@ -8,10 +8,10 @@ export function createProgram(): Command {
     .description("Fast npm-check-updates — check for outdated dependencies")
     .version("0.1.0")
     .option("-u, --upgrade", "overwrite package file with upgraded versions", false)
-    .option("-t, --target <target>", "target version: latest, minor, patch, semver", "latest")
+    .option("-t, --target <target>", "target version: latest, minor, patch, semver, security", "latest")
     .option("--filter <pattern>", "include only matching package names")
     .option("--reject <pattern>", "exclude matching package names")
-    .option("--dep <types...>", "dependency types: prod, dev, peer, optional", [
+    .option("--dep <types...>", "dependency types: prod, dev, peer, optional, override", [
       "prod",
       "dev",
       "peer",
@@ -21,10 +21,33 @@ export function createProgram(): Command {
     .option("--cacheTtl <seconds>", "cache TTL in seconds", "600")
     .option("--concurrency <n>", "number of concurrent requests", "24")
     .option("--registry <url>", "npm registry URL")
+    .option("--proxy <url>", "proxy for http:// registries (default: npm proxy, HTTP_PROXY)")
+    .option("--httpsProxy <url>", "proxy for https:// registries (default: npm https-proxy, HTTPS_PROXY)")
+    .option("--noProxy <hosts>", "comma-separated hosts that bypass the proxy (default: npm noproxy, NO_PROXY)")
+    .option("--caFile <path>", "PEM bundle of extra trusted CA certificates")
+    .option("--no-strictSsl", "skip TLS certificate validation")
+    .option("--certFile <path>", "PEM client certificate for mutual TLS")
+    .option("--keyFile <path>", "PEM private key for --certFile")
+    .option("--fallbackRegistries <urls...>", "registries tried in order when --registry fails")
+    .option("--failoverOn <failures...>", "failures that move on to the next registry: 5xx, timeout, 404, 429")
+    .option("--fullMetadata", "fetch full packuments for per-version details in --jsonAll", false)
+    .option("--allowedLicenses <licenses...>", "SPDX licenses an upgrade may move to; others are skipped")
+    .option("--checkAdvisories", "look up security advisories for current and target versions", false)
     .option("--pre", "include prerelease versions", false)
     .option("-w, --workspaces", "check all workspaces", false)
     .option("--workspace <name>", "check a specific workspace")
     .option("--root", "include root package in workspace mode", false)
+    .option("--sync <policy>", "align packages shared by workspaces: highest, latest, pinned")
+    .option("--transitive", "also report outdated indirect dependencies from the lockfile", false)
+    .option("--installed", "compare node_modules with the declared ranges", false)
+    .option("--group", "upgrade packages of the same scope or repository together", false)
+    .option("--doctor", "upgrade, install and test, keeping only upgrades that pass", false)
+    .option("--doctorInstall <command>", "install command for --doctor")
+    .option("--doctorTest <command>", "test command for --doctor")
+    .option("--doctorStrategy <strategy>", "how --doctor retries failing upgrades: iterate, bisect")
+    .option("--plan <file>", "write an update plan with one batch per group or package")
+    .option("--applyPlan <file>", "apply the edits of an update plan")
+    .option("--batch <ids...>", "batches of --applyPlan to apply")
     .option("-g, --global", "check global packages", false)
     .option("--json", "output as JSON", false)
     .option("--jsonAll", "output full update details as JSON", false)
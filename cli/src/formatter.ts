@@ -1,5 +1,14 @@
 import chalk from "chalk";
-import type { UpdateResult } from "../../index.js";
+import type {
+  AppliedPlan,
+  Divergence,
+  DoctorReport,
+  InstalledIssue,
+  TransitiveUpdate,
+  UpdateGroup,
+  UpdateResult,
+  WorkspaceEdge,
+} from "../../index.js";
 
 export function formatHeader(version: string): string {
   return chalk.bold(`turbo-ncu`) + chalk.dim(` v${version}`);
@@ -18,7 +27,11 @@ function colorByType(text: string, updateType: string): string {
   }
 }
 
-export function formatTable(updates: UpdateResult[]): string {
+/**
+ * One line per update; `consumers` (workspace names by pointer) adds the
+ * workspaces each pnpm catalog entry is used by.
+ */
+export function formatTable(updates: UpdateResult[], consumers?: Map<string, string[]>): string {
   if (updates.length === 0) {
     return chalk.green("All dependencies match the latest package versions :)");
   }
@@ -34,13 +47,121 @@ export function formatTable(updates: UpdateResult[]): string {
     const current = update.current.padEnd(currentWidth);
     const arrow = "→";
     const newRange = colorByType(update.newRange, update.updateType);
+    const users = consumers?.get(update.pointer ?? "") ?? [];
+    const usedBy = users.length > 0 ? chalk.dim(`  (used by ${users.join(", ")})`) : "";
 
-    lines.push(` ${name}  ${current}  ${arrow}  ${newRange}`);
+    lines.push(` ${name}  ${current}  ${arrow}  ${newRange}${usedBy}`);
   }
 
   return lines.join("\n");
 }
 
+/**
+ * Outdated indirect dependencies, each followed by the direct dependencies that
+ * pull it in; chains whose range rules out the newer version are marked.
+ */
+export function formatTransitive(updates: TransitiveUpdate[]): string {
+  const nameWidth = Math.max(...updates.map((u) => u.name.length));
+  const installedWidth = Math.max(...updates.map((u) => u.installed.length));
+
+  const lines: string[] = [];
+  for (const update of updates) {
+    const name = update.name.padEnd(nameWidth);
+    const installed = update.installed.padEnd(installedWidth);
+    lines.push(` ${name}  ${installed}  →  ${colorByType(update.latest, update.updateType)}`);
+    for (const chain of update.dependents) {
+      const via = chalk.dim(`   via ${chain.path.join(" > ")}`);
+      const pinned = chain.allowsLatest === false ? chalk.yellow(` (requires ${chain.range})`) : "";
+      lines.push(via + pinned);
+    }
+  }
+  return lines.join("\n");
+}
+
+/** Workspace references whose range excludes the referenced workspace's version */
+export function formatInconsistent(edges: WorkspaceEdge[]): string {
+  const fromWidth = Math.max(...edges.map((e) => e.from.length));
+  return edges
+    .map((edge) => ` ${edge.from.padEnd(fromWidth)}  →  ${edge.to} ${chalk.yellow(edge.specifier)}`)
+    .join("\n");
+}
+
+/** Packages declared with different ranges, and the version --sync aligned them to */
+export function formatDivergences(divergences: Divergence[]): string {
+  const lines: string[] = [];
+  for (const divergence of divergences) {
+    const agreed = divergence.agreed ? chalk.green(`  →  ${divergence.agreed}`) : "";
+    lines.push(` ${divergence.name}${agreed}`);
+    for (const occurrence of divergence.occurrences) {
+      const where = occurrence.workspace || "root";
+      lines.push(chalk.dim(`   ${where}: `) + chalk.yellow(occurrence.range));
+    }
+  }
+  return lines.join("\n");
+}
+
+export function formatInstalled(issues: InstalledIssue[]): string {
+  const nameWidth = Math.max(...issues.map((i) => i.name.length));
+  return issues
+    .map((issue) => {
+      const where = issue.workspace ? chalk.dim(`  (${issue.workspace})`) : "";
+      const name = issue.name.padEnd(nameWidth);
+      switch (issue.kind) {
+        case "missing":
+          return ` ${name}  ${chalk.red("missing")}, wants ${issue.range}${where}`;
+        case "mismatched":
+          return ` ${name}  ${chalk.yellow(issue.installed)} installed, wants ${issue.range}${where}`;
+        default:
+          return ` ${name}  ${chalk.dim(`${issue.installed} extraneous`)}${where}`;
+      }
+    })
+    .join("\n");
+}
+
+export function formatDoctorReport(report: DoctorReport): string {
+  const lines: string[] = [];
+  for (const update of report.passed) {
+    lines.push(`${chalk.green(" ✓")} ${update.name}  ${update.current} → ${update.newRange}`);
+  }
+  for (const { update, output } of report.failed) {
+    lines.push(`${chalk.red(" ✗")} ${update.name}  ${update.current} → ${update.newRange}`);
+    // The end of the output usually names the failing test
+    for (const line of output.trimEnd().split("\n").slice(-5)) {
+      lines.push(chalk.dim(`     ${line}`));
+    }
+  }
+  return lines.join("\n");
+}
+
+export function formatGroups(groups: UpdateGroup[]): string {
+  const lines: string[] = [];
+  for (const group of groups) {
+    const version = group.version
+      ? chalk.green(`  →  ${group.version}`)
+      : chalk.dim("  (no version in common)");
+    const note = group.fellBack ? chalk.yellow("  held back: not every member has the newest version") : "";
+    lines.push(` ${group.name}${version}${note}`);
+    for (const update of group.updates) {
+      lines.push(chalk.dim(`   ${update.name}: `) + `${update.current} → ${update.newRange}`);
+    }
+  }
+  return lines.join("\n");
+}
+
+export function formatAppliedPlan(applied: AppliedPlan): string {
+  const lines = [
+    `Applied ${applied.applied} edit${applied.applied === 1 ? "" : "s"}` +
+      chalk.dim(`, ${applied.unchanged} already up to date`),
+  ];
+  if (applied.conflicts.length > 0) {
+    lines.push(chalk.yellow("Skipped edits whose file no longer holds the planned value:"));
+    for (const edit of applied.conflicts) {
+      lines.push(chalk.dim(`   ${edit.path} ${edit.pointer}: `) + `${edit.oldValue} → ${edit.newValue}`);
+    }
+  }
+  return lines.join("\n");
+}
+
 export function formatJson(updates: UpdateResult[]): string {
   const result: Record<string, string> = {};
   for (const update of updates) {
@@ -1,30 +1,22 @@
-import { execSync } from "node:child_process";
-import type { PackageInfo } from "../../index.js";
+import { createRequire } from "node:module";
+import type { GlobalPackage, PackageInfo } from "../../index.js";
 
-export function getGlobalPackages(): PackageInfo[] {
-  try {
-    const output = execSync("npm ls -g --depth=0 --json", {
-      encoding: "utf-8",
-      stdio: ["pipe", "pipe", "pipe"],
-    });
-
-    const parsed = JSON.parse(output);
-    const deps = parsed.dependencies || {};
-    const packages: PackageInfo[] = [];
+const require = createRequire(import.meta.url);
+const native: {
+  globalPackages: (managers?: string[]) => GlobalPackage[];
+} = require("../../index.cjs");
 
-    for (const [name, info] of Object.entries(deps)) {
-      const version = (info as { version?: string }).version;
-      if (version) {
-        packages.push({
-          name,
-          versionRange: `^${version}`,
-          depType: "prod",
-        });
-      }
-    }
-
-    return packages;
-  } catch {
-    return [];
+/** Globally installed packages, grouped by the package manager that installed them */
+export function getGlobalPackages(): Map<string, PackageInfo[]> {
+  const byManager = new Map<string, PackageInfo[]>();
+  for (const pkg of native.globalPackages()) {
+    const packages = byManager.get(pkg.manager) ?? [];
+    packages.push({
+      name: pkg.name,
+      versionRange: `^${pkg.version}`,
+      depType: "prod",
+    });
+    byManager.set(pkg.manager, packages);
   }
+  return byManager;
 }
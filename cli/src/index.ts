@@ -2,11 +2,42 @@ import * as fs from "node:fs";
 import * as path from "node:path";
 import { createRequire } from "node:module";
 import chalk from "chalk";
-import type { CheckOptions, PackageInfo, CheckResult, UpdateResult } from "../../index.js";
+import type {
+  AppliedPlan,
+  ApplyPlanOptions,
+  CheckOptions,
+  PackageInfo,
+  CheckResult,
+  Divergence,
+  DoctorOptions,
+  DoctorReport,
+  DoctorStep,
+  InstalledIssue,
+  PlanInput,
+  PlanOptions,
+  TransitiveUpdate,
+  UpdateGroup,
+  UpdateResult,
+  ProgressEvent,
+  WorkspaceEdge,
+} from "../../index.js";
 
 const require = createRequire(import.meta.url);
 const native: {
-  checkUpdates: (packages: PackageInfo[], options: CheckOptions) => Promise<CheckResult>;
+  checkUpdates: (
+    packages: PackageInfo[],
+    options: CheckOptions,
+    onProgress?: (event: ProgressEvent) => void,
+    signal?: AbortSignal,
+  ) => Promise<CheckResult>;
+  doctor: (
+    path: string,
+    updates: UpdateResult[],
+    options?: DoctorOptions,
+    onStep?: (step: DoctorStep) => void,
+  ) => Promise<DoctorReport>;
+  exportPlan: (input: PlanInput, options?: PlanOptions) => string;
+  applyPlan: (plan: string, options?: ApplyPlanOptions) => AppliedPlan;
   clearCache: (cacheFile?: string) => void;
 } = require("../../index.cjs");
 const checkUpdates = native.checkUpdates;
@@ -19,6 +50,13 @@ import {
   formatJsonAll,
   formatSummary,
   formatHeader,
+  formatTransitive,
+  formatInconsistent,
+  formatDivergences,
+  formatDoctorReport,
+  formatGroups,
+  formatInstalled,
+  formatAppliedPlan,
 } from "./formatter.js";
 import { getGlobalPackages } from "./global.js";
 import * as spinner from "./spinner.js";
@@ -27,8 +65,9 @@ import {
   findPackageJson,
   extractPackages,
   parseDepTypes,
+  readCatalogPackages,
 } from "./package-reader.js";
-import { writeUpdates } from "./package-writer.js";
+import { writeCatalogUpdates, writeUpdates } from "./package-writer.js";
 import type { CliOptions, DepType } from "./types.js";
 import { discoverWorkspaces } from "./workspace.js";
 
@@ -45,16 +84,55 @@ function detectPackageManager(dir: string): string {
   return "npm";
 }
 
+const LOCKFILES = [
+  "package-lock.json",
+  "npm-shrinkwrap.json",
+  "pnpm-lock.yaml",
+  "yarn.lock",
+  "bun.lock",
+];
+
+/**
+ * The lockfile governing the package in `dir` (searching parent directories, as
+ * workspaces share the root lockfile) and the package's path relative to it.
+ */
+function findLockfile(dir: string): { lockfile: string; lockfileImporter: string } | undefined {
+  for (let current = dir; ; current = path.dirname(current)) {
+    for (const name of LOCKFILES) {
+      const lockfile = path.join(current, name);
+      if (fs.existsSync(lockfile)) {
+        const lockfileImporter = path.relative(current, dir).split(path.sep).join("/");
+        return { lockfile, lockfileImporter };
+      }
+    }
+    if (path.dirname(current) === current) return undefined;
+  }
+}
+
 interface RunTarget {
   label: string;
   packageJsonPath: string;
   packages: PackageInfo[];
+  /** `packageJsonPath` is a pnpm-workspace.yaml whose catalogs are checked */
+  catalog?: boolean;
+  /** Workspaces consuming each catalog entry, by pointer */
+  consumers?: Map<string, string[]>;
+}
+
+interface Targets {
+  targets: RunTarget[];
+  /** References between workspaces whose range excludes the referenced version */
+  inconsistent: WorkspaceEdge[];
 }
 
-function resolveTargets(opts: CliOptions): RunTarget[] | Promise<RunTarget[]> {
+function resolveTargets(opts: CliOptions): Targets | Promise<Targets> {
   if (opts.global) {
-    const packages = getGlobalPackages();
-    return [{ label: "global", packageJsonPath: "", packages }];
+    const targets = [...getGlobalPackages()].map(([manager, packages]) => ({
+      label: `global (${manager})`,
+      packageJsonPath: "",
+      packages,
+    }));
+    return { targets, inconsistent: [] };
   }
 
   const depTypes = parseDepTypes(opts.dep) as DepType[];
@@ -67,22 +145,22 @@ function resolveTargets(opts: CliOptions): RunTarget[] | Promise<RunTarget[]> {
   const pkg = readPackageJson(pkgPath);
   const packages = extractPackages(pkg, depTypes);
 
-  return [{ label: pkgPath, packageJsonPath: pkgPath, packages }];
+  return { targets: [{ label: pkgPath, packageJsonPath: pkgPath, packages }], inconsistent: [] };
 }
 
-async function resolveWorkspaceTargets(
-  opts: CliOptions,
-  depTypes: DepType[],
-): Promise<RunTarget[]> {
+async function resolveWorkspaceTargets(opts: CliOptions, depTypes: DepType[]): Promise<Targets> {
   const rootDir = process.cwd();
-  const workspaces = await discoverWorkspaces(rootDir, opts.workspace);
+  const { workspaces, internal, inconsistent } = await discoverWorkspaces(rootDir, opts.workspace);
   const targets: RunTarget[] = [];
+  // Workspaces reference each other locally; those references are checked for
+  // consistency instead of against the registry
+  const external = (packages: PackageInfo[]) => packages.filter((p) => !internal.has(p.name));
 
   if (opts.root || !opts.workspaces) {
     try {
       const rootPkgPath = findPackageJson(rootDir);
       const rootPkg = readPackageJson(rootPkgPath);
-      const packages = extractPackages(rootPkg, depTypes);
+      const packages = external(extractPackages(rootPkg, depTypes));
       targets.push({
         label: rootPkg.name || "root",
         packageJsonPath: rootPkgPath,
@@ -95,7 +173,7 @@ async function resolveWorkspaceTargets(
 
   for (const ws of workspaces) {
     const pkg = readPackageJson(ws.packageJsonPath);
-    const packages = extractPackages(pkg, depTypes);
+    const packages = external(extractPackages(pkg, depTypes));
     targets.push({
       label: ws.name,
       packageJsonPath: ws.packageJsonPath,
@@ -103,7 +181,27 @@ async function resolveWorkspaceTargets(
     });
   }
 
-  return targets;
+  const catalogPath = path.resolve(rootDir, "pnpm-workspace.yaml");
+  if (!opts.workspace && fs.existsSync(catalogPath)) {
+    // The root may reference catalog entries even when it is not checked
+    const rootPkgPath = path.resolve(rootDir, "package.json");
+    const manifests = [
+      ...(fs.existsSync(rootPkgPath) ? [rootPkgPath] : []),
+      ...workspaces.map((ws) => ws.packageJsonPath),
+    ];
+    const { packages, consumers } = readCatalogPackages(catalogPath, manifests);
+    if (packages.length > 0) {
+      targets.push({
+        label: "pnpm catalogs",
+        packageJsonPath: catalogPath,
+        packages,
+        catalog: true,
+        consumers,
+      });
+    }
+  }
+
+  return { targets, inconsistent };
 }
 
 export async function run(opts: CliOptions): Promise<number> {
@@ -123,9 +221,24 @@ export async function run(opts: CliOptions): Promise<number> {
     console.log();
   }
 
+  // Re-applying a plan needs no registry: its edits carry the new ranges
+  if (mergedOpts.applyPlan) {
+    try {
+      const applied = native.applyPlan(fs.readFileSync(mergedOpts.applyPlan, "utf-8"), {
+        root: process.cwd(),
+        batches: mergedOpts.batch,
+      });
+      console.log(isJsonOutput ? JSON.stringify(applied, null, 2) : formatAppliedPlan(applied));
+      return applied.conflicts.length > 0 ? 1 : 0;
+    } catch (err) {
+      console.error((err as Error).message);
+      return 1;
+    }
+  }
+
   // Resolve targets with spinner
   spinner.startResolving();
-  const targets = await resolveTargets(mergedOpts);
+  const { targets, inconsistent } = await resolveTargets(mergedOpts);
   const totalPackages = targets.reduce((sum, t) => sum + t.packages.length, 0);
   spinner.succeed(
     `Found ${totalPackages} packages across ${targets.length} target${targets.length === 1 ? "" : "s"}`,
@@ -133,6 +246,19 @@ export async function run(opts: CliOptions): Promise<number> {
 
   const checkOptions: CheckOptions = {
     registry: mergedOpts.registry,
+    proxy: mergedOpts.proxy,
+    httpsProxy: mergedOpts.httpsProxy,
+    noProxy: mergedOpts.noProxy,
+    caFile: mergedOpts.caFile,
+    // --no-strictSsl defaults to true; leave it unset so .npmrc can turn it off
+    strictSsl: mergedOpts.strictSsl === false ? false : undefined,
+    certFile: mergedOpts.certFile,
+    keyFile: mergedOpts.keyFile,
+    fallbackRegistries: mergedOpts.fallbackRegistries,
+    failoverOn: mergedOpts.failoverOn,
+    fullMetadata: mergedOpts.fullMetadata,
+    allowedLicenses: mergedOpts.allowedLicenses,
+    checkAdvisories: mergedOpts.checkAdvisories,
     target: mergedOpts.target,
     concurrency: mergedOpts.concurrency,
     timeoutMs: mergedOpts.timeout,
@@ -140,65 +266,200 @@ export async function run(opts: CliOptions): Promise<number> {
     cacheTtlSeconds: mergedOpts.cacheTtl,
     includePrerelease: mergedOpts.pre,
     retries: 3,
+    sync: mergedOpts.sync,
+    syncVersions: mergedOpts.syncVersions,
+    group: mergedOpts.group,
+    groupRules: mergedOpts.groupRules,
   };
 
+  // Ctrl-C aborts outstanding registry requests; fetched packages are still cached
+  const abortController = new AbortController();
+  const onSigint = () => abortController.abort();
+  process.once("SIGINT", onSigint);
+
   let allUpdates: UpdateResult[] = [];
+  let allInRange: UpdateResult[] = [];
+  let allTransitive: TransitiveUpdate[] = [];
+  let allDivergences: Divergence[] = [];
+  let allInstalled: InstalledIssue[] = [];
+  let allGroups: UpdateGroup[] = [];
+  // Updates for --plan, whose `workspace` is the manifest's directory
+  // relative to the working directory
+  let planUpdates: UpdateResult[] = [];
+  let planGroups: UpdateGroup[] = [];
+  let planCatalog: string | undefined;
   let totalChecked = 0;
   let totalTimeMs = 0;
   let totalCacheHits = 0;
   let totalCacheMisses = 0;
   const multiTarget = targets.length > 1;
 
-  for (const target of targets) {
-    const filtered = applyFilters(target.packages, mergedOpts.filter, mergedOpts.reject);
-
-    if (filtered.length === 0) continue;
-    totalChecked += filtered.length;
-
-    // Start checking spinner with simulated per-package progress
-    const targetLabel = multiTarget ? target.label : "";
-    spinner.startChecking(targetLabel, filtered.length);
-
-    let progressIndex = 0;
-    const progressInterval = setInterval(() => {
-      progressIndex = (progressIndex + 1) % filtered.length;
-      spinner.updatePackageProgress(
-        progressIndex + 1,
-        filtered.length,
-        filtered[progressIndex].name,
+  // Workspaces are checked together, so packages they share are fetched once
+  // and ranges that differ between them are reported (and aligned by --sync)
+  const workspaceTargets = targets.filter((t) => t.packageJsonPath && !t.catalog);
+  const runs: RunTarget[][] =
+    multiTarget && workspaceTargets.length > 1
+      ? [workspaceTargets, ...targets.filter((t) => !workspaceTargets.includes(t)).map((t) => [t])]
+      : targets.map((t) => [t]);
+
+  for (const run of runs) {
+    const combined = run.length > 1;
+    const entries = run.map((target) => {
+      const dir = path.dirname(target.packageJsonPath);
+      const lockfile =
+        target.packageJsonPath && !target.catalog ? findLockfile(dir) : undefined;
+      // Packages are told apart by their directory relative to the lockfile,
+      // which also selects their entries in it
+      const workspace = combined
+        ? (lockfile?.lockfileImporter ?? path.relative(process.cwd(), dir).split(path.sep).join("/"))
+        : undefined;
+      const packages = applyFilters(target.packages, mergedOpts.filter, mergedOpts.reject).map(
+        (pkg) => (combined ? { ...pkg, workspace } : pkg),
       );
-    }, 150);
+      return { target, lockfile, workspace, packages };
+    });
+    const packages = entries.flatMap((entry) => entry.packages);
+
+    if (packages.length === 0) continue;
+    totalChecked += packages.length;
+
+    // Start checking spinner, driven by progress events from the native module
+    const targetLabel = combined ? "workspaces" : multiTarget ? run[0].label : "";
+    spinner.startChecking(targetLabel, packages.length);
+
+    const lockfile = entries[0].lockfile;
+    // Workspace packages resolve their node_modules below the directory their
+    // `workspace` is relative to
+    let installRoot: string | undefined;
+    if (combined) {
+      installRoot = lockfile ? path.dirname(lockfile.lockfile) : process.cwd();
+    } else if (run[0].packageJsonPath && !run[0].catalog) {
+      installRoot = path.dirname(run[0].packageJsonPath);
+    }
+    const result = await checkUpdates(
+      packages,
+      {
+        ...checkOptions,
+        ...(combined ? { lockfile: lockfile?.lockfile } : lockfile),
+        includeTransitive: mergedOpts.transitive && !!lockfile,
+        installRoot: mergedOpts.installed ? installRoot : undefined,
+      },
+      (event) => {
+        spinner.updatePackageProgress(event);
+      },
+      abortController.signal,
+    );
+
+    if (result.cancelled) {
+      spinner.fail("Cancelled");
+      return 130;
+    }
 
-    const result = await checkUpdates(filtered, checkOptions);
-    clearInterval(progressInterval);
     totalTimeMs += result.totalTimeMs;
     totalCacheHits += result.cacheHits;
     totalCacheMisses += result.cacheMisses;
 
     spinner.succeed(
-      `Checked ${filtered.length} packages (${result.cacheMisses} fetched, ${result.cacheHits} from cache)`,
+      `Checked ${packages.length} packages (${result.cacheMisses} fetched, ${result.cacheHits} from cache)`,
     );
 
-    if (multiTarget && result.updates.length > 0 && !isJsonOutput) {
-      console.log(`\n${target.label}`);
-    }
+    for (const { target, lockfile, workspace } of entries) {
+      const updates = combined
+        ? result.updates.filter((u) => u.workspace === workspace)
+        : result.updates;
 
-    if (!isJsonOutput) {
-      if (result.updates.length > 0) {
-        console.log(formatTable(result.updates));
+      if (multiTarget && updates.length > 0 && !isJsonOutput) {
+        console.log(`\n${target.label}`);
       }
-    }
 
-    if (mergedOpts.upgrade && target.packageJsonPath && result.updates.length > 0) {
-      writeUpdates(target.packageJsonPath, result.updates);
       if (!isJsonOutput) {
-        const pm = detectPackageManager(path.dirname(target.packageJsonPath));
-        console.log(`\nUpdated ${target.packageJsonPath}`);
-        console.log(chalk.cyan(`Run ${chalk.bold(`${pm} install`)} to install new versions`));
+        if (updates.length > 0) {
+          console.log(formatTable(updates, target.consumers));
+        }
       }
+
+      if (mergedOpts.doctor && target.packageJsonPath && !target.catalog && updates.length > 0) {
+        // Commands run where the lockfile is, so workspace installs update it
+        const cwd = lockfile ? path.dirname(lockfile.lockfile) : path.dirname(target.packageJsonPath);
+        const pm = detectPackageManager(cwd);
+        spinner.startDoctor(multiTarget ? target.label : "");
+        try {
+          const report = await native.doctor(
+            target.packageJsonPath,
+            updates,
+            {
+              installCommand: mergedOpts.doctorInstall ?? `${pm} install`,
+              testCommand: mergedOpts.doctorTest ?? `${pm} test`,
+              cwd,
+              strategy: mergedOpts.doctorStrategy,
+            },
+            (step) => spinner.updateDoctorStep(step),
+          );
+          spinner.succeed(
+            `Kept ${report.passed.length} of ${updates.length} upgrades (${report.runs} test runs)`,
+          );
+          if (!isJsonOutput) {
+            console.log(formatDoctorReport(report));
+          }
+        } catch (err) {
+          spinner.fail("Doctor stopped");
+          console.error((err as Error).message);
+          return 1;
+        }
+        continue;
+      }
+
+      if (mergedOpts.upgrade && target.packageJsonPath && updates.length > 0) {
+        if (target.catalog) {
+          writeCatalogUpdates(target.packageJsonPath, updates);
+        } else {
+          writeUpdates(target.packageJsonPath, updates);
+        }
+        if (!isJsonOutput) {
+          const pm = detectPackageManager(path.dirname(target.packageJsonPath));
+          console.log(`\nUpdated ${target.packageJsonPath}`);
+          console.log(chalk.cyan(`Run ${chalk.bold(`${pm} install`)} to install new versions`));
+        }
+      }
+    }
+
+    // Catalog updates name the workspaces they reach
+    const consumers = run[0].consumers;
+    allUpdates = allUpdates.concat(
+      consumers
+        ? result.updates.map((u) => ({ ...u, consumers: consumers.get(u.pointer ?? "") ?? [] }))
+        : result.updates,
+    );
+    allInRange = allInRange.concat(result.inRange);
+    allTransitive = allTransitive.concat(result.transitive);
+    allDivergences = allDivergences.concat(result.divergences);
+    allInstalled = allInstalled.concat(result.installed);
+    allGroups = allGroups.concat(result.groups);
+
+    if (mergedOpts.plan && run[0].packageJsonPath) {
+      const relative = (file: string) =>
+        path.relative(process.cwd(), file).split(path.sep).join("/");
+      const locate = (update: UpdateResult): UpdateResult => {
+        const entry = combined ? entries.find((e) => e.workspace === update.workspace) : entries[0];
+        if (!entry || entry.target.catalog) return { ...update, workspace: undefined };
+        return { ...update, workspace: relative(path.dirname(entry.target.packageJsonPath)) };
+      };
+      if (run[0].catalog) planCatalog = relative(run[0].packageJsonPath);
+      planUpdates = planUpdates.concat(result.updates.map(locate));
+      planGroups = planGroups.concat(
+        result.groups.map((group) => ({ ...group, updates: group.updates.map(locate) })),
+      );
     }
+  }
 
-    allUpdates = allUpdates.concat(result.updates);
+  process.removeListener("SIGINT", onSigint);
+
+  if (mergedOpts.plan) {
+    const plan = native.exportPlan(
+      { updates: planUpdates, groups: planGroups },
+      { catalog: planCatalog },
+    );
+    fs.writeFileSync(mergedOpts.plan, `${plan}\n`);
   }
 
   // Output
@@ -210,10 +471,38 @@ export async function run(opts: CliOptions): Promise<number> {
     if (allUpdates.length === 0 && totalChecked > 0) {
       console.log(formatTable([]));
     }
+    if (allInRange.length > 0) {
+      const pm = detectPackageManager(process.cwd());
+      console.log(`\nAllowed by current ranges; run ${chalk.bold(`${pm} update`)} to install:`);
+      console.log(formatTable(allInRange));
+    }
+    if (allDivergences.length > 0) {
+      console.log("\nRanges that differ between workspaces:");
+      console.log(formatDivergences(allDivergences));
+    }
+    if (inconsistent.length > 0) {
+      console.log("\nWorkspace references that do not match the referenced version:");
+      console.log(formatInconsistent(inconsistent));
+    }
+    if (allGroups.length > 0) {
+      console.log("\nUpgraded together:");
+      console.log(formatGroups(allGroups));
+    }
+    if (allInstalled.length > 0) {
+      console.log("\nInstalled packages that do not match package.json:");
+      console.log(formatInstalled(allInstalled));
+    }
+    if (allTransitive.length > 0) {
+      console.log("\nOutdated indirect dependencies:");
+      console.log(formatTransitive(allTransitive));
+    }
     console.log(
       formatSummary(totalChecked, allUpdates.length, totalTimeMs, totalCacheHits, totalCacheMisses),
     );
-    if (allUpdates.length > 0 && !mergedOpts.upgrade) {
+    if (mergedOpts.plan) {
+      console.log(`\nWrote update plan to ${mergedOpts.plan}`);
+    }
+    if (allUpdates.length > 0 && !mergedOpts.upgrade && !mergedOpts.doctor) {
       console.log("\nRun turbo-ncu --upgrade to update your package.json");
     }
   }
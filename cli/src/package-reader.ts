@@ -1,8 +1,14 @@
 import * as fs from "node:fs";
 import * as path from "node:path";
-import type { PackageInfo } from "../../index.js";
-import type { PackageJson, DepType, PackageJsonDeps } from "./types.js";
-import { DEP_TYPE_MAP } from "./types.js";
+import { createRequire } from "node:module";
+import type { CatalogEntry, Manifest, PackageInfo } from "../../index.js";
+import type { PackageJson, DepType } from "./types.js";
+
+const require = createRequire(import.meta.url);
+const native: {
+  parseManifest: (text: string) => Manifest;
+  readCatalogs: (path: string, manifests?: string[]) => CatalogEntry[];
+} = require("../../index.cjs");
 
 export function readPackageJson(filePath: string): PackageJson {
   const content = fs.readFileSync(filePath, "utf-8");
@@ -18,37 +24,46 @@ export function findPackageJson(dir?: string): string {
   return filePath;
 }
 
+/**
+ * Dependencies of the requested types that can be checked against the registry.
+ * Specifiers are classified natively; git, file, link, workspace, catalog, tag
+ * and URL specifiers are skipped, and `npm:` aliases are kept as written.
+ */
 export function extractPackages(pkg: PackageJson, depTypes: DepType[]): PackageInfo[] {
-  const packages: PackageInfo[] = [];
-
-  for (const depType of depTypes) {
-    const key = DEP_TYPE_MAP[depType];
-    const deps = pkg[key] as PackageJsonDeps | undefined;
-    if (!deps) continue;
-
-    for (const [name, versionRange] of Object.entries(deps)) {
-      // Skip non-registry ranges (URLs, file:, git:, etc.)
-      if (
-        versionRange.startsWith("file:") ||
-        versionRange.startsWith("git:") ||
-        versionRange.startsWith("git+") ||
-        versionRange.startsWith("github:") ||
-        versionRange.startsWith("http:") ||
-        versionRange.startsWith("https:") ||
-        versionRange.includes("/")
-      ) {
-        continue;
-      }
+  const { dependencies } = native.parseManifest(JSON.stringify(pkg));
+  return dependencies
+    .filter((dep) => dep.checkable && depTypes.includes(dep.depType as DepType))
+    .sort((a, b) => depTypes.indexOf(a.depType as DepType) - depTypes.indexOf(b.depType as DepType))
+    .map((dep) => ({
+      name: dep.name,
+      versionRange: dep.specifier,
+      depType: dep.depType,
+      pointer: dep.pointer,
+    }));
+}
 
-      packages.push({
-        name,
-        versionRange,
-        depType,
-      });
-    }
-  }
+export interface CatalogPackages {
+  packages: PackageInfo[];
+  /** Names of the workspaces referencing each entry, by the entry's pointer */
+  consumers: Map<string, string[]>;
+}
 
-  return packages;
+/**
+ * Entries of the pnpm catalogs in `workspaceFile`, as packages to check, with
+ * the workspaces among `manifests` (package.json paths) that consume each.
+ * Each entry is checked once however many workspaces reference it.
+ */
+export function readCatalogPackages(workspaceFile: string, manifests: string[] = []): CatalogPackages {
+  const entries = native.readCatalogs(workspaceFile, manifests);
+  return {
+    packages: entries.map((entry) => ({
+      name: entry.name,
+      versionRange: entry.specifier,
+      depType: "catalog",
+      pointer: entry.pointer,
+    })),
+    consumers: new Map(entries.map((entry) => [entry.pointer, entry.consumers])),
+  };
 }
 
 export function parseDepTypes(depOption: string[]): DepType[] {
@@ -56,7 +71,8 @@ export function parseDepTypes(depOption: string[]): DepType[] {
     return ["prod", "dev", "peer", "optional"];
   }
 
-  const valid: DepType[] = ["prod", "dev", "peer", "optional"];
+  // Overrides pin transitive versions on purpose, so they are only checked on request
+  const valid: DepType[] = ["prod", "dev", "peer", "optional", "override"];
   const result: DepType[] = [];
 
   for (const d of depOption) {
@@ -69,5 +85,5 @@ export function parseDepTypes(depOption: string[]): DepType[] {
     }
   }
 
-  return result.length > 0 ? result : valid;
+  return result.length > 0 ? result : ["prod", "dev", "peer", "optional"];
 }
@@ -1,33 +1,25 @@
-import * as fs from "node:fs";
+import { createRequire } from "node:module";
 import type { UpdateResult } from "../../index.js";
-import type { PackageJson } from "./types.js";
-import { DEP_TYPE_MAP, type DepType } from "./types.js";
 
-export function writeUpdates(filePath: string, updates: UpdateResult[]): void {
-  const raw = fs.readFileSync(filePath, "utf-8");
+const require = createRequire(import.meta.url);
+const native: {
+  applyUpdates: (path: string, updates: UpdateResult[]) => number;
+  applyCatalogUpdates: (path: string, updates: UpdateResult[]) => number;
+} = require("../../index.cjs");
 
-  // Detect indentation
-  const indentMatch = raw.match(/^(\s+)"/m);
-  const indent = indentMatch ? indentMatch[1] : "  ";
-
-  // Parse and update
-  const pkg: PackageJson = JSON.parse(raw);
-
-  for (const update of updates) {
-    const depType = update.depType as DepType;
-    const key = DEP_TYPE_MAP[depType];
-    const deps = pkg[key] as Record<string, string> | undefined;
-    if (deps && deps[update.name] !== undefined) {
-      deps[update.name] = update.newRange;
-    }
-  }
-
-  // Detect trailing newline
-  const trailingNewline = raw.endsWith("\n");
-  let output = JSON.stringify(pkg, null, indent);
-  if (trailingNewline) {
-    output += "\n";
-  }
+/**
+ * Write the new ranges into a package.json. Only the version strings change, so
+ * indentation, key order, line endings and escaped characters are preserved.
+ * Returns the number of ranges rewritten.
+ */
+export function writeUpdates(filePath: string, updates: UpdateResult[]): number {
+  return native.applyUpdates(filePath, updates);
+}
 
-  fs.writeFileSync(filePath, output, "utf-8");
+/**
+ * Write upgraded catalog entries back into pnpm-workspace.yaml, keeping comments
+ * and quoting. Returns the number of specifiers rewritten.
+ */
+export function writeCatalogUpdates(filePath: string, updates: UpdateResult[]): number {
+  return native.applyCatalogUpdates(filePath, updates);
 }
@@ -1,4 +1,5 @@
 import ora, { type Ora } from "ora";
+import type { DoctorStep, ProgressEvent } from "../../index.js";
 
 let spinner: Ora | null = null;
 let silent = false;
@@ -22,9 +23,45 @@ export function startChecking(label: string, count: number): void {
   spinner = ora(msg).start();
 }
 
-export function updatePackageProgress(current: number, total: number, name: string): void {
+export function updatePackageProgress(event: ProgressEvent): void {
   if (silent || !spinner) return;
-  spinner.text = `Checking [${current}/${total}] ${name}...`;
+  const counter = `[${event.completed}/${event.total}]`;
+  switch (event.kind) {
+    case "start":
+      spinner.text = `Checking ${counter} fetching ${event.name}...`;
+      break;
+    case "retry":
+      spinner.text = `Checking ${counter} retrying ${event.name} (attempt ${event.attempt})...`;
+      break;
+    case "failure":
+      spinner.text = `Checking ${counter} failed to fetch ${event.name}`;
+      break;
+    default:
+      spinner.text = `Checking ${counter} ${event.name}...`;
+  }
+}
+
+export function startDoctor(label: string): void {
+  if (silent) return;
+  spinner = ora(label ? `Running doctor on ${label}...` : "Running doctor...").start();
+}
+
+export function updateDoctorStep(step: DoctorStep): void {
+  // Only starts are shown; the report lists the outcomes
+  if (silent || !spinner || step.passed != null) return;
+  switch (step.kind) {
+    case "baseline":
+      spinner.text = "Installing and testing before upgrading...";
+      break;
+    case "all":
+      spinner.text = `Testing all ${step.packages.length} upgrades...`;
+      break;
+    case "restore":
+      spinner.text = "Installing the upgrades that passed...";
+      break;
+    default:
+      spinner.text = `Testing ${step.packages.join(", ")}...`;
+  }
 }
 
 export function succeed(msg: string): void {
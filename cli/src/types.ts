@@ -1,6 +1,8 @@
+import type { GroupRule } from "../../index.js";
+
 export interface CliOptions {
   upgrade: boolean;
-  target: "latest" | "minor" | "patch" | "semver";
+  target: "latest" | "minor" | "patch" | "semver" | "security";
   filter?: string;
   reject?: string;
   dep: string[];
@@ -8,10 +10,42 @@ export interface CliOptions {
   cacheTtl: number;
   concurrency: number;
   registry?: string;
+  proxy?: string;
+  httpsProxy?: string;
+  noProxy?: string;
+  caFile?: string;
+  strictSsl?: boolean;
+  certFile?: string;
+  keyFile?: string;
+  fallbackRegistries?: string[];
+  failoverOn?: string[];
+  fullMetadata: boolean;
+  allowedLicenses?: string[];
+  checkAdvisories: boolean;
   pre: boolean;
   workspaces: boolean;
   workspace?: string;
   root: boolean;
+  transitive: boolean;
+  installed: boolean;
+  group: boolean;
+  /** Packages always upgraded together; set in the config file */
+  groupRules?: GroupRule[];
+  doctor: boolean;
+  /** Install command for `--doctor`; defaults to the package manager's install */
+  doctorInstall?: string;
+  /** Test command for `--doctor`; defaults to the package manager's test script */
+  doctorTest?: string;
+  doctorStrategy?: "iterate" | "bisect";
+  /** File to write the update plan to */
+  plan?: string;
+  /** Update plan to apply instead of checking */
+  applyPlan?: string;
+  /** Batches of `applyPlan` to apply; defaults to all */
+  batch?: string[];
+  sync?: "highest" | "latest" | "pinned";
+  /** Versions for `--sync pinned`, by package name; set in the config file */
+  syncVersions?: Record<string, string>;
   global: boolean;
   json: boolean;
   jsonAll: boolean;
@@ -36,11 +70,4 @@ export interface PackageJson {
   [key: string]: unknown;
 }
 
-export type DepType = "prod" | "dev" | "peer" | "optional";
-
-export const DEP_TYPE_MAP: Record<DepType, keyof PackageJson> = {
-  prod: "dependencies",
-  dev: "devDependencies",
-  peer: "peerDependencies",
-  optional: "optionalDependencies",
-};
+export type DepType = "prod" | "dev" | "peer" | "optional" | "override";
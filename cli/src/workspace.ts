@@ -1,7 +1,10 @@
-import * as fs from "node:fs";
-import * as path from "node:path";
-import { glob } from "glob";
-import type { PackageJson } from "./types.js";
+import { createRequire } from "node:module";
+import type { WorkspaceEdge, WorkspaceGraph } from "../../index.js";
+
+const require = createRequire(import.meta.url);
+const native: {
+  discoverWorkspaces: (root: string) => WorkspaceGraph;
+} = require("../../index.cjs");
 
 export interface WorkspaceInfo {
   name: string;
@@ -9,84 +12,41 @@ export interface WorkspaceInfo {
   packageJsonPath: string;
 }
 
+export interface Workspaces {
+  workspaces: WorkspaceInfo[];
+  /** Names of every package in the monorepo, root included */
+  internal: Set<string>;
+  /** References between workspaces whose range excludes the referenced version */
+  inconsistent: WorkspaceEdge[];
+}
+
 export async function discoverWorkspaces(
   rootDir: string,
   specificWorkspace?: string,
-): Promise<WorkspaceInfo[]> {
-  const rootPkgPath = path.resolve(rootDir, "package.json");
-  if (!fs.existsSync(rootPkgPath)) {
-    return [];
-  }
-
-  const rootPkg: PackageJson = JSON.parse(fs.readFileSync(rootPkgPath, "utf-8"));
-
-  // Get workspace patterns
-  let patterns: string[] = [];
-
-  // npm/yarn workspaces field
-  if (rootPkg.workspaces) {
-    if (Array.isArray(rootPkg.workspaces)) {
-      patterns = rootPkg.workspaces;
-    } else if (
-      rootPkg.workspaces &&
-      typeof rootPkg.workspaces === "object" &&
-      "packages" in rootPkg.workspaces
-    ) {
-      patterns = (rootPkg.workspaces as { packages: string[] }).packages;
-    }
-  }
-
-  // pnpm-workspace.yaml
-  if (patterns.length === 0) {
-    const pnpmPath = path.resolve(rootDir, "pnpm-workspace.yaml");
-    if (fs.existsSync(pnpmPath)) {
-      try {
-        const yaml = await import("js-yaml");
-        const content = fs.readFileSync(pnpmPath, "utf-8");
-        const parsed = yaml.load(content) as { packages?: string[] };
-        if (parsed?.packages) {
-          patterns = parsed.packages;
-        }
-      } catch {
-        // ignore parse errors
-      }
-    }
-  }
-
-  if (patterns.length === 0) {
-    return [];
-  }
+): Promise<Workspaces> {
+  const graph = native.discoverWorkspaces(rootDir);
 
-  // Resolve globs to workspace directories
   const workspaces: WorkspaceInfo[] = [];
-
-  for (const pattern of patterns) {
-    const matches = await glob(pattern, {
-      cwd: rootDir,
-      absolute: false,
-    });
-
-    for (const match of matches) {
-      const wsDir = path.resolve(rootDir, match);
-      const wsPkgPath = path.resolve(wsDir, "package.json");
-
-      if (!fs.existsSync(wsPkgPath)) continue;
-
-      const wsPkg: PackageJson = JSON.parse(fs.readFileSync(wsPkgPath, "utf-8"));
-      const name = wsPkg.name || match;
-
-      // If specific workspace requested, filter
-      if (specificWorkspace && name !== specificWorkspace) {
-        continue;
-      }
-
-      workspaces.push({
-        name,
-        dir: wsDir,
-        packageJsonPath: wsPkgPath,
-      });
+  for (const pkg of graph.packages) {
+    // The root is a target of its own
+    if (pkg.path === "") continue;
+    // If specific workspace requested, filter
+    if (specificWorkspace && pkg.name !== specificWorkspace && pkg.path !== specificWorkspace) {
+      continue;
     }
+    workspaces.push({
+      name: pkg.name,
+      dir: pkg.dir,
+      packageJsonPath: pkg.manifestPath,
+    });
   }
 
-  return workspaces;
+  const selected = new Set(workspaces.map((ws) => ws.name));
+  return {
+    workspaces,
+    internal: new Set(graph.packages.map((pkg) => pkg.name)),
+    inconsistent: graph.edges.filter(
+      (edge) => !edge.consistent && (!specificWorkspace || selected.has(edge.from)),
+    ),
+  };
 }
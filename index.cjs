@@ -309,7 +309,19 @@ if (!nativeBinding) {
   throw new Error(`Failed to load native binding`)
 }
 
-const { checkUpdates, clearCache } = nativeBinding
+const { checkUpdates, streamUpdates, applyUpdates, doctor, readManifest, parseManifest, readCatalogs, applyCatalogUpdates, discoverWorkspaces, checkInstalled, globalPackages, exportPlan, applyPlan, clearCache } = nativeBinding
 
 module.exports.checkUpdates = checkUpdates
+module.exports.streamUpdates = streamUpdates
+module.exports.applyUpdates = applyUpdates
+module.exports.doctor = doctor
+module.exports.readManifest = readManifest
+module.exports.parseManifest = parseManifest
+module.exports.readCatalogs = readCatalogs
+module.exports.applyCatalogUpdates = applyCatalogUpdates
+module.exports.discoverWorkspaces = discoverWorkspaces
+module.exports.checkInstalled = checkInstalled
+module.exports.globalPackages = globalPackages
+module.exports.exportPlan = exportPlan
+module.exports.applyPlan = applyPlan
 module.exports.clearCache = clearCache
@@ -5,6 +5,10 @@ export interface PackageInfo {
   name: string;
   versionRange: string;
   depType: string;
+  /** JSON pointer to the range in package.json; needed to rewrite entries outside the dependency sections, such as overrides */
+  pointer?: string;
+  /** Directory of the package's manifest relative to the lockfile, when one check covers several manifests. Overrides `lockfileImporter` and tells the occurrences of divergent ranges apart */
+  workspace?: string;
 }
 
 export interface CheckOptions {
@@ -16,29 +20,448 @@ export interface CheckOptions {
   cacheTtlSeconds?: number;
   includePrerelease?: boolean;
   retries?: number;
+  /** Proxy for http:// registries; defaults to npm's `proxy` setting, then `HTTP_PROXY` */
+  proxy?: string;
+  /** Proxy for https:// registries; defaults to npm's `https-proxy` setting, then `HTTPS_PROXY`, then `proxy` */
+  httpsProxy?: string;
+  /** Comma-separated hosts that bypass the proxy; defaults to npm's `noproxy` setting, then `NO_PROXY` */
+  noProxy?: string;
+  /** PEM bundle of extra trusted CA certificates; defaults to npm's `cafile` */
+  caFile?: string;
+  /** Set to false to skip TLS certificate validation; defaults to npm's `strict-ssl`, then true */
+  strictSsl?: boolean;
+  /** PEM client certificate for mutual TLS; requires `keyFile` */
+  certFile?: string;
+  /** PEM private key matching `certFile` */
+  keyFile?: string;
+  /** Registries tried in order after `registry` when it fails */
+  fallbackRegistries?: string[];
+  /** Failures that move on to the next registry: "5xx", "timeout", "404", "429". Defaults to ["5xx", "timeout", "404"] */
+  failoverOn?: string[];
+  /** "record" saves every registry response to `fixtureDir`; "replay" serves them back without touching the network */
+  fixtureMode?: string;
+  /** Directory of recorded registry responses, one JSON file per request */
+  fixtureDir?: string;
+  /** Fetch full packuments so each update carries per-version `metadata`; responses are several times larger */
+  fullMetadata?: boolean;
+  /** SPDX identifiers or expressions an upgrade may move to; versions under any other (or no) license are skipped. Implies `fullMetadata` */
+  allowedLicenses?: string[];
+  /** Look up security advisories for the current and target versions via the registry's bulk advisory endpoint. Implied by the "security" target, which picks the lowest version no advisory affects */
+  checkAdvisories?: boolean;
+  /** package-lock.json, pnpm-lock.yaml, yarn.lock or bun.lock to read installed versions from; updates are then classified against those */
+  lockfile?: string;
+  /** Directory of the checked package relative to the lockfile, for workspaces. Defaults to the root */
+  lockfileImporter?: string;
+  /** Also report outdated packages installed below the direct dependencies; requires `lockfile` */
+  includeTransitive?: boolean;
+  /** Align every package several manifests depend on to one version: "highest" (the highest version any of them declares), "latest" or "pinned" (from `syncVersions`) */
+  sync?: string;
+  /** Versions for the "pinned" sync policy, by package name */
+  syncVersions?: Record<string, string>;
+  /** Project directory whose node_modules are compared with the declared ranges; packages with a `workspace` resolve from that directory below it */
+  installRoot?: string;
+  /** Batch updates of related packages into `groups`: packages with the same scope or, with `fullMetadata`, the same repository. Members that cannot all reach the newest version move to the highest version they share */
+  group?: boolean;
+  /** Packages that are always upgraded together; implies `group` */
+  groupRules?: GroupRule[];
+}
+
+export interface GroupRule {
+  name: string;
+  /** Package names; `*` matches any run of characters, as in "@babel/*" */
+  packages: string[];
 }
 
 export interface UpdateResult {
   name: string;
   current: string;
   currentVersion: string;
+  /** Version the lockfile has installed; only set with `lockfile` */
+  installedVersion?: string;
+  /** Whether the target lies outside the declared range, so the manifest must change; otherwise a lockfile refresh (`npm update`) is enough. Always set by checks; unset counts as true */
+  requiresManifestChange?: boolean;
   latest: string;
   newRange: string;
   updateType: string;
   depType: string;
+  /** Location of the range in package.json, copied from the package */
+  pointer?: string;
+  /** Workspace of the package, copied from it */
+  workspace?: string;
+  /** Registry that supplied the version list */
+  registry?: string;
+  /** Details of the target version, when its packument was fetched in full (see `fullMetadata`) */
+  metadata?: VersionDetails;
+  /** License of the version the current range resolves to */
+  licenseFrom?: string;
+  /** License of the target version */
+  licenseTo?: string;
+  /** Whether the upgrade changes the license; only set when license metadata was fetched */
+  licenseChanged?: boolean;
+  /** Advisories affecting the current version but not the target; only set with `checkAdvisories` */
+  fixedVulnerabilities?: Vulnerability[];
+  /** Advisories affecting the target version but not the current one */
+  introducedVulnerabilities?: Vulnerability[];
+}
+
+export interface Vulnerability {
+  id: number;
+  title: string;
+  /** "low", "moderate", "high" or "critical" */
+  severity: string;
+  url: string;
+  /** Affected range, e.g. "<4.17.21" */
+  vulnerableVersions: string;
+}
+
+/** Per-version metadata only present in full packuments */
+export interface VersionDetails {
+  /** Publish time as an ISO 8601 timestamp */
+  published?: string;
+  /** Repository URL */
+  repository?: string;
+  homepage?: string;
+  /** SPDX expression, or the legacy `licenses` list joined with " OR " */
+  license?: string;
+  /** Deprecation message */
+  deprecated?: string;
+  engines?: Record<string, string>;
+  peerDependencies?: Record<string, string>;
+  unpackedSize?: number;
+  integrity?: string;
 }
 
 export interface CheckResult {
   updates: UpdateResult[];
+  /** Updates the declared range already allows, for packages whose installed version is known from `lockfile`; installing them only needs a lockfile refresh */
+  inRange: UpdateResult[];
   cacheHits: number;
   cacheMisses: number;
   fetchTimeMs: number;
   totalTimeMs: number;
+  /** True when the check was aborted before every package was fetched */
+  cancelled: boolean;
+  /** Outdated indirect dependencies; only filled with `includeTransitive` */
+  transitive: TransitiveUpdate[];
+  /** Packages declared with different ranges across the checked manifests */
+  divergences: Divergence[];
+  /** Differences between node_modules and the declared ranges; only filled with `installRoot` */
+  installed: InstalledIssue[];
+  /** Updates of related packages, moved to one shared version; only filled with `group` or `groupRules`. `streamUpdates` emits updates before they are grouped */
+  groups: UpdateGroup[];
+}
+
+export interface UpdateGroup {
+  /** Rule name, scope (e.g. "@babel") or repository URL */
+  name: string;
+  /** "rule", "repository" or "scope" */
+  source: string;
+  /** Version every member moves to; unset when they share none, in which case each keeps its own target */
+  version?: string;
+  /** Whether some member cannot reach the highest target of the group, so all of them move to a lower common version */
+  fellBack: boolean;
+  /** Updates of the members, also listed in `updates` or `inRange` */
+  updates: UpdateResult[];
+}
+
+export interface InstalledIssue {
+  name: string;
+  /** "missing", "mismatched" (installed outside the declared range) or "extraneous" (installed, but nothing checked depends on it) */
+  kind: string;
+  /** Declared range; not set for extraneous packages */
+  range?: string;
+  /** Version found on disk; not set for missing packages */
+  installed?: string;
+  depType?: string;
+  workspace?: string;
+  /** Directory the package is installed in, or would be */
+  path: string;
+}
+
+export interface Divergence {
+  name: string;
+  occurrences: Occurrence[];
+  /** Version every occurrence is aligned to; only set with `sync` */
+  agreed?: string;
+}
+
+export interface Occurrence {
+  workspace?: string;
+  range: string;
+  depType: string;
+}
+
+export interface TransitiveUpdate {
+  name: string;
+  installed: string;
+  latest: string;
+  updateType: string;
+  /** How the installed copy is reached from the checked package's direct dependencies */
+  dependents: DependencyChain[];
+}
+
+export interface DependencyChain {
+  /** `name@version` of each package from a direct dependency down to the one that depends on this package */
+  path: string[];
+  /** Range that last package declares; pnpm lockfiles do not record it */
+  range?: string;
+  /** Whether that range allows `latest`; when false, the first package of `path` has to be upgraded to get it */
+  allowsLatest?: boolean;
+}
+
+export interface ProgressEvent {
+  /** One of "cache-hit", "start", "retry", "success" or "failure" */
+  kind: string;
+  name: string;
+  completed: number;
+  total: number;
+  attempt: number;
+  bytes: number;
+}
+
+/** One dependency entry of a package.json */
+export interface ManifestDependency {
+  /** Key in the manifest; for aliases this is the alias, not the package, and for overrides the full selector such as "foo>bar" or "pkg@^2" */
+  name: string;
+  /** Value as written, e.g. "^1.2.3" or "npm:strip-ansi@^6.0.1" */
+  specifier: string;
+  /** "prod", "dev", "peer", "optional", "override", "bundle" or "devEngines" */
+  depType: string;
+  /** JSON pointer to the specifier in the manifest */
+  pointer: string;
+  /** "registry", "alias", "git", "file", "link", "workspace", "catalog", "tag" or "url" */
+  kind: string;
+  /** Registry package to look up, when checkable */
+  packageName?: string;
+  /** Semver range to resolve, when checkable */
+  range?: string;
+  /** Whether `checkUpdates` can look this dependency up */
+  checkable: boolean;
+}
+
+export interface Manifest {
+  name?: string;
+  version?: string;
+  /** Every dependency, in section then source order */
+  dependencies: ManifestDependency[];
+}
+
+/** One entry of a pnpm catalog */
+export interface CatalogEntry {
+  /** Catalog name; "default" for the top-level `catalog:` */
+  catalog: string;
+  name: string;
+  specifier: string;
+  /** Location in pnpm-workspace.yaml, e.g. "/catalogs/react17/react"; pass it on as the package's `pointer` so upgrades can be written back */
+  pointer: string;
+  /** Names of the workspaces that reference this entry */
+  consumers: string[];
+}
+
+export interface WorkspaceGraph {
+  /** Directory discovery started from */
+  root: string;
+  /** Which configurations declared workspaces: "npm" (package.json `workspaces`, also used by yarn and bun), "pnpm", "lerna", "nx" or "rush" */
+  sources: string[];
+  /** The root package, when it has a package.json, and every workspace, ordered by path */
+  packages: WorkspacePackage[];
+  /** Dependencies of one workspace on another */
+  edges: WorkspaceEdge[];
+}
+
+export interface WorkspacePackage {
+  /** Package name, or its path when the manifest has none */
+  name: string;
+  /** Directory relative to the root with `/` separators; "" for the root */
+  path: string;
+  dir: string;
+  manifestPath: string;
+  version?: string;
+}
+
+export interface WorkspaceEdge {
+  from: string;
+  to: string;
+  /** Value as written, e.g. "workspace:^" or "^1.2.0" */
+  specifier: string;
+  depType: string;
+  /** JSON pointer to the specifier in the dependent's manifest */
+  pointer: string;
+  /** Whether the specifier allows the workspace's current version, so the package manager links it instead of installing from the registry */
+  consistent: boolean;
+}
+
+export interface GlobalPackage {
+  name: string;
+  /** Version installed on disk */
+  version: string;
+  /** "npm", "pnpm", "yarn" or "bun" */
+  manager: string;
+  /** Directory the package is installed in */
+  path: string;
+}
+
+export interface DoctorOptions {
+  /** Command that installs dependencies; defaults to "npm install" */
+  installCommand?: string;
+  /** Command that runs the tests; defaults to "npm test" */
+  testCommand?: string;
+  /** Directory the commands run in; defaults to the manifest's. Lockfiles found there are restored along with the manifest */
+  cwd?: string;
+  /** How upgrades are retried when applying all of them fails: "iterate" (one at a time, in order) or "bisect" (halving each failing group). Defaults to "iterate" */
+  strategy?: string;
+}
+
+export interface DoctorReport {
+  /** Upgrades kept in the manifest */
+  passed: UpdateResult[];
+  /** Upgrades reverted because the install or the tests failed with them */
+  failed: DoctorFailure[];
+  /** Install and test runs made, the baseline included */
+  runs: number;
+}
+
+export interface DoctorFailure {
+  update: UpdateResult;
+  /** Combined stdout and stderr of the failing run */
+  output: string;
+}
+
+export interface DoctorStep {
+  /** "baseline", "all", "single", "group" or "restore" (the final install of the kept upgrades) */
+  kind: string;
+  /** Packages tried in this run, on top of the upgrades kept so far */
+  packages: string[];
+  /** Unset when the run starts */
+  passed?: boolean;
+}
+
+export interface PlanInput {
+  updates: UpdateResult[];
+  groups?: UpdateGroup[];
+}
+
+export interface PlanOptions {
+  /** Manifest of updates without a `workspace`, relative to the plan's root; defaults to "package.json". Workspace updates use `<workspace>/package.json` */
+  manifest?: string;
+  /** pnpm-workspace.yaml holding catalog updates; defaults to "pnpm-workspace.yaml" */
+  catalog?: string;
+  /** Prefix of the suggested branch names; defaults to "turbo-ncu/" */
+  branchPrefix?: string;
+}
+
+/** A versioned, self-contained description of the manifest edits a check suggests, split into batches that can each become one pull request */
+export interface UpdatePlan {
+  /** Format version of the document; readers reject versions they do not know */
+  version: number;
+  /** One batch per update group and per ungrouped package, ordered by id */
+  batches: PlanBatch[];
+}
+
+export interface PlanBatch {
+  /** Stable identifier, e.g. "lodash" or "group-babel" */
+  id: string;
+  /** Name of the update group, for batches of one */
+  group?: string;
+  /** Suggested branch name */
+  branch: string;
+  /** Suggested commit and pull request title */
+  commitTitle: string;
+  packages: PlanPackage[];
+  edits: PlanEdit[];
+}
+
+export interface PlanPackage {
+  name: string;
+  /** Version upgraded to */
+  version: string;
+  updateType: string;
+  /** npm page of `version` */
+  npmUrl: string;
+  /** Source repository; only known with full metadata */
+  repositoryUrl?: string;
+  /** Release notes of the repository, for GitHub and GitLab repositories */
+  releasesUrl?: string;
+  homepage?: string;
+}
+
+export interface PlanEdit {
+  /** File to edit, relative to the plan's root with `/` separators */
+  path: string;
+  /** JSON pointer to the value, also used for pnpm-workspace.yaml catalogs */
+  pointer: string;
+  /** Value the edit expects to find */
+  oldValue: string;
+  newValue: string;
+}
+
+export interface ApplyPlanOptions {
+  /** Directory the plan's paths are relative to; defaults to the working directory */
+  root?: string;
+  /** Ids of the batches to apply; defaults to all of them */
+  batches?: string[];
+}
+
+export interface AppliedPlan {
+  /** Edits written */
+  applied: number;
+  /** Edits whose new value was already in place */
+  unchanged: number;
+  /** Edits skipped because the file holds neither their old nor their new value */
+  conflicts: PlanEdit[];
 }
 
 export declare function checkUpdates(
   packages: PackageInfo[],
   options: CheckOptions,
+  onProgress?: (event: ProgressEvent) => void,
+  signal?: AbortSignal,
+): Promise<CheckResult>;
+
+export declare function streamUpdates(
+  packages: PackageInfo[],
+  options: CheckOptions,
+  onUpdate: (update: UpdateResult) => void,
+  onProgress?: (event: ProgressEvent) => void,
+  signal?: AbortSignal,
 ): Promise<CheckResult>;
 
+/** Write each update's `newRange` into the package.json at `path`, changing only the affected version strings so formatting survives byte for byte. Returns how many strings were replaced */
+export declare function applyUpdates(path: string, updates: UpdateResult[]): number;
+
+/** Apply `updates` to the package.json at `path`, then install and run the tests. Rejects if they fail before any upgrade; otherwise reverts the upgrades they fail with and leaves the passing ones applied and installed */
+export declare function doctor(
+  path: string,
+  updates: UpdateResult[],
+  options?: DoctorOptions,
+  onStep?: (step: DoctorStep) => void,
+): Promise<DoctorReport>;
+
+/** Read the package.json at `path` and classify every dependency specifier */
+export declare function readManifest(path: string): Manifest;
+
+/** Like `readManifest`, for package.json text that is already in memory */
+export declare function parseManifest(text: string): Manifest;
+
+/** Read the catalogs of the pnpm-workspace.yaml at `path`, listing for each entry which of the `manifests` (package.json paths) reference it */
+export declare function readCatalogs(path: string, manifests?: string[]): CatalogEntry[];
+
+/** Write catalog updates back into the pnpm-workspace.yaml at `path`, keeping comments and quoting. Updates are matched by `pointer`. Returns how many specifiers were replaced */
+export declare function applyCatalogUpdates(path: string, updates: UpdateResult[]): number;
+
+/** Discover the workspaces of the monorepo rooted at `root` (package.json `workspaces`, pnpm-workspace.yaml, lerna.json, nx.json and rush.json, honoring `!` exclusions and nested workspaces), with the dependencies between them */
+export declare function discoverWorkspaces(root: string): WorkspaceGraph;
+
+/** Compare the node_modules under `root` with the ranges `packages` declare, reporting missing, mismatched and extraneous installs. Dependencies the package.json files of `root` and the packages' workspaces declare are never extraneous */
+export declare function checkInstalled(root: string, packages: PackageInfo[]): InstalledIssue[];
+
+/** List the packages installed globally by `managers` (default: npm, pnpm, yarn and bun), reading their global folders from disk without running the package managers */
+export declare function globalPackages(managers?: string[]): GlobalPackage[];
+
+/** Export the updates and groups of a check (a whole `CheckResult` can be passed) as a versioned `UpdatePlan` in JSON, with one batch per group or package carrying its file edits, branch name, commit title and release links */
+export declare function exportPlan(input: PlanInput, options?: PlanOptions): string;
+
+/** Apply an update plan written by `exportPlan` to the files below `root`. Edits are only written where the file still holds their old value, so a plan can be applied again */
+export declare function applyPlan(plan: string, options?: ApplyPlanOptions): AppliedPlan;
+
 export declare function clearCache(cacheFile?: string): void;
@@ -2,4 +2,16 @@ import { createRequire } from "node:module";
 const require = createRequire(import.meta.url);
 const native = require("./index.cjs");
 export const checkUpdates = native.checkUpdates;
+export const streamUpdates = native.streamUpdates;
+export const applyUpdates = native.applyUpdates;
+export const doctor = native.doctor;
+export const readManifest = native.readManifest;
+export const parseManifest = native.parseManifest;
+export const readCatalogs = native.readCatalogs;
+export const applyCatalogUpdates = native.applyCatalogUpdates;
+export const discoverWorkspaces = native.discoverWorkspaces;
+export const checkInstalled = native.checkInstalled;
+export const globalPackages = native.globalPackages;
+export const exportPlan = native.exportPlan;
+export const applyPlan = native.applyPlan;
 export const clearCache = native.clearCache;
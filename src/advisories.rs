@@ -0,0 +1,194 @@
+use node_semver::{Range, Version};
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+
+use crate::semver_utils;
+use crate::types::{RegistryVersionInfo, Vulnerability};
+
+/// One entry of a bulk advisory response
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Advisory {
+    #[serde(default)]
+    pub id: i64,
+    #[serde(default)]
+    pub url: String,
+    #[serde(default)]
+    pub title: String,
+    #[serde(default)]
+    pub severity: String,
+    /// Range of affected versions, e.g. `<4.17.21`
+    #[serde(default)]
+    pub vulnerable_versions: String,
+}
+
+impl Advisory {
+    pub fn affects(&self, version: &Version) -> bool {
+        self.vulnerable_versions
+            .parse::<Range>()
+            .is_ok_and(|range| range.satisfies(version))
+    }
+
+    pub fn to_vulnerability(&self) -> Vulnerability {
+        Vulnerability {
+            id: self.id,
+            title: self.title.clone(),
+            severity: self.severity.clone(),
+            url: self.url.clone(),
+            vulnerable_versions: self.vulnerable_versions.clone(),
+        }
+    }
+}
+
+/// Advisories by package name, as returned by the bulk endpoint
+pub type AdvisoryIndex = HashMap<String, Vec<Advisory>>;
+
+/// Body for the bulk endpoint: for each package, the versions we may report
+/// on, from the installed one (or else the one the current range resolves
+/// to) upwards
+pub fn bulk_query<'a>(
+    packages: impl Iterator<Item = (&'a str, Option<&'a str>, &'a RegistryVersionInfo)>,
+) -> BTreeMap<String, Vec<String>> {
+    let mut query = BTreeMap::new();
+    for (range, installed, info) in packages {
+        let floor = installed
+            .and_then(|v| v.parse::<Version>().ok())
+            .or_else(|| semver_utils::resolved_version(range, &info.versions));
+        let versions: Vec<String> = info
+            .versions
+            .iter()
+            .filter(|v| match (&floor, v.parse::<Version>()) {
+                (Some(floor), Ok(v)) => v >= *floor,
+                (None, Ok(_)) => true,
+                (_, Err(_)) => false,
+            })
+            .cloned()
+            .collect();
+        if !versions.is_empty() {
+            query
+                .entry(info.name.clone())
+                .or_insert_with(Vec::new)
+                .extend(versions);
+        }
+    }
+    for versions in query.values_mut() {
+        versions.sort();
+        versions.dedup();
+    }
+    query
+}
+
+pub fn affecting<'a>(advisories: &'a [Advisory], version: &Version) -> Vec<&'a Advisory> {
+    advisories.iter().filter(|a| a.affects(version)).collect()
+}
+
+/// Lowest candidate above `current` that no advisory affects, provided
+/// `current` itself is vulnerable; the smallest step that ships the fix
+pub fn lowest_safe_version(
+    current: &Version,
+    candidates: &[String],
+    advisories: &[Advisory],
+    include_prerelease: bool,
+) -> Option<Version> {
+    if affecting(advisories, current).is_empty() {
+        return None;
+    }
+    candidates
+        .iter()
+        .filter_map(|v| v.parse::<Version>().ok())
+        .filter(|v| include_prerelease || v.pre_release.is_empty())
+        .filter(|v| v > current && affecting(advisories, v).is_empty())
+        .min()
+}
+
+/// Advisories fixed by moving from `from` to `to`, and those introduced by it
+pub fn compare(
+    advisories: &[Advisory],
+    from: Option<&Version>,
+    to: &Version,
+) -> (Vec<Vulnerability>, Vec<Vulnerability>) {
+    let before = from.map(|v| affecting(advisories, v)).unwrap_or_default();
+    let after = affecting(advisories, to);
+    let fixed = before
+        .iter()
+        .filter(|a| !after.contains(a))
+        .map(|a| a.to_vulnerability())
+        .collect();
+    let introduced = after
+        .iter()
+        .filter(|a| !before.contains(a))
+        .map(|a| a.to_vulnerability())
+        .collect();
+    (fixed, introduced)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn advisory(id: i64, vulnerable_versions: &str) -> Advisory {
+        Advisory {
+            id,
+            url: format!("https://github.com/advisories/{}", id),
+            title: format!("Advisory {}", id),
+            severity: "high".to_string(),
+            vulnerable_versions: vulnerable_versions.to_string(),
+        }
+    }
+
+    fn version(v: &str) -> Version {
+        v.parse().unwrap()
+    }
+
+    fn versions(list: &[&str]) -> Vec<String> {
+        list.iter().map(|v| v.to_string()).collect()
+    }
+
+    #[test]
+    fn test_lowest_safe_version() {
+        let advisories = vec![advisory(1, "<4.17.19"), advisory(2, ">=4.17.19 <4.17.21")];
+        let candidates = versions(&["4.17.15", "4.17.19", "4.17.21", "4.17.22", "5.0.0"]);
+
+        assert_eq!(
+            lowest_safe_version(&version("4.17.15"), &candidates, &advisories, false),
+            Some(version("4.17.21"))
+        );
+        // Not vulnerable, nothing to do
+        assert_eq!(
+            lowest_safe_version(&version("4.17.21"), &candidates, &advisories, false),
+            None
+        );
+        // No fixed release yet
+        let unfixed = vec![advisory(3, ">=1.0.0")];
+        assert_eq!(
+            lowest_safe_version(&version("4.17.21"), &candidates, &unfixed, false),
+            None
+        );
+    }
+
+    #[test]
+    fn test_compare_fixed_and_introduced() {
+        let advisories = vec![advisory(1, "<2.0.0"), advisory(2, ">=2.0.0 <2.1.0")];
+        let (fixed, introduced) = compare(&advisories, Some(&version("1.5.0")), &version("2.0.1"));
+        assert_eq!(fixed.iter().map(|v| v.id).collect::<Vec<_>>(), vec![1]);
+        assert_eq!(introduced.iter().map(|v| v.id).collect::<Vec<_>>(), vec![2]);
+
+        let (fixed, introduced) = compare(&advisories, None, &version("3.0.0"));
+        assert!(fixed.is_empty() && introduced.is_empty());
+    }
+
+    #[test]
+    fn test_bulk_query_starts_at_installed_version() {
+        let info = RegistryVersionInfo {
+            name: "lodash".to_string(),
+            versions: versions(&["4.0.0", "4.17.20", "4.17.21", "not-semver"]),
+            registry: String::new(),
+            details: HashMap::new(),
+        };
+        let query = bulk_query([("4.17.20", None, &info), ("^4.17.21", None, &info)].into_iter());
+        assert_eq!(query["lodash"], versions(&["4.17.20", "4.17.21"]));
+
+        // A locked version below what the range resolves to is covered too
+        let query = bulk_query([("^4.0.0", Some("4.0.0"), &info)].into_iter());
+        assert_eq!(query["lodash"], versions(&["4.0.0", "4.17.20", "4.17.21"]));
+    }
+}
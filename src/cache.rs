@@ -3,12 +3,16 @@ use std::collections::HashMap;
 use std::path::Path;
 use std::time::{SystemTime, UNIX_EPOCH};
 
-use crate::types::RegistryVersionInfo;
+use crate::types::{RegistryVersionInfo, VersionDetails};
 
 #[derive(Debug, Serialize, Deserialize)]
 struct CacheEntry {
     versions: Vec<String>,
     timestamp: u64,
+    #[serde(default)]
+    registry: String,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    details: HashMap<String, VersionDetails>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Default)]
@@ -59,15 +63,19 @@ impl Cache {
         Some(RegistryVersionInfo {
             name: name.to_string(),
             versions: entry.versions.clone(),
+            registry: entry.registry.clone(),
+            details: entry.details.clone(),
         })
     }
 
-    pub fn set(&mut self, name: &str, versions: Vec<String>) {
+    pub fn set(&mut self, info: &RegistryVersionInfo) {
         self.store.entries.insert(
-            name.to_string(),
+            info.name.clone(),
             CacheEntry {
-                versions,
+                versions: info.versions.clone(),
                 timestamp: Self::now(),
+                registry: info.registry.clone(),
+                details: info.details.clone(),
             },
         );
     }
@@ -112,10 +120,24 @@ mod tests {
     use std::sync::atomic::{AtomicU64, Ordering};
     static TEST_COUNTER: AtomicU64 = AtomicU64::new(0);
 
+    fn info(name: &str, versions: Vec<String>) -> RegistryVersionInfo {
+        RegistryVersionInfo {
+            name: name.to_string(),
+            versions,
+            registry: "https://registry.npmjs.org".to_string(),
+            details: HashMap::new(),
+        }
+    }
+
     fn tmp_cache_path(label: &str) -> String {
         let id = TEST_COUNTER.fetch_add(1, Ordering::SeqCst);
         let mut path = env::temp_dir();
-        path.push(format!("turbo-ncu-test-{}-{}-{}.json", label, std::process::id(), id));
+        path.push(format!(
+            "turbo-ncu-test-{}-{}-{}.json",
+            label,
+            std::process::id(),
+            id
+        ));
         path.to_string_lossy().to_string()
     }
 
@@ -123,7 +145,7 @@ mod tests {
     fn test_cache_set_and_get() {
         let path = tmp_cache_path("setget");
         let mut cache = Cache::new(&path, 600);
-        cache.set("lodash", vec!["4.17.21".to_string()]);
+        cache.set(&info("lodash", vec!["4.17.21".to_string()]));
 
         let result = cache.get("lodash");
         assert!(result.is_some());
@@ -145,7 +167,10 @@ mod tests {
         let path = tmp_cache_path("savereload");
         {
             let mut cache = Cache::new(&path, 600);
-            cache.set("express", vec!["4.18.0".to_string(), "4.19.0".to_string()]);
+            cache.set(&info(
+                "express",
+                vec!["4.18.0".to_string(), "4.19.0".to_string()],
+            ));
             cache.save().unwrap();
         }
         {
@@ -157,11 +182,35 @@ mod tests {
         let _ = std::fs::remove_file(&path);
     }
 
+    #[test]
+    fn test_cache_keeps_version_details() {
+        let path = tmp_cache_path("details");
+        let mut full = info("lodash", vec!["4.17.21".to_string()]);
+        full.details.insert(
+            "4.17.21".to_string(),
+            VersionDetails {
+                license: Some("MIT".to_string()),
+                ..Default::default()
+            },
+        );
+        {
+            let mut cache = Cache::new(&path, 600);
+            cache.set(&full);
+            cache.set(&info("chalk", vec!["5.3.0".to_string()]));
+            cache.save().unwrap();
+        }
+
+        let cache = Cache::new(&path, 600);
+        assert_eq!(cache.get("lodash").unwrap().details, full.details);
+        assert!(cache.get("chalk").unwrap().details.is_empty());
+        let _ = std::fs::remove_file(&path);
+    }
+
     #[test]
     fn test_cache_expired() {
         let path = tmp_cache_path("expired");
         let mut cache = Cache::new(&path, 0); // 0 second TTL
-        cache.set("old-pkg", vec!["1.0.0".to_string()]);
+        cache.set(&info("old-pkg", vec!["1.0.0".to_string()]));
         // With 0 TTL, entry should be expired immediately
         std::thread::sleep(std::time::Duration::from_secs(1));
         assert!(cache.get("old-pkg").is_none());
@@ -172,7 +221,7 @@ mod tests {
     fn test_cache_clear() {
         let path = tmp_cache_path("clear");
         let mut cache = Cache::new(&path, 600);
-        cache.set("pkg", vec!["1.0.0".to_string()]);
+        cache.set(&info("pkg", vec!["1.0.0".to_string()]));
         cache.save().unwrap();
         cache.clear();
         assert!(cache.get("pkg").is_none());
@@ -0,0 +1,73 @@
+use std::future::Future;
+use std::sync::Arc;
+use tokio::sync::watch;
+
+/// Cloneable flag shared between JS (via `AbortSignal`) and in-flight work
+#[derive(Clone)]
+pub struct CancelToken {
+    sender: Arc<watch::Sender<bool>>,
+}
+
+impl CancelToken {
+    pub fn new() -> Self {
+        let (sender, _) = watch::channel(false);
+        Self {
+            sender: Arc::new(sender),
+        }
+    }
+
+    pub fn cancel(&self) {
+        self.sender.send_replace(true);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        *self.sender.borrow()
+    }
+
+    /// Resolves once `cancel` has been called
+    pub async fn cancelled(&self) {
+        let mut receiver = self.sender.subscribe();
+        let _ = receiver.wait_for(|cancelled| *cancelled).await;
+    }
+
+    /// Run `fut` to completion unless the token is cancelled first
+    pub async fn run<F: Future>(&self, fut: F) -> Result<F::Output, String> {
+        tokio::select! {
+            biased;
+            _ = self.cancelled() => Err("Cancelled".to_string()),
+            output = fut => Ok(output),
+        }
+    }
+}
+
+impl Default for CancelToken {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_run_completes_without_cancel() {
+        let token = CancelToken::new();
+        assert_eq!(token.run(async { 42 }).await, Ok(42));
+    }
+
+    #[tokio::test]
+    async fn test_cancel_interrupts_pending_future() {
+        let token = CancelToken::new();
+        let canceller = token.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            canceller.cancel();
+        });
+
+        let result = token.run(tokio::time::sleep(Duration::from_secs(30))).await;
+        assert!(result.is_err());
+        assert!(token.is_cancelled());
+    }
+}
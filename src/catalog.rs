@@ -0,0 +1,409 @@
+use std::ops::Range;
+
+use crate::json_edit::{pointer_token, Edit};
+use crate::manifest::{self, Specifier};
+use crate::types::{CatalogEntry, UpdateResult};
+
+/// Name pnpm gives the top-level `catalog:` mapping
+pub const DEFAULT_CATALOG: &str = "default";
+
+/// One `package: specifier` line of a catalog, with the byte range of the
+/// specifier token (quotes included) so it can be replaced in place
+#[derive(Debug, Clone, PartialEq)]
+struct Line {
+    catalog: String,
+    name: String,
+    specifier: String,
+    span: Range<usize>,
+}
+
+impl Line {
+    fn pointer(&self) -> String {
+        pointer_for(&self.catalog, &self.name)
+    }
+}
+
+/// Location of a catalog entry in the YAML document, as a JSON pointer
+fn pointer_for(catalog: &str, name: &str) -> String {
+    if catalog == DEFAULT_CATALOG {
+        format!("/catalog/{}", pointer_token(name))
+    } else {
+        format!(
+            "/catalogs/{}/{}",
+            pointer_token(catalog),
+            pointer_token(name)
+        )
+    }
+}
+
+/// Read the catalogs of a pnpm-workspace.yaml. Only block mappings are
+/// understood, which is how pnpm itself writes them; flow mappings such as
+/// `catalog: { react: ^18 }` are skipped
+fn parse(text: &str) -> Vec<Line> {
+    let mut lines = Vec::new();
+    // Top-level section, and for `catalogs:` the catalog being read
+    let mut section: Option<&str> = None;
+    let mut catalog: Option<(String, usize)> = None;
+    let mut offset = 0;
+
+    for raw in text.split_inclusive('\n') {
+        let start = offset;
+        offset += raw.len();
+        let line = raw.trim_end_matches(['\n', '\r']);
+        let content = strip_comment(line);
+        if content.trim().is_empty() {
+            continue;
+        }
+        let indent = content.len() - content.trim_start().len();
+        let Some((key, value, value_start)) = split_entry(content) else {
+            if indent == 0 {
+                section = None;
+            }
+            continue;
+        };
+
+        if indent == 0 {
+            section = match key.as_str() {
+                "catalog" if value.is_empty() => Some("catalog"),
+                "catalogs" if value.is_empty() => Some("catalogs"),
+                _ => None,
+            };
+            catalog = None;
+            continue;
+        }
+
+        let catalog_name = match section {
+            Some("catalog") => DEFAULT_CATALOG.to_string(),
+            Some("catalogs") => {
+                match &catalog {
+                    Some((_, catalog_indent)) if indent > *catalog_indent => {}
+                    _ => {
+                        if value.is_empty() {
+                            catalog = Some((key, indent));
+                        }
+                        continue;
+                    }
+                }
+                catalog
+                    .as_ref()
+                    .map(|(name, _)| name.clone())
+                    .unwrap_or_default()
+            }
+            _ => continue,
+        };
+        if value.is_empty() {
+            continue;
+        }
+        let value_end = value_start + content[value_start..].trim_end().len();
+        lines.push(Line {
+            catalog: catalog_name,
+            name: key,
+            specifier: value,
+            span: start + value_start..start + value_end,
+        });
+    }
+    lines
+}
+
+/// Drop a trailing `# comment`; a `#` only starts one after whitespace and
+/// outside quotes
+fn strip_comment(line: &str) -> &str {
+    let mut quote: Option<char> = None;
+    let mut previous = ' ';
+    for (i, c) in line.char_indices() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => {}
+            None if c == '\'' || c == '"' => quote = Some(c),
+            None if c == '#' && previous.is_whitespace() => return &line[..i],
+            None => {}
+        }
+        previous = c;
+    }
+    line
+}
+
+/// Split `key: value` into the decoded key, the decoded value and the byte
+/// offset of the value token
+fn split_entry(content: &str) -> Option<(String, String, usize)> {
+    let trimmed = content.trim_start();
+    let key_start = content.len() - trimmed.len();
+    let (key, after_key) = match trimmed.chars().next()? {
+        quote @ ('\'' | '"') => {
+            let (key, len) = unquote(trimmed, quote)?;
+            (key, key_start + len)
+        }
+        _ => {
+            let colon = trimmed.find(": ").or_else(|| {
+                trimmed
+                    .trim_end()
+                    .ends_with(':')
+                    .then(|| trimmed.trim_end().len() - 1)
+            })?;
+            (trimmed[..colon].trim_end().to_string(), key_start + colon)
+        }
+    };
+    let rest = content[after_key..].strip_prefix(':')?;
+    let value_start = content.len() - rest.trim_start().len();
+    let token = content[value_start..].trim_end();
+    let value = match token.chars().next() {
+        Some(quote @ ('\'' | '"')) => unquote(token, quote)?.0,
+        _ => token.to_string(),
+    };
+    Some((key, value, value_start))
+}
+
+/// Decode a quoted scalar at the start of `text`, returning it and the length
+/// of the token
+fn unquote(text: &str, quote: char) -> Option<(String, usize)> {
+    let mut value = String::new();
+    let mut chars = text.char_indices().skip(1).peekable();
+    while let Some((i, c)) = chars.next() {
+        if c == quote {
+            // '' is an escaped quote in single-quoted scalars
+            if quote == '\'' && chars.peek().map(|(_, c)| *c) == Some('\'') {
+                chars.next();
+                value.push('\'');
+                continue;
+            }
+            return Some((value, i + 1));
+        }
+        if c == '\\' && quote == '"' {
+            if let Some((_, escaped)) = chars.next() {
+                value.push(escaped);
+            }
+            continue;
+        }
+        value.push(c);
+    }
+    None
+}
+
+/// Encode `value` in the style of the token it replaces: quoted values keep
+/// their quotes, and plain values are quoted only when YAML requires it
+fn encode(value: &str, original: &str) -> String {
+    let quote = match original.chars().next() {
+        Some(q @ ('\'' | '"')) => Some(q),
+        _ if needs_quotes(value) => Some('\''),
+        _ => None,
+    };
+    match quote {
+        Some('\'') => format!("'{}'", value.replace('\'', "''")),
+        Some(_) => format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\"")),
+        None => value.to_string(),
+    }
+}
+
+fn needs_quotes(value: &str) -> bool {
+    value.is_empty()
+        || value.starts_with([
+            '>', '|', '*', '&', '!', '%', '@', '`', '\'', '"', '{', '[', ',', '#', '?', ':', '-',
+        ])
+        || value.contains(": ")
+        || value.contains(" #")
+        || value.trim() != value
+}
+
+/// Every catalog entry of the pnpm-workspace.yaml at `path`, with the
+/// workspaces among `manifests` (package.json paths) that reference it
+pub fn read_catalogs(path: &str, manifests: &[String]) -> Result<Vec<CatalogEntry>, String> {
+    let text =
+        std::fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+
+    // (catalog, package, consumer) for each `catalog:` reference
+    let mut references: Vec<(String, String, String)> = Vec::new();
+    for manifest_path in manifests {
+        let manifest = manifest::read_manifest(manifest_path)?;
+        let consumer = manifest
+            .name
+            .clone()
+            .unwrap_or_else(|| manifest_path.clone());
+        for dep in &manifest.dependencies {
+            if let Specifier::Catalog { catalog } = Specifier::parse(&dep.specifier) {
+                let catalog = if catalog.is_empty() {
+                    DEFAULT_CATALOG.to_string()
+                } else {
+                    catalog
+                };
+                references.push((catalog, dep.name.clone(), consumer.clone()));
+            }
+        }
+    }
+
+    Ok(parse(&text)
+        .into_iter()
+        .map(|line| {
+            let mut consumers: Vec<String> = references
+                .iter()
+                .filter(|(catalog, name, _)| *catalog == line.catalog && *name == line.name)
+                .map(|(_, _, consumer)| consumer.clone())
+                .collect();
+            consumers.dedup();
+            CatalogEntry {
+                pointer: line.pointer(),
+                catalog: line.catalog,
+                name: line.name,
+                specifier: line.specifier,
+                consumers,
+            }
+        })
+        .collect())
+}
+
+/// Replace the specifiers addressed by each update's `pointer`, keeping every
+/// other byte, comments included. Returns the new text and the replacements
+fn apply_edits(text: &str, updates: &[UpdateResult]) -> (String, u32) {
+    let edits: Vec<Edit> = updates
+        .iter()
+        .filter_map(|update| {
+            Some(Edit {
+                pointer: update.pointer.clone()?,
+                value: update.new_range.clone(),
+            })
+        })
+        .collect();
+    replace(text, &edits)
+}
+
+/// Specifier of the catalog entry at `pointer`
+pub fn specifier_at(text: &str, pointer: &str) -> Option<String> {
+    parse(text)
+        .into_iter()
+        .find(|line| line.pointer() == pointer)
+        .map(|line| line.specifier)
+}
+
+/// Replace the specifiers at each edit's pointer, keeping every other byte
+pub fn replace(text: &str, edits: &[Edit]) -> (String, u32) {
+    let mut replacements: Vec<(Range<usize>, String)> = Vec::new();
+    for line in parse(text) {
+        let pointer = line.pointer();
+        if let Some(edit) = edits.iter().find(|e| e.pointer == pointer) {
+            if edit.value != line.specifier {
+                let original = &text[line.span.clone()];
+                replacements.push((line.span, encode(&edit.value, original)));
+            }
+        }
+    }
+
+    let mut output = String::with_capacity(text.len());
+    let mut last = 0;
+    for (span, replacement) in &replacements {
+        output.push_str(&text[last..span.start]);
+        output.push_str(replacement);
+        last = span.end;
+    }
+    output.push_str(&text[last..]);
+    (output, replacements.len() as u32)
+}
+
+/// Write catalog updates (as produced from `read_catalogs` entries) into the
+/// pnpm-workspace.yaml at `path`. Returns the number of specifiers replaced
+pub fn apply_catalog_updates(path: &str, updates: &[UpdateResult]) -> Result<u32, String> {
+    let text =
+        std::fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+    let (output, count) = apply_edits(&text, updates);
+    if count > 0 {
+        let tmp_path = format!("{}.tmp", path);
+        std::fs::write(&tmp_path, output).map_err(|e| format!("Write tmp: {}", e))?;
+        std::fs::rename(&tmp_path, path).map_err(|e| format!("Rename: {}", e))?;
+    }
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WORKSPACE: &str = "packages:\n  - 'packages/*'\n\n# Shared versions\ncatalog:\n  react: ^18.2.0 # keep in sync with react-dom\n  'react-dom': \"^18.2.0\"\n  \"@types/node\": ~20.1.0\n\ncatalogs:\n  # Legacy apps\n  react17:\n    react: ^17.0.2\n    react-dom: '^17.0.2'\n  tools:\n    vite: 5.0.0\n";
+
+    fn update(pointer: &str, new_range: &str) -> UpdateResult {
+        UpdateResult {
+            dep_type: "catalog".to_string(),
+            pointer: Some(pointer.to_string()),
+            ..UpdateResult::for_test("", "", new_range)
+        }
+    }
+
+    #[test]
+    fn test_parse_catalogs() {
+        let entries: Vec<(String, String, String)> = parse(WORKSPACE)
+            .into_iter()
+            .map(|l| (l.catalog, l.name, l.specifier))
+            .collect();
+        let expected = [
+            ("default", "react", "^18.2.0"),
+            ("default", "react-dom", "^18.2.0"),
+            ("default", "@types/node", "~20.1.0"),
+            ("react17", "react", "^17.0.2"),
+            ("react17", "react-dom", "^17.0.2"),
+            ("tools", "vite", "5.0.0"),
+        ];
+        let expected: Vec<(String, String, String)> = expected
+            .iter()
+            .map(|(c, n, s)| (c.to_string(), n.to_string(), s.to_string()))
+            .collect();
+        assert_eq!(entries, expected);
+    }
+
+    #[test]
+    fn test_rewrite_keeps_comments_and_quotes() {
+        let (output, count) = apply_edits(
+            WORKSPACE,
+            &[
+                update("/catalog/react", "^18.3.1"),
+                update("/catalog/react-dom", "^18.3.1"),
+                update("/catalogs/react17/react-dom", "^17.0.3"),
+                update("/catalogs/tools/vite", ">=5.4.0"),
+            ],
+        );
+        assert_eq!(count, 4);
+        assert_eq!(
+            output,
+            WORKSPACE
+                .replace("react: ^18.2.0 #", "react: ^18.3.1 #")
+                .replace("\"^18.2.0\"", "\"^18.3.1\"")
+                .replace("'^17.0.2'", "'^17.0.3'")
+                .replace("vite: 5.0.0", "vite: '>=5.4.0'")
+        );
+    }
+
+    #[test]
+    fn test_read_catalogs_reports_consumers() {
+        let dir = std::env::temp_dir().join(format!("turbo-ncu-catalog-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let workspace = dir.join("pnpm-workspace.yaml");
+        std::fs::write(&workspace, WORKSPACE).unwrap();
+        let app = dir.join("app.json");
+        std::fs::write(
+            &app,
+            r#"{"name":"app","dependencies":{"react":"catalog:","vite":"catalog:tools"}}"#,
+        )
+        .unwrap();
+        let legacy = dir.join("legacy.json");
+        std::fs::write(
+            &legacy,
+            r#"{"name":"legacy","dependencies":{"react":"catalog:react17"}}"#,
+        )
+        .unwrap();
+
+        let manifests = vec![
+            app.to_string_lossy().to_string(),
+            legacy.to_string_lossy().to_string(),
+        ];
+        let entries = read_catalogs(&workspace.to_string_lossy(), &manifests).unwrap();
+        let consumers = |pointer: &str| {
+            entries
+                .iter()
+                .find(|e| e.pointer == pointer)
+                .map(|e| e.consumers.clone())
+                .unwrap()
+        };
+        assert_eq!(consumers("/catalog/react"), vec!["app"]);
+        assert_eq!(consumers("/catalogs/react17/react"), vec!["legacy"]);
+        assert_eq!(consumers("/catalogs/tools/vite"), vec!["app"]);
+        assert!(consumers("/catalog/react-dom").is_empty());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,193 @@
+use node_semver::Version;
+use std::collections::{BTreeMap, HashMap};
+
+use crate::semver_utils;
+use crate::types::{Divergence, Occurrence, PackageInfo};
+
+/// Dependency types whose ranges are expected to agree across workspaces; peer
+/// ranges are deliberately broad and are left out
+const ALIGNED_DEP_TYPES: [&str; 3] = ["prod", "dev", "optional"];
+
+/// How the one version every occurrence of a package is aligned to is chosen
+#[derive(Debug, Clone, PartialEq)]
+pub enum SyncPolicy {
+    /// The highest version any occurrence currently declares
+    Highest,
+    /// The newest version available under the check's rules
+    Latest,
+    /// Versions set per package; packages without one are not aligned
+    Pinned(HashMap<String, Version>),
+}
+
+impl SyncPolicy {
+    pub fn parse(
+        policy: &str,
+        versions: Option<&HashMap<String, String>>,
+    ) -> Result<SyncPolicy, String> {
+        match policy {
+            "highest" => Ok(SyncPolicy::Highest),
+            "latest" => Ok(SyncPolicy::Latest),
+            "pinned" => {
+                let versions = versions.ok_or("syncVersions is required with sync \"pinned\"")?;
+                versions
+                    .iter()
+                    .map(|(name, version)| {
+                        version
+                            .parse::<Version>()
+                            .map(|v| (name.clone(), v))
+                            .map_err(|_| {
+                                format!("Invalid pinned version for {}: {}", name, version)
+                            })
+                    })
+                    .collect::<Result<_, _>>()
+                    .map(SyncPolicy::Pinned)
+            }
+            other => Err(format!(
+                "Unknown sync policy \"{}\"; expected highest, latest or pinned",
+                other
+            )),
+        }
+    }
+
+    /// The agreed version of a package declared with `ranges`, choosing from
+    /// the `candidates` the check allows for "latest"
+    pub fn agreed_version(
+        &self,
+        name: &str,
+        ranges: &[String],
+        candidates: &[String],
+        include_prerelease: bool,
+    ) -> Option<Version> {
+        match self {
+            SyncPolicy::Highest => ranges
+                .iter()
+                .filter_map(|range| semver_utils::parse_base_version(range))
+                .max(),
+            SyncPolicy::Latest => candidates
+                .iter()
+                .filter_map(|v| v.parse::<Version>().ok())
+                .filter(|v| include_prerelease || v.pre_release.is_empty())
+                .max(),
+            SyncPolicy::Pinned(versions) => versions.get(name).cloned(),
+        }
+    }
+
+    /// Whether a package occurring `count` times is aligned
+    pub fn applies(&self, name: &str, count: usize) -> bool {
+        match self {
+            SyncPolicy::Pinned(versions) => versions.contains_key(name),
+            _ => count > 1,
+        }
+    }
+}
+
+/// Ranges each registry package is declared with, keyed by the package looked
+/// up; `lookups` are the registry lookups of `packages`, index for index
+pub fn occurrences<'a>(
+    packages: &'a [PackageInfo],
+    lookups: &'a [PackageInfo],
+) -> BTreeMap<&'a str, Vec<(&'a PackageInfo, &'a str)>> {
+    let mut by_name: BTreeMap<&str, Vec<(&PackageInfo, &str)>> = BTreeMap::new();
+    for (pkg, lookup) in packages.iter().zip(lookups) {
+        if ALIGNED_DEP_TYPES.contains(&pkg.dep_type.as_str()) {
+            by_name
+                .entry(lookup.name.as_str())
+                .or_default()
+                .push((pkg, lookup.version_range.as_str()));
+        }
+    }
+    by_name
+}
+
+/// Packages declared with more than one distinct range
+pub fn divergences(packages: &[PackageInfo], lookups: &[PackageInfo]) -> Vec<Divergence> {
+    occurrences(packages, lookups)
+        .into_iter()
+        .filter(|(_, found)| found.iter().any(|(_, range)| *range != found[0].1))
+        .map(|(name, found)| Divergence {
+            name: name.to_string(),
+            occurrences: found
+                .into_iter()
+                .map(|(pkg, range)| Occurrence {
+                    workspace: pkg.workspace.clone(),
+                    range: range.to_string(),
+                    dep_type: pkg.dep_type.clone(),
+                })
+                .collect(),
+            agreed: None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manifest;
+
+    fn pkg(workspace: &str, name: &str, range: &str, dep_type: &str) -> PackageInfo {
+        PackageInfo {
+            name: name.to_string(),
+            version_range: range.to_string(),
+            dep_type: dep_type.to_string(),
+            pointer: None,
+            workspace: Some(workspace.to_string()),
+        }
+    }
+
+    #[test]
+    fn test_reports_divergent_ranges() {
+        let packages = vec![
+            pkg("web", "react", "^18.2.0", "prod"),
+            pkg("ui", "react", "^18.0.0", "dev"),
+            pkg("ui", "react-peer", "npm:react@^18.2.0", "prod"),
+            pkg("ui", "lodash", "^4.17.21", "prod"),
+            pkg("web", "lodash", "^4.17.21", "prod"),
+            pkg("ui", "vue", "^3.0.0", "peer"),
+            pkg("web", "vue", "^3.4.0", "prod"),
+        ];
+        let lookups: Vec<PackageInfo> = packages.iter().map(manifest::registry_lookup).collect();
+        let found = divergences(&packages, &lookups);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].name, "react");
+        let ranges: Vec<(Option<&str>, &str)> = found[0]
+            .occurrences
+            .iter()
+            .map(|o| (o.workspace.as_deref(), o.range.as_str()))
+            .collect();
+        assert_eq!(
+            ranges,
+            vec![
+                (Some("web"), "^18.2.0"),
+                (Some("ui"), "^18.0.0"),
+                (Some("ui"), "^18.2.0")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_agreed_versions() {
+        let ranges = vec!["^18.0.0".to_string(), "~18.2.0".to_string()];
+        let candidates: Vec<String> = ["18.2.0", "18.3.1", "19.0.0-rc.1"]
+            .iter()
+            .map(|v| v.to_string())
+            .collect();
+        let agreed = |policy: &SyncPolicy| {
+            policy
+                .agreed_version("react", &ranges, &candidates, false)
+                .map(|v| v.to_string())
+        };
+
+        assert_eq!(agreed(&SyncPolicy::Highest).as_deref(), Some("18.2.0"));
+        assert_eq!(agreed(&SyncPolicy::Latest).as_deref(), Some("18.3.1"));
+        let pins = HashMap::from([("react".to_string(), "18.2.0".to_string())]);
+        let pinned = SyncPolicy::parse("pinned", Some(&pins)).unwrap();
+        assert_eq!(agreed(&pinned).as_deref(), Some("18.2.0"));
+        assert!(pinned.applies("react", 1));
+        assert!(!pinned.applies("vue", 2));
+
+        assert!(SyncPolicy::parse("pinned", None).is_err());
+        assert!(SyncPolicy::parse("newest", None).is_err());
+        let bad = HashMap::from([("react".to_string(), "^18".to_string())]);
+        assert!(SyncPolicy::parse("pinned", Some(&bad)).is_err());
+    }
+}
@@ -0,0 +1,353 @@
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::process::Command;
+
+use crate::json_edit;
+use crate::types::{DoctorFailure, DoctorOptions, DoctorReport, DoctorStep, UpdateResult};
+
+pub type DoctorCallback = Arc<dyn Fn(DoctorStep) + Send + Sync>;
+
+/// Lockfiles an install may rewrite, restored together with the manifest
+const LOCKFILES: [&str; 6] = [
+    "package-lock.json",
+    "npm-shrinkwrap.json",
+    "pnpm-lock.yaml",
+    "yarn.lock",
+    "bun.lock",
+    "bun.lockb",
+];
+
+/// Manifest and lockfiles as they were before the first upgrade; `None` for
+/// files that did not exist
+struct Snapshot {
+    files: Vec<(PathBuf, Option<Vec<u8>>)>,
+}
+
+impl Snapshot {
+    fn take(manifest: &Path, dir: &Path) -> Result<Self, String> {
+        let mut paths = vec![manifest.to_path_buf()];
+        paths.extend(LOCKFILES.iter().map(|name| dir.join(name)));
+        let files = paths
+            .into_iter()
+            .map(|path| {
+                let contents = match std::fs::read(&path) {
+                    Ok(contents) => Some(contents),
+                    Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+                    Err(e) => return Err(format!("Failed to read {}: {}", path.display(), e)),
+                };
+                Ok((path, contents))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self { files })
+    }
+
+    fn restore(&self) -> Result<(), String> {
+        for (path, contents) in &self.files {
+            let result = match contents {
+                Some(contents) => std::fs::write(path, contents),
+                None if path.exists() => std::fs::remove_file(path),
+                None => Ok(()),
+            };
+            result.map_err(|e| format!("Failed to restore {}: {}", path.display(), e))?;
+        }
+        Ok(())
+    }
+}
+
+/// Outcome of one install and test run
+struct Run {
+    passed: bool,
+    output: String,
+}
+
+struct Doctor {
+    manifest: String,
+    dir: PathBuf,
+    install: String,
+    test: String,
+    snapshot: Snapshot,
+    on_step: Option<DoctorCallback>,
+    runs: u32,
+}
+
+impl Doctor {
+    /// Restore the original files, apply the `kept` and `tried` upgrades on
+    /// top, install and test
+    async fn try_updates(
+        &mut self,
+        kind: &str,
+        kept: &[UpdateResult],
+        tried: &[UpdateResult],
+    ) -> Result<Run, String> {
+        self.snapshot.restore()?;
+        json_edit::apply_updates(&self.manifest, &[kept, tried].concat())?;
+        let packages: Vec<String> = tried.iter().map(|u| u.name.clone()).collect();
+        self.emit(kind, &packages, None);
+        let mut run = self.run(&self.install).await?;
+        if run.passed {
+            let test = self.run(&self.test).await?;
+            run.output.push_str(&test.output);
+            run.passed = test.passed;
+        }
+        self.runs += 1;
+        self.emit(kind, &packages, Some(run.passed));
+        Ok(run)
+    }
+
+    async fn run(&self, command: &str) -> Result<Run, String> {
+        let mut shell = if cfg!(windows) {
+            let mut shell = Command::new("cmd");
+            shell.arg("/C");
+            shell
+        } else {
+            let mut shell = Command::new("sh");
+            shell.arg("-c");
+            shell
+        };
+        let output = shell
+            .arg(command)
+            .current_dir(&self.dir)
+            .stdin(std::process::Stdio::null())
+            .output()
+            .await
+            .map_err(|e| format!("Failed to run {}: {}", command, e))?;
+        let mut text = String::from_utf8_lossy(&output.stdout).to_string();
+        text.push_str(&String::from_utf8_lossy(&output.stderr));
+        Ok(Run {
+            passed: output.status.success(),
+            output: text,
+        })
+    }
+
+    fn emit(&self, kind: &str, packages: &[String], passed: Option<bool>) {
+        if let Some(callback) = &self.on_step {
+            callback(DoctorStep {
+                kind: kind.to_string(),
+                packages: packages.to_vec(),
+                passed,
+            });
+        }
+    }
+}
+
+/// Apply `updates` to the manifest at `path` and keep those the project still
+/// installs and passes its tests with. When upgrading everything at once
+/// fails, upgrades are retried one at a time ("iterate") or in halves of the
+/// failing group ("bisect"); each retry builds on the upgrades kept so far.
+/// The manifest is left with the passing upgrades applied and installed
+pub async fn run(
+    path: &str,
+    updates: Vec<UpdateResult>,
+    options: &DoctorOptions,
+    on_step: Option<DoctorCallback>,
+) -> Result<DoctorReport, String> {
+    let bisect = match options.strategy.as_deref().unwrap_or("iterate") {
+        "iterate" => false,
+        "bisect" => true,
+        other => {
+            return Err(format!(
+                "Unknown doctor strategy \"{}\"; expected iterate or bisect",
+                other
+            ))
+        }
+    };
+    let manifest = Path::new(path);
+    let dir = match &options.cwd {
+        Some(cwd) => PathBuf::from(cwd),
+        None => manifest
+            .parent()
+            .filter(|dir| !dir.as_os_str().is_empty())
+            .map_or_else(|| PathBuf::from("."), Path::to_path_buf),
+    };
+    let mut doctor = Doctor {
+        manifest: path.to_string(),
+        snapshot: Snapshot::take(manifest, &dir)?,
+        dir,
+        install: options
+            .install_command
+            .clone()
+            .unwrap_or_else(|| "npm install".to_string()),
+        test: options
+            .test_command
+            .clone()
+            .unwrap_or_else(|| "npm test".to_string()),
+        on_step,
+        runs: 0,
+    };
+
+    let baseline = doctor.try_updates("baseline", &[], &[]).await?;
+    if !baseline.passed {
+        doctor.snapshot.restore()?;
+        return Err(format!(
+            "Install or tests fail before any upgrade:\n{}",
+            baseline.output
+        ));
+    }
+
+    let mut passed: Vec<UpdateResult> = Vec::new();
+    let mut failed = Vec::new();
+    let all = doctor.try_updates("all", &[], &updates).await?;
+    if all.passed {
+        passed = updates;
+    } else if bisect {
+        // Groups known to break the build, with the output of that run
+        let mut queue = VecDeque::from([(updates, all.output)]);
+        while let Some((mut group, output)) = queue.pop_front() {
+            if group.len() == 1 {
+                failed.push(DoctorFailure {
+                    update: group.remove(0),
+                    output,
+                });
+                continue;
+            }
+            let right = group.split_off(group.len() / 2);
+            let left = group;
+            let run = doctor.try_updates("group", &passed, &left).await?;
+            if run.passed {
+                passed.extend(left);
+                // The whole group failed and its first half passes, so the
+                // second half is at fault without testing it on its own
+                queue.push_back((right, output));
+            } else {
+                queue.push_back((left, run.output));
+                let run = doctor.try_updates("group", &passed, &right).await?;
+                if run.passed {
+                    passed.extend(right);
+                } else {
+                    queue.push_back((right, run.output));
+                }
+            }
+        }
+    } else {
+        for update in updates {
+            let run = doctor
+                .try_updates("single", &passed, std::slice::from_ref(&update))
+                .await?;
+            if run.passed {
+                passed.push(update);
+            } else {
+                failed.push(DoctorFailure {
+                    update,
+                    output: run.output,
+                });
+            }
+        }
+    }
+
+    // Leave the project installed with exactly the upgrades that passed
+    if !failed.is_empty() {
+        doctor.snapshot.restore()?;
+        json_edit::apply_updates(path, &passed)?;
+        let names: Vec<String> = passed.iter().map(|u| u.name.clone()).collect();
+        doctor.emit("restore", &names, None);
+        let install = doctor.run(&doctor.install).await?;
+        doctor.emit("restore", &names, Some(install.passed));
+    }
+
+    Ok(DoctorReport {
+        passed,
+        failed,
+        runs: doctor.runs,
+    })
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    /// A project whose "tests" fail while package.json requests `broken@^2`
+    /// or `flaky@^3`, and whose install writes a lockfile
+    fn project(label: &str) -> (PathBuf, DoctorOptions) {
+        let dir =
+            std::env::temp_dir().join(format!("turbo-ncu-doctor-{}-{}", label, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("package.json"),
+            "{\n  \"dependencies\": {\n    \"a\": \"^1.0.0\",\n    \"broken\": \"^1.0.0\",\n    \"c\": \"^1.0.0\",\n    \"flaky\": \"^1.0.0\"\n  }\n}\n",
+        )
+        .unwrap();
+        let options = DoctorOptions {
+            install_command: Some("cp package.json package-lock.json".to_string()),
+            test_command: Some(
+                "! grep -q -e '\"broken\": \"\\^2' -e '\"flaky\": \"\\^3' package.json".to_string(),
+            ),
+            cwd: None,
+            strategy: None,
+        };
+        (dir, options)
+    }
+
+    fn updates() -> Vec<UpdateResult> {
+        vec![
+            UpdateResult::for_test("a", "^1.0.0", "^2.0.0"),
+            UpdateResult::for_test("broken", "^1.0.0", "^2.0.0"),
+            UpdateResult::for_test("c", "^1.0.0", "^2.0.0"),
+            UpdateResult::for_test("flaky", "^1.0.0", "^3.0.0"),
+        ]
+    }
+
+    fn names(updates: &[UpdateResult]) -> Vec<&str> {
+        updates.iter().map(|u| u.name.as_str()).collect()
+    }
+
+    #[tokio::test]
+    async fn test_iterate_keeps_passing_upgrades() {
+        let (dir, options) = project("iterate");
+        let manifest = dir.join("package.json");
+        let report = run(manifest.to_str().unwrap(), updates(), &options, None)
+            .await
+            .unwrap();
+
+        assert_eq!(names(&report.passed), vec!["a", "c"]);
+        let failed: Vec<&str> = report
+            .failed
+            .iter()
+            .map(|f| f.update.name.as_str())
+            .collect();
+        assert_eq!(failed, vec!["broken", "flaky"]);
+        // baseline, all, then one run per upgrade
+        assert_eq!(report.runs, 6);
+
+        let text = std::fs::read_to_string(&manifest).unwrap();
+        assert!(text.contains("\"a\": \"^2.0.0\""));
+        assert!(text.contains("\"broken\": \"^1.0.0\""));
+        assert!(text.contains("\"c\": \"^2.0.0\""));
+        assert!(text.contains("\"flaky\": \"^1.0.0\""));
+        // The final install ran against the kept upgrades
+        assert_eq!(
+            std::fs::read_to_string(dir.join("package-lock.json")).unwrap(),
+            text
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_bisect_and_failing_baseline() {
+        let (dir, mut options) = project("bisect");
+        let manifest = dir.join("package.json");
+        options.strategy = Some("bisect".to_string());
+        let report = run(manifest.to_str().unwrap(), updates(), &options, None)
+            .await
+            .unwrap();
+        assert_eq!(names(&report.passed), vec!["a", "c"]);
+        let mut failed: Vec<&str> = report
+            .failed
+            .iter()
+            .map(|f| f.update.name.as_str())
+            .collect();
+        failed.sort();
+        assert_eq!(failed, vec!["broken", "flaky"]);
+
+        // Nothing is touched when the project is already broken
+        let before = std::fs::read_to_string(&manifest).unwrap();
+        options.test_command = Some("echo failing; exit 1".to_string());
+        let err = run(manifest.to_str().unwrap(), updates(), &options, None)
+            .await
+            .unwrap_err();
+        assert!(err.contains("failing"));
+        assert_eq!(std::fs::read_to_string(&manifest).unwrap(), before);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,135 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+/// A registry response as saved to, and served from, a fixture directory
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordedResponse {
+    pub url: String,
+    pub status: u16,
+    pub headers: BTreeMap<String, String>,
+    pub body: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FixtureMode {
+    /// Hit the network and save every response
+    Record,
+    /// Serve saved responses and never touch the network
+    Replay,
+}
+
+impl FixtureMode {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "record" => Ok(FixtureMode::Record),
+            "replay" => Ok(FixtureMode::Replay),
+            other => Err(format!(
+                "Unknown fixture mode \"{}\" (expected \"record\" or \"replay\")",
+                other
+            )),
+        }
+    }
+}
+
+/// Directory of recorded registry responses, one JSON file per request
+pub struct FixtureStore {
+    dir: PathBuf,
+    mode: FixtureMode,
+}
+
+impl FixtureStore {
+    pub fn new(dir: &str, mode: FixtureMode) -> Self {
+        Self {
+            dir: PathBuf::from(dir),
+            mode,
+        }
+    }
+
+    pub fn mode(&self) -> FixtureMode {
+        self.mode
+    }
+
+    /// File holding the response for `key` (the request URL, plus anything else
+    /// that changes the response)
+    pub fn path_for(&self, key: &str) -> PathBuf {
+        let key = key
+            .trim_start_matches("https://")
+            .trim_start_matches("http://");
+        let mut file = String::with_capacity(key.len() + 5);
+        for byte in key.bytes() {
+            match byte {
+                b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'.' | b'-' | b'_' | b'@' => {
+                    file.push(byte as char)
+                }
+                _ => file.push_str(&format!("%{:02X}", byte)),
+            }
+        }
+        file.push_str(".json");
+        self.dir.join(file)
+    }
+
+    pub fn load(&self, key: &str) -> Result<RecordedResponse, String> {
+        let path = self.path_for(key);
+        let content = std::fs::read_to_string(&path)
+            .map_err(|e| format!("No fixture for {} at {}: {}", key, path.display(), e))?;
+        serde_json::from_str(&content)
+            .map_err(|e| format!("Invalid fixture {}: {}", path.display(), e))
+    }
+
+    pub fn save(&self, key: &str, response: &RecordedResponse) -> Result<(), String> {
+        std::fs::create_dir_all(&self.dir).map_err(|e| format!("Mkdir: {}", e))?;
+        let json =
+            serde_json::to_string_pretty(response).map_err(|e| format!("Serialize: {}", e))?;
+
+        // Atomic write, as concurrent fetches may record at the same time
+        let path = self.path_for(key);
+        let tmp_path = path.with_extension(format!("{}.tmp", std::process::id()));
+        std::fs::write(&tmp_path, json).map_err(|e| format!("Write tmp: {}", e))?;
+        std::fs::rename(&tmp_path, &path).map_err(|e| format!("Rename: {}", e))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_path_for_escapes_url() {
+        let store = FixtureStore::new("/fixtures", FixtureMode::Replay);
+        assert_eq!(
+            store.path_for("https://registry.npmjs.org/@types%2fnode"),
+            PathBuf::from("/fixtures/registry.npmjs.org%2F@types%252fnode.json")
+        );
+        assert_ne!(
+            store.path_for("http://localhost:4873/a/b"),
+            store.path_for("http://localhost:4873/a_b")
+        );
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let dir = std::env::temp_dir().join(format!("turbo-ncu-fixtures-{}", std::process::id()));
+        let store = FixtureStore::new(dir.to_str().unwrap(), FixtureMode::Record);
+        let response = RecordedResponse {
+            url: "https://registry.npmjs.org/lodash".to_string(),
+            status: 200,
+            headers: BTreeMap::from([("etag".to_string(), "\"abc\"".to_string())]),
+            body: r#"{"name":"lodash"}"#.to_string(),
+        };
+
+        store.save(&response.url, &response).unwrap();
+        assert_eq!(store.load(&response.url).unwrap(), response);
+        assert!(store.load("https://registry.npmjs.org/chalk").is_err());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_parse_mode() {
+        assert_eq!(FixtureMode::parse("record"), Ok(FixtureMode::Record));
+        assert_eq!(FixtureMode::parse("replay"), Ok(FixtureMode::Replay));
+        assert!(FixtureMode::parse("live").is_err());
+    }
+}
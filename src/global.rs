@@ -0,0 +1,382 @@
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::types::GlobalPackage;
+
+pub const MANAGERS: [&str; 4] = ["npm", "pnpm", "yarn", "bun"];
+
+/// Environment variables and home directory that global locations and npm
+/// settings are derived from, captured once so they can be tested without
+/// touching the process environment
+pub struct Environment {
+    vars: HashMap<String, String>,
+    home: Option<PathBuf>,
+}
+
+impl Environment {
+    pub fn from_process() -> Self {
+        let vars: HashMap<String, String> = std::env::vars().collect();
+        let home = vars
+            .get("HOME")
+            .or_else(|| vars.get("USERPROFILE"))
+            .map(PathBuf::from);
+        Self::new(vars, home)
+    }
+
+    pub fn new(vars: HashMap<String, String>, home: Option<PathBuf>) -> Self {
+        Self { vars, home }
+    }
+
+    pub fn var(&self, key: &str) -> Option<&str> {
+        self.vars
+            .get(key)
+            .map(String::as_str)
+            .filter(|v| !v.is_empty())
+    }
+
+    /// npm reads `npm_config_*` variables case-insensitively
+    fn npm_config(&self, key: &str) -> Option<&str> {
+        let name = format!("npm_config_{}", key.replace('-', "_"));
+        self.vars
+            .iter()
+            .find(|(k, v)| k.eq_ignore_ascii_case(&name) && !v.is_empty())
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// npm setting `key`: its `npm_config_*` variable, then the user's .npmrc
+    pub fn npm_setting(&self, key: &str) -> Option<String> {
+        self.npm_config(key)
+            .map(|value| self.expand(value))
+            .or_else(|| self.npmrc(key))
+    }
+
+    fn home_join(&self, path: &str) -> Option<PathBuf> {
+        self.home.as_ref().map(|home| home.join(path))
+    }
+
+    /// `key` from the user's .npmrc, with `${VAR}` references expanded
+    fn npmrc(&self, key: &str) -> Option<String> {
+        let path = self
+            .npm_config("userconfig")
+            .map(PathBuf::from)
+            .or_else(|| self.home_join(".npmrc"))?;
+        let text = std::fs::read_to_string(path).ok()?;
+        text.lines()
+            .map(str::trim)
+            .filter(|line| !line.starts_with(['#', ';']))
+            .filter_map(|line| line.split_once('='))
+            .rfind(|(k, _)| k.trim() == key)
+            .map(|(_, v)| self.expand(v.trim().trim_matches('"')))
+    }
+
+    fn expand(&self, value: &str) -> String {
+        let mut output = String::new();
+        let mut rest = value;
+        while let Some(start) = rest.find("${") {
+            let Some(end) = rest[start..].find('}') else {
+                break;
+            };
+            output.push_str(&rest[..start]);
+            output.push_str(self.var(&rest[start + 2..start + end]).unwrap_or_default());
+            rest = &rest[start + end + 1..];
+        }
+        output.push_str(rest);
+        match output.strip_prefix("~/") {
+            Some(path) => self
+                .home_join(path)
+                .map_or(output.clone(), |p| p.to_string_lossy().to_string()),
+            None => output,
+        }
+    }
+}
+
+/// Packages installed globally by each of `managers`
+pub fn global_packages(
+    env: &Environment,
+    managers: &[String],
+) -> Result<Vec<GlobalPackage>, String> {
+    let mut packages = Vec::new();
+    for manager in managers {
+        let found = match manager.as_str() {
+            "npm" => npm_root(env).map(|root| scan_node_modules(&root)),
+            "pnpm" => pnpm_dir(env).map(|dir| manifest_dependencies(&dir)),
+            "yarn" => yarn_dir(env).map(|dir| manifest_dependencies(&dir)),
+            "bun" => bun_dir(env).map(|dir| manifest_dependencies(&dir)),
+            other => {
+                return Err(format!(
+                    "Unknown package manager \"{}\"; expected one of {}",
+                    other,
+                    MANAGERS.join(", ")
+                ))
+            }
+        };
+        packages.extend(
+            found
+                .unwrap_or_default()
+                .into_iter()
+                .map(|(name, version, path)| GlobalPackage {
+                    name,
+                    version,
+                    manager: manager.clone(),
+                    path: path.to_string_lossy().to_string(),
+                }),
+        );
+    }
+    Ok(packages)
+}
+
+/// npm's global prefix: `npm_config_prefix`, then `prefix` in .npmrc, then the
+/// installation `node` runs from, as `npm prefix -g` resolves it
+fn npm_prefix(env: &Environment) -> Option<PathBuf> {
+    if let Some(prefix) = env.npm_setting("prefix") {
+        return Some(PathBuf::from(prefix));
+    }
+    let node = if cfg!(windows) { "node.exe" } else { "node" };
+    let bin = std::env::split_paths(env.var("PATH")?).find(|dir| dir.join(node).is_file())?;
+    if cfg!(windows) {
+        Some(bin)
+    } else {
+        bin.parent().map(Path::to_path_buf)
+    }
+}
+
+fn npm_root(env: &Environment) -> Option<PathBuf> {
+    let prefix = npm_prefix(env)?;
+    Some(if cfg!(windows) {
+        prefix.join("node_modules")
+    } else {
+        prefix.join("lib").join("node_modules")
+    })
+}
+
+/// pnpm keeps global packages in `<global-dir>/<layout version>/`, the
+/// global dir defaulting to `$PNPM_HOME/global`
+fn pnpm_dir(env: &Environment) -> Option<PathBuf> {
+    let global = env
+        .npm_config("global-dir")
+        .map(PathBuf::from)
+        .or_else(|| env.npmrc("global-dir").map(PathBuf::from))
+        .or_else(|| pnpm_home(env).map(|home| home.join("global")))?;
+    // The highest layout version present is the one in use
+    std::fs::read_dir(&global)
+        .ok()?
+        .flatten()
+        .filter_map(|entry| {
+            let version = entry.file_name().to_string_lossy().parse::<u32>().ok()?;
+            Some((version, entry.path()))
+        })
+        .max_by_key(|(version, _)| *version)
+        .map(|(_, path)| path)
+}
+
+fn pnpm_home(env: &Environment) -> Option<PathBuf> {
+    if let Some(home) = env.var("PNPM_HOME") {
+        return Some(PathBuf::from(home));
+    }
+    if cfg!(windows) {
+        env.var("LOCALAPPDATA")
+            .map(|dir| Path::new(dir).join("pnpm"))
+    } else if cfg!(target_os = "macos") {
+        env.home_join("Library/pnpm")
+    } else {
+        env.var("XDG_DATA_HOME")
+            .map(|dir| Path::new(dir).join("pnpm"))
+            .or_else(|| env.home_join(".local/share/pnpm"))
+    }
+}
+
+/// Yarn classic's global folder (yarn 2+ has no global installs)
+fn yarn_dir(env: &Environment) -> Option<PathBuf> {
+    if let Some(folder) = env.var("YARN_GLOBAL_FOLDER") {
+        return Some(PathBuf::from(folder));
+    }
+    if cfg!(windows) {
+        env.var("LOCALAPPDATA")
+            .map(|dir| Path::new(dir).join("Yarn").join("Data").join("global"))
+    } else {
+        env.var("XDG_CONFIG_HOME")
+            .map(|dir| Path::new(dir).join("yarn"))
+            .or_else(|| env.home_join(".config/yarn"))
+            .map(|dir| dir.join("global"))
+    }
+}
+
+fn bun_dir(env: &Environment) -> Option<PathBuf> {
+    if let Some(dir) = env.var("BUN_INSTALL_GLOBAL_DIR") {
+        return Some(PathBuf::from(dir));
+    }
+    env.var("BUN_INSTALL")
+        .map(PathBuf::from)
+        .or_else(|| env.home_join(".bun"))
+        .map(|dir| dir.join("install").join("global"))
+}
+
+/// Name and version of the package installed at `dir`
+pub fn installed(dir: &Path) -> Option<(String, String)> {
+    let text = std::fs::read_to_string(dir.join("package.json")).ok()?;
+    let manifest: Value = serde_json::from_str(&text).ok()?;
+    let name = manifest.get("name")?.as_str()?.to_string();
+    let version = manifest.get("version")?.as_str()?.to_string();
+    Some((name, version))
+}
+
+/// Every package directly inside a `node_modules`, scoped ones included
+pub fn scan_node_modules(root: &Path) -> Vec<(String, String, PathBuf)> {
+    let mut packages = Vec::new();
+    let mut dirs: Vec<PathBuf> = Vec::new();
+    for entry in std::fs::read_dir(root).into_iter().flatten().flatten() {
+        let name = entry.file_name().to_string_lossy().to_string();
+        if name.starts_with('.') {
+            continue;
+        }
+        if name.starts_with('@') {
+            dirs.extend(
+                std::fs::read_dir(entry.path())
+                    .into_iter()
+                    .flatten()
+                    .flatten()
+                    .map(|scoped| scoped.path()),
+            );
+        } else {
+            dirs.push(entry.path());
+        }
+    }
+    dirs.sort();
+    for dir in dirs {
+        if let Some((name, version)) = installed(&dir) {
+            packages.push((name, version, dir));
+        }
+    }
+    packages
+}
+
+/// Packages a global folder's package.json depends on, at their installed
+/// versions; pnpm, yarn and bun all keep global installs this way
+fn manifest_dependencies(dir: &Path) -> Vec<(String, String, PathBuf)> {
+    let Ok(text) = std::fs::read_to_string(dir.join("package.json")) else {
+        return Vec::new();
+    };
+    let Ok(manifest) = serde_json::from_str::<Value>(&text) else {
+        return Vec::new();
+    };
+    let mut names: Vec<&String> = manifest
+        .get("dependencies")
+        .and_then(Value::as_object)
+        .into_iter()
+        .flat_map(|deps| deps.keys())
+        .collect();
+    names.sort();
+    names
+        .into_iter()
+        .filter_map(|name| {
+            let path = dir.join("node_modules").join(name);
+            let (_, version) = installed(&path)?;
+            Some((name.clone(), version, path))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(path: &Path, text: &str) {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, text).unwrap();
+    }
+
+    fn install(node_modules: &Path, name: &str, version: &str) {
+        write(
+            &node_modules.join(name).join("package.json"),
+            &format!(r#"{{ "name": "{}", "version": "{}" }}"#, name, version),
+        );
+    }
+
+    #[test]
+    fn test_reads_each_manager_from_disk() {
+        let root = std::env::temp_dir().join(format!("turbo-ncu-global-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+
+        let npm_modules = if cfg!(windows) {
+            root.join("npm").join("node_modules")
+        } else {
+            root.join("npm").join("lib").join("node_modules")
+        };
+        install(&npm_modules, "npm", "10.2.0");
+        install(&npm_modules, "@vue/cli", "5.0.8");
+        write(&npm_modules.join(".bin").join("vue"), "");
+
+        let pnpm = root.join("pnpm-home").join("global");
+        write(&pnpm.join("4").join("package.json"), "{}");
+        write(
+            &pnpm.join("5").join("package.json"),
+            r#"{ "dependencies": { "typescript": "^5.0.0" } }"#,
+        );
+        install(&pnpm.join("5").join("node_modules"), "typescript", "5.4.5");
+
+        let yarn = root.join("yarn");
+        write(
+            &yarn.join("package.json"),
+            r#"{ "dependencies": { "serve": "^14.0.0", "missing": "^1.0.0" } }"#,
+        );
+        install(&yarn.join("node_modules"), "serve", "14.2.1");
+
+        let bun = root.join("bun").join("install").join("global");
+        write(
+            &bun.join("package.json"),
+            r#"{ "dependencies": { "@biomejs/biome": "^1.8.0" } }"#,
+        );
+        install(&bun.join("node_modules"), "@biomejs/biome", "1.8.3");
+
+        let path = |p: PathBuf| p.to_string_lossy().to_string();
+        let env = Environment {
+            vars: HashMap::from([
+                ("NPM_CONFIG_PREFIX".to_string(), path(root.join("npm"))),
+                (
+                    "NPM_CONFIG_USERCONFIG".to_string(),
+                    path(root.join("npmrc")),
+                ),
+                ("PNPM_HOME".to_string(), path(root.join("pnpm-home"))),
+                ("YARN_GLOBAL_FOLDER".to_string(), path(yarn.clone())),
+                ("BUN_INSTALL".to_string(), path(root.join("bun"))),
+            ]),
+            home: None,
+        };
+        let managers: Vec<String> = MANAGERS.iter().map(|m| m.to_string()).collect();
+        let found: Vec<(String, String, String)> = global_packages(&env, &managers)
+            .unwrap()
+            .into_iter()
+            .map(|p| (p.manager, p.name, p.version))
+            .collect();
+        let expected: Vec<(String, String, String)> = [
+            ("npm", "@vue/cli", "5.0.8"),
+            ("npm", "npm", "10.2.0"),
+            ("pnpm", "typescript", "5.4.5"),
+            ("yarn", "serve", "14.2.1"),
+            ("bun", "@biomejs/biome", "1.8.3"),
+        ]
+        .iter()
+        .map(|(m, n, v)| (m.to_string(), n.to_string(), v.to_string()))
+        .collect();
+        assert_eq!(found, expected);
+
+        assert!(global_packages(&env, &["volta".to_string()]).is_err());
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn test_npmrc_prefix() {
+        let root = std::env::temp_dir().join(format!("turbo-ncu-global-rc-{}", std::process::id()));
+        write(
+            &root.join(".npmrc"),
+            "; comment\nregistry=https://registry.npmjs.org/\nprefix = ${PREFIX_BASE}/npm-global\n",
+        );
+        let env = Environment {
+            vars: HashMap::from([("PREFIX_BASE".to_string(), "/opt".to_string())]),
+            home: Some(root.clone()),
+        };
+        assert_eq!(npm_prefix(&env), Some(PathBuf::from("/opt/npm-global")));
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
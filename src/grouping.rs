@@ -0,0 +1,360 @@
+use node_semver::Version;
+use std::collections::{BTreeMap, HashSet};
+
+use crate::types::{GroupRule, UpdateGroup, UpdateResult};
+use crate::workspace;
+
+/// Scopes whose packages are versioned independently of each other
+const INDEPENDENT_SCOPES: [&str; 1] = ["@types"];
+
+/// Packages that should be upgraded together
+#[derive(Debug, Clone, PartialEq)]
+pub struct Family {
+    /// Rule name, scope or repository URL
+    pub name: String,
+    /// "rule", "repository" or "scope"
+    pub source: &'static str,
+    /// Indices of the members in the list families were formed from
+    pub members: Vec<usize>,
+}
+
+/// One upgrade of a family member
+pub struct Member {
+    /// Version the member would move to on its own
+    pub target: Version,
+    /// Version the member is at now, when known
+    pub current: Option<Version>,
+    /// Versions the member may move to
+    pub candidates: Vec<String>,
+}
+
+/// Group `packages` (registry name and repository URL, when known) into
+/// families of at least two distinct packages. A matching rule wins over a
+/// shared repository, which wins over a shared scope
+pub fn families(packages: &[(&str, Option<&str>)], rules: &[GroupRule]) -> Vec<Family> {
+    let mut by_key: BTreeMap<(u8, String), Vec<usize>> = BTreeMap::new();
+    for (i, (name, repository)) in packages.iter().enumerate() {
+        let key = if let Some(rule) = rules.iter().position(|rule| {
+            rule.packages
+                .iter()
+                .any(|pattern| workspace::wildcard_match(pattern, name))
+        }) {
+            (0, rules[rule].name.clone())
+        } else if let Some(repository) = repository.and_then(normalize_repository) {
+            (1, repository)
+        } else if let Some(scope) = scope(name) {
+            (2, scope.to_string())
+        } else {
+            continue;
+        };
+        by_key.entry(key).or_default().push(i);
+    }
+
+    by_key
+        .into_iter()
+        .filter(|(_, members)| {
+            members
+                .iter()
+                .any(|&m| packages[m].0 != packages[members[0]].0)
+        })
+        .map(|((source, name), members)| Family {
+            name,
+            source: ["rule", "repository", "scope"][source as usize],
+            members,
+        })
+        .collect()
+}
+
+/// Move each family among `updates` (package index and update, in order) to
+/// its shared version. `name` gives a package's registry name, `candidates`
+/// the versions it may move to (None leaves its family alone) and `rebuild`
+/// its update at the shared version, None when it then has nothing to do.
+/// Returns the updates left and the groups they form
+pub fn group_updates<'a>(
+    mut updates: Vec<(usize, UpdateResult)>,
+    rules: &[GroupRule],
+    name: impl Fn(usize) -> &'a str,
+    candidates: impl Fn(usize) -> Option<Vec<String>>,
+    mut rebuild: impl FnMut(usize, &Version) -> Option<UpdateResult>,
+) -> (Vec<(usize, UpdateResult)>, Vec<UpdateGroup>) {
+    let keys: Vec<(&str, Option<&str>)> = updates
+        .iter()
+        .map(|(i, update)| {
+            let repository = update
+                .metadata
+                .as_ref()
+                .and_then(|m| m.repository.as_deref());
+            (name(*i), repository)
+        })
+        .collect();
+    let found = families(&keys, rules);
+
+    let mut groups = Vec::new();
+    let mut dropped = HashSet::new();
+    for family in found {
+        let members: Vec<Member> = family
+            .members
+            .iter()
+            .map_while(|&m| {
+                let (i, update) = &updates[m];
+                let current = update
+                    .installed_version
+                    .as_deref()
+                    .unwrap_or(&update.current_version);
+                Some(Member {
+                    target: update.latest.parse().ok()?,
+                    current: current.parse().ok(),
+                    candidates: candidates(*i)?,
+                })
+            })
+            .collect();
+        if members.len() < family.members.len() {
+            continue;
+        }
+        let version = shared_version(&members);
+        let highest = members.iter().map(|m| &m.target).max();
+        let fell_back = version.as_ref().zip(highest).is_some_and(|(v, h)| v < h);
+
+        let mut grouped = Vec::new();
+        for &m in &family.members {
+            let (i, update) = &mut updates[m];
+            if let Some(version) = version.as_ref().filter(|v| update.latest != v.to_string()) {
+                match rebuild(*i, version) {
+                    Some(rebuilt) => *update = rebuilt,
+                    None => {
+                        dropped.insert(m);
+                        continue;
+                    }
+                }
+            }
+            grouped.push(update.clone());
+        }
+        if !grouped.is_empty() {
+            groups.push(UpdateGroup {
+                name: family.name,
+                source: family.source.to_string(),
+                version: version.map(|v| v.to_string()),
+                fell_back,
+                updates: grouped,
+            });
+        }
+    }
+
+    let updates = updates
+        .into_iter()
+        .enumerate()
+        .filter(|(m, _)| !dropped.contains(m))
+        .map(|(_, u)| u)
+        .collect();
+    (updates, groups)
+}
+
+/// Whether `families` could put the package in a family, depending on which
+/// other packages it is checked with
+pub fn may_group(name: &str, repository: Option<&str>, rules: &[GroupRule]) -> bool {
+    rules.iter().any(|rule| {
+        rule.packages
+            .iter()
+            .any(|pattern| workspace::wildcard_match(pattern, name))
+    }) || repository.and_then(normalize_repository).is_some()
+        || scope(name).is_some()
+}
+
+/// Version a family moves to: the highest target among its members when all
+/// of them can reach it, otherwise the highest version every member can reach
+/// without a downgrade. None when the members share no such version
+pub fn shared_version(members: &[Member]) -> Option<Version> {
+    let highest = members.iter().map(|m| &m.target).max()?;
+    // Reachable: a candidate of each member, within each member's own target
+    let reachable = |version: &Version| {
+        members.iter().all(|m| {
+            *version <= m.target
+                && m.current.as_ref().is_none_or(|current| version >= current)
+                && m.candidates
+                    .iter()
+                    .any(|c| c.parse::<Version>().is_ok_and(|c| c == *version))
+        })
+    };
+    if reachable(highest) {
+        return Some(highest.clone());
+    }
+    members[0]
+        .candidates
+        .iter()
+        .filter_map(|c| c.parse::<Version>().ok())
+        .filter(|v| reachable(v))
+        .max()
+}
+
+fn scope(name: &str) -> Option<&str> {
+    let (scope, _) = name.split_once('/')?;
+    (scope.starts_with('@') && !INDEPENDENT_SCOPES.contains(&scope)).then_some(scope)
+}
+
+/// Repository URLs in a comparable form: `git+https://github.com/a/b.git`,
+/// `git@github.com:a/b` and `github:a/b` all become `https://github.com/a/b`
+pub fn normalize_repository(url: &str) -> Option<String> {
+    let url = url.trim().split('#').next().unwrap_or_default();
+    let url = url.strip_prefix("git+").unwrap_or(url);
+    let url = if let Some(path) = url.strip_prefix("github:") {
+        format!("https://github.com/{}", path)
+    } else if let Some(rest) = url.strip_prefix("git@") {
+        format!("https://{}", rest.replacen(':', "/", 1))
+    } else if let Some(rest) = url
+        .strip_prefix("git://")
+        .or_else(|| url.strip_prefix("ssh://git@"))
+        .or_else(|| url.strip_prefix("http://"))
+    {
+        format!("https://{}", rest)
+    } else if url.contains("://") {
+        url.to_string()
+    } else if url.split('/').count() == 2 {
+        // npm's shorthand for GitHub
+        format!("https://github.com/{}", url)
+    } else {
+        return None;
+    };
+    let url = url.trim_end_matches('/');
+    let url = url.strip_suffix(".git").unwrap_or(url);
+    Some(url.to_ascii_lowercase()).filter(|url| url.len() > "https://".len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn member(target: &str, current: &str, candidates: &[&str]) -> Member {
+        Member {
+            target: target.parse().unwrap(),
+            current: current.parse().ok(),
+            candidates: candidates.iter().map(|v| v.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_forms_families() {
+        let rules = vec![GroupRule {
+            name: "react".to_string(),
+            packages: vec!["react".to_string(), "react-dom".to_string()],
+        }];
+        let packages = [
+            ("@babel/core", None),
+            ("react", None),
+            ("@babel/preset-env", None),
+            ("react-dom", None),
+            ("@types/node", None),
+            ("@types/react", None),
+            (
+                "eslint-plugin-a",
+                Some("git+https://github.com/acme/lint.git"),
+            ),
+            ("eslint-plugin-b", Some("git@github.com:Acme/lint")),
+            ("@scope/only", None),
+            ("@scope/only", None),
+        ];
+        let found = families(&packages, &rules);
+        let summary: Vec<(&str, &str, Vec<usize>)> = found
+            .iter()
+            .map(|f| (f.source, f.name.as_str(), f.members.clone()))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("rule", "react", vec![1, 3]),
+                ("repository", "https://github.com/acme/lint", vec![6, 7]),
+                ("scope", "@babel", vec![0, 2]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_group_updates() {
+        let all = ["7.23.0", "7.24.0", "7.24.5"];
+        let updates = vec![
+            (
+                0,
+                UpdateResult::for_test("@babel/core", "^7.23.0", "^7.24.5"),
+            ),
+            (1, UpdateResult::for_test("lodash", "^4.17.20", "^4.17.21")),
+            (
+                2,
+                UpdateResult::for_test("@babel/preset-env", "^7.23.0", "^7.24.0"),
+            ),
+            (
+                3,
+                UpdateResult::for_test("@babel/cli", "^7.24.0", "^7.24.5"),
+            ),
+            (4, UpdateResult::for_test("@acme/a", "^1.0.0", "^2.0.0")),
+            (5, UpdateResult::for_test("@acme/b", "^1.0.0", "^2.0.0")),
+        ];
+        let names = updates
+            .iter()
+            .map(|(_, u)| u.name.clone())
+            .collect::<Vec<_>>();
+        let candidates = |i: usize| match i {
+            2 => Some(vec!["7.23.0".to_string(), "7.24.0".to_string()]),
+            // Version fixed elsewhere: its family is left alone
+            4 => None,
+            _ => Some(all.iter().map(|v| v.to_string()).collect()),
+        };
+        let mut rebuilt = Vec::new();
+        let rebuild = |i: usize, version: &Version| {
+            rebuilt.push(i);
+            // @babel/cli already declares the shared version
+            (i != 3).then(|| {
+                let (_, update) = &updates[i];
+                UpdateResult::for_test(&update.name, &update.current, &format!("^{}", version))
+            })
+        };
+
+        let (left, groups) = group_updates(
+            updates.clone(),
+            &[],
+            |i| names[i].as_str(),
+            candidates,
+            rebuild,
+        );
+        assert_eq!(rebuilt, vec![0, 3]);
+        let left: Vec<(usize, &str)> = left.iter().map(|(i, u)| (*i, u.latest.as_str())).collect();
+        assert_eq!(
+            left,
+            vec![
+                (0, "7.24.0"),
+                (1, "4.17.21"),
+                (2, "7.24.0"),
+                (4, "2.0.0"),
+                (5, "2.0.0")
+            ]
+        );
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].name, "@babel");
+        assert_eq!(groups[0].version.as_deref(), Some("7.24.0"));
+        assert!(groups[0].fell_back);
+        assert_eq!(groups[0].updates.len(), 2);
+    }
+
+    #[test]
+    fn test_shared_version_falls_back() {
+        let all = ["7.23.0", "7.24.0", "7.24.5"];
+        // Both can reach the highest target
+        let members = vec![
+            member("7.24.5", "7.23.0", &all),
+            member("7.24.5", "7.23.0", &all),
+        ];
+        assert_eq!(shared_version(&members).unwrap().to_string(), "7.24.5");
+
+        // One member never published 7.24.5: both move to 7.24.0
+        let members = vec![
+            member("7.24.5", "7.23.0", &all),
+            member("7.24.0", "7.23.0", &["7.23.0", "7.24.0"]),
+        ];
+        assert_eq!(shared_version(&members).unwrap().to_string(), "7.24.0");
+
+        // Nothing in common without a downgrade
+        let members = vec![
+            member("20.1.0", "20.0.0", &["18.3.0", "20.0.0", "20.1.0"]),
+            member("18.3.0", "18.2.0", &["18.2.0", "18.3.0"]),
+        ];
+        assert_eq!(shared_version(&members), None);
+    }
+}
@@ -0,0 +1,483 @@
+use std::ops::Range;
+
+use crate::types::UpdateResult;
+
+/// A parsed JSON value. Strings keep the byte range of their token, so edits
+/// can replace exactly that token and leave every other byte alone
+#[derive(Debug)]
+pub enum Node {
+    Object {
+        members: Vec<Member>,
+    },
+    Array {
+        items: Vec<Node>,
+    },
+    String {
+        value: String,
+        span: Range<usize>,
+    },
+    /// Numbers, booleans and null
+    Scalar,
+}
+
+#[derive(Debug)]
+pub struct Member {
+    pub key: String,
+    pub value: Node,
+}
+
+impl Node {
+    /// Value of member `key`; the last one wins, as with `JSON.parse`
+    pub fn get(&self, key: &str) -> Option<&Node> {
+        self.get_all(key).pop()
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Node::String { value, .. } => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Values of every member named `key`; duplicates are all returned, in
+    /// source order (`JSON.parse` keeps the last)
+    pub fn get_all(&self, key: &str) -> Vec<&Node> {
+        match self {
+            Node::Object { members, .. } => members
+                .iter()
+                .filter(|m| m.key == key)
+                .map(|m| &m.value)
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Resolve an RFC 6901 JSON pointer such as `/dependencies/@types~1node`,
+    /// following every duplicate key along the way
+    pub fn pointer_all(&self, pointer: &str) -> Vec<&Node> {
+        let mut nodes = vec![self];
+        if pointer.is_empty() {
+            return nodes;
+        }
+        for token in pointer.trim_start_matches('/').split('/') {
+            let token = token.replace("~1", "/").replace("~0", "~");
+            nodes = nodes
+                .into_iter()
+                .flat_map(|node| match node {
+                    Node::Array { items, .. } => token
+                        .parse::<usize>()
+                        .ok()
+                        .and_then(|i| items.get(i))
+                        .into_iter()
+                        .collect(),
+                    _ => node.get_all(&token),
+                })
+                .collect();
+        }
+        nodes
+    }
+}
+
+/// Escape a key for use as one JSON pointer token
+pub fn pointer_token(key: &str) -> String {
+    key.replace('~', "~0").replace('/', "~1")
+}
+
+/// Parse `text` keeping source spans. A leading byte order mark is skipped
+pub fn parse(text: &str) -> Result<Node, String> {
+    let mut parser = Parser {
+        bytes: text.as_bytes(),
+        text,
+        pos: if text.starts_with('\u{feff}') { 3 } else { 0 },
+    };
+    parser.skip_whitespace();
+    let node = parser.value()?;
+    parser.skip_whitespace();
+    if parser.pos != parser.bytes.len() {
+        return Err(parser.error("Unexpected content after JSON value"));
+    }
+    Ok(node)
+}
+
+struct Parser<'a> {
+    bytes: &'a [u8],
+    text: &'a str,
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn error(&self, message: &str) -> String {
+        let line = self.text[..self.pos.min(self.text.len())]
+            .matches('\n')
+            .count()
+            + 1;
+        format!("{} at line {} (byte {})", message, line, self.pos)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.pos < self.bytes.len()
+            && matches!(self.bytes[self.pos], b' ' | b'\t' | b'\n' | b'\r')
+        {
+            self.pos += 1;
+        }
+    }
+
+    fn expect(&mut self, byte: u8) -> Result<(), String> {
+        if self.bytes.get(self.pos) == Some(&byte) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(self.error(&format!("Expected '{}'", byte as char)))
+        }
+    }
+
+    fn value(&mut self) -> Result<Node, String> {
+        match self.bytes.get(self.pos) {
+            Some(b'{') => self.object(),
+            Some(b'[') => self.array(),
+            Some(b'"') => {
+                let (value, span) = self.string()?;
+                Ok(Node::String { value, span })
+            }
+            Some(b'-' | b'0'..=b'9') => self.number(),
+            Some(_) => {
+                let start = self.pos;
+                for literal in ["true", "false", "null"] {
+                    if self.text[start..].starts_with(literal) {
+                        self.pos += literal.len();
+                        return Ok(Node::Scalar);
+                    }
+                }
+                Err(self.error("Unexpected character"))
+            }
+            None => Err(self.error("Unexpected end of input")),
+        }
+    }
+
+    fn object(&mut self) -> Result<Node, String> {
+        self.expect(b'{')?;
+        let mut members = Vec::new();
+        self.skip_whitespace();
+        if self.bytes.get(self.pos) == Some(&b'}') {
+            self.pos += 1;
+            return Ok(Node::Object { members });
+        }
+        loop {
+            self.skip_whitespace();
+            if self.bytes.get(self.pos) != Some(&b'"') {
+                return Err(self.error("Expected a string key"));
+            }
+            let (key, _) = self.string()?;
+            self.skip_whitespace();
+            self.expect(b':')?;
+            self.skip_whitespace();
+            let value = self.value()?;
+            members.push(Member { key, value });
+            self.skip_whitespace();
+            match self.bytes.get(self.pos) {
+                Some(b',') => self.pos += 1,
+                Some(b'}') => {
+                    self.pos += 1;
+                    return Ok(Node::Object { members });
+                }
+                _ => return Err(self.error("Expected ',' or '}'")),
+            }
+        }
+    }
+
+    fn array(&mut self) -> Result<Node, String> {
+        self.expect(b'[')?;
+        let mut items = Vec::new();
+        self.skip_whitespace();
+        if self.bytes.get(self.pos) == Some(&b']') {
+            self.pos += 1;
+            return Ok(Node::Array { items });
+        }
+        loop {
+            self.skip_whitespace();
+            items.push(self.value()?);
+            self.skip_whitespace();
+            match self.bytes.get(self.pos) {
+                Some(b',') => self.pos += 1,
+                Some(b']') => {
+                    self.pos += 1;
+                    return Ok(Node::Array { items });
+                }
+                _ => return Err(self.error("Expected ',' or ']'")),
+            }
+        }
+    }
+
+    fn number(&mut self) -> Result<Node, String> {
+        let start = self.pos;
+        while self.pos < self.bytes.len()
+            && matches!(
+                self.bytes[self.pos],
+                b'-' | b'+' | b'.' | b'e' | b'E' | b'0'..=b'9'
+            )
+        {
+            self.pos += 1;
+        }
+        if self.text[start..self.pos].parse::<f64>().is_err() {
+            return Err(self.error("Invalid number"));
+        }
+        Ok(Node::Scalar)
+    }
+
+    /// Decode a string token, returning its value and the span including quotes
+    fn string(&mut self) -> Result<(String, Range<usize>), String> {
+        let start = self.pos;
+        self.expect(b'"')?;
+        let mut value = String::new();
+        loop {
+            let Some(&byte) = self.bytes.get(self.pos) else {
+                return Err(self.error("Unterminated string"));
+            };
+            match byte {
+                b'"' => {
+                    self.pos += 1;
+                    return Ok((value, start..self.pos));
+                }
+                b'\\' => {
+                    self.pos += 1;
+                    let escaped = match self.bytes.get(self.pos) {
+                        Some(b'"') => '"',
+                        Some(b'\\') => '\\',
+                        Some(b'/') => '/',
+                        Some(b'b') => '\u{8}',
+                        Some(b'f') => '\u{c}',
+                        Some(b'n') => '\n',
+                        Some(b'r') => '\r',
+                        Some(b't') => '\t',
+                        Some(b'u') => {
+                            self.pos += 1;
+                            let unit = self.hex4()?;
+                            let c = if (0xd800..0xdc00).contains(&unit)
+                                && self.text[self.pos..].starts_with("\\u")
+                            {
+                                self.pos += 2;
+                                let low = self.hex4()?;
+                                let combined = 0x10000
+                                    + ((unit - 0xd800) << 10)
+                                    + (low.wrapping_sub(0xdc00) & 0x3ff);
+                                char::from_u32(combined)
+                            } else {
+                                char::from_u32(unit)
+                            };
+                            value.push(c.unwrap_or('\u{fffd}'));
+                            continue;
+                        }
+                        _ => return Err(self.error("Invalid escape")),
+                    };
+                    value.push(escaped);
+                    self.pos += 1;
+                }
+                b if b < 0x20 => return Err(self.error("Control character in string")),
+                _ => {
+                    // Copy one whole UTF-8 character
+                    let c = self.text[self.pos..].chars().next().unwrap_or('\u{fffd}');
+                    value.push(c);
+                    self.pos += c.len_utf8();
+                }
+            }
+        }
+    }
+
+    fn hex4(&mut self) -> Result<u32, String> {
+        let digits = self
+            .text
+            .get(self.pos..self.pos + 4)
+            .ok_or_else(|| self.error("Truncated unicode escape"))?;
+        let unit =
+            u32::from_str_radix(digits, 16).map_err(|_| self.error("Invalid unicode escape"))?;
+        self.pos += 4;
+        Ok(unit)
+    }
+}
+
+/// Replace the string value at `pointer` (every duplicate of it) with
+/// `value`, leaving the rest of `text` untouched
+pub struct Edit {
+    pub pointer: String,
+    pub value: String,
+}
+
+/// Apply `edits` to `text`, returning the new text and how many string tokens
+/// were replaced. Pointers that do not lead to a string are skipped
+pub fn apply_edits(text: &str, edits: &[Edit]) -> Result<(String, u32), String> {
+    let root = parse(text)?;
+
+    let mut replacements: Vec<(Range<usize>, String)> = Vec::new();
+    for edit in edits {
+        for node in root.pointer_all(&edit.pointer) {
+            if let Node::String { value, span } = node {
+                if *value != edit.value {
+                    replacements.push((span.clone(), encode_string(&edit.value)));
+                }
+            }
+        }
+    }
+
+    replacements.sort_by_key(|(span, _)| span.start);
+    replacements.dedup_by(|a, b| a.0 == b.0);
+
+    let mut output = String::with_capacity(text.len());
+    let mut last = 0;
+    for (span, replacement) in &replacements {
+        output.push_str(&text[last..span.start]);
+        output.push_str(replacement);
+        last = span.end;
+    }
+    output.push_str(&text[last..]);
+    Ok((output, replacements.len() as u32))
+}
+
+/// Encode a string token, escaping only what JSON requires
+fn encode_string(value: &str) -> String {
+    serde_json::Value::String(value.to_string()).to_string()
+}
+
+/// Manifest section holding dependencies of the given type
+pub fn section_for_dep_type(dep_type: &str) -> Option<&'static str> {
+    match dep_type {
+        "prod" => Some("dependencies"),
+        "dev" => Some("devDependencies"),
+        "peer" => Some("peerDependencies"),
+        "optional" => Some("optionalDependencies"),
+        _ => None,
+    }
+}
+
+/// Write each update's `new_range` into the manifest at `path`, changing only
+/// the affected version strings. Updates are located by their `pointer`, or by
+/// dep type and name when it is unset. Returns the number of strings replaced
+pub fn apply_updates(path: &str, updates: &[UpdateResult]) -> Result<u32, String> {
+    let text =
+        std::fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+    let edits: Vec<Edit> = updates
+        .iter()
+        .filter_map(|update| {
+            let pointer = match &update.pointer {
+                Some(pointer) => pointer.clone(),
+                None => {
+                    let section = section_for_dep_type(&update.dep_type)?;
+                    format!("/{}/{}", section, pointer_token(&update.name))
+                }
+            };
+            Some(Edit {
+                pointer,
+                value: update.new_range.clone(),
+            })
+        })
+        .collect();
+
+    let (output, count) =
+        apply_edits(&text, &edits).map_err(|e| format!("Failed to parse {}: {}", path, e))?;
+    if count > 0 {
+        let tmp_path = format!("{}.tmp", path);
+        std::fs::write(&tmp_path, output).map_err(|e| format!("Write tmp: {}", e))?;
+        std::fs::rename(&tmp_path, path).map_err(|e| format!("Rename: {}", e))?;
+    }
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn edit(pointer: &str, value: &str) -> Edit {
+        Edit {
+            pointer: pointer.to_string(),
+            value: value.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_only_version_tokens_change() {
+        let text = "\u{feff}{\r\n\t\"name\": \"caf\\u00e9\",\r\n\t\"dependencies\": {\r\n\t\t\"lodash\":   \"^4.0.0\" ,\r\n\t\t\"@types/node\": \"^18.0.0\"\r\n\t},\r\n\t\"z\": [1, 2.5e3, true, null]\r\n}";
+        let (output, count) = apply_edits(
+            text,
+            &[
+                edit("/dependencies/lodash", "^4.17.21"),
+                edit("/dependencies/@types~1node", "^20.11.0"),
+                edit("/dependencies/missing", "^1.0.0"),
+            ],
+        )
+        .unwrap();
+
+        assert_eq!(count, 2);
+        assert_eq!(
+            output,
+            text.replace("\"^4.0.0\"", "\"^4.17.21\"")
+                .replace("\"^18.0.0\"", "\"^20.11.0\"")
+        );
+    }
+
+    #[test]
+    fn test_duplicate_keys_are_all_updated() {
+        let text = r#"{"dependencies":{"a":"^1.0.0","b":"1","a":"^1.0.0"}}"#;
+        let (output, count) = apply_edits(text, &[edit("/dependencies/a", "^2.0.0")]).unwrap();
+        assert_eq!(count, 2);
+        assert_eq!(
+            output,
+            r#"{"dependencies":{"a":"^2.0.0","b":"1","a":"^2.0.0"}}"#
+        );
+    }
+
+    #[test]
+    fn test_non_string_targets_are_skipped() {
+        let text = r#"{"dependencies":{"a":{"nested":"^1.0.0"}}}"#;
+        let (output, count) = apply_edits(text, &[edit("/dependencies/a", "^2.0.0")]).unwrap();
+        assert_eq!(count, 0);
+        assert_eq!(output, text);
+    }
+
+    #[test]
+    fn test_decodes_escapes_in_keys() {
+        let root = parse(r#"{"a\/b": {"😀": "x"}, "list": ["y"]}"#).unwrap();
+        let string = |pointer: &str| match root.pointer_all(pointer)[..] {
+            [Node::String { ref value, .. }] => value.clone(),
+            _ => panic!("no string at {}", pointer),
+        };
+        assert_eq!(string("/a~1b/\u{1f600}"), "x");
+        assert_eq!(string("/list/0"), "y");
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(parse("{\"a\": }").is_err());
+        assert!(parse("{\"a\": 1,}").is_err());
+        assert!(parse("{\"a\": \"b\"} x").is_err());
+        assert!(parse("[\"unterminated]").is_err());
+    }
+
+    #[test]
+    fn test_apply_updates_writes_file() {
+        let path =
+            std::env::temp_dir().join(format!("turbo-ncu-manifest-{}.json", std::process::id()));
+        let path = path.to_string_lossy().to_string();
+        let text = "{\n    \"devDependencies\": {\n        \"vitest\": \"~1.0.0\"\n    },\n    \"overrides\": { \"foo\": { \"bar\": \"1.0.0\" } }\n}\n";
+        std::fs::write(&path, text).unwrap();
+
+        let update = UpdateResult {
+            dep_type: "dev".to_string(),
+            ..UpdateResult::for_test("vitest", "~1.0.0", "~1.6.0")
+        };
+        let pinned = UpdateResult {
+            name: "foo>bar".to_string(),
+            current: "1.0.0".to_string(),
+            new_range: "1.2.0".to_string(),
+            dep_type: "override".to_string(),
+            pointer: Some("/overrides/foo/bar".to_string()),
+            ..update.clone()
+        };
+        assert_eq!(apply_updates(&path, &[update, pinned]).unwrap(), 2);
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            text.replace("~1.0.0", "~1.6.0")
+                .replace("1.0.0\"", "1.2.0\"")
+        );
+
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -1,19 +1,287 @@
+mod advisories;
 mod cache;
+mod cancel;
+mod catalog;
+mod consistency;
+mod doctor;
+mod fixtures;
+mod global;
+mod grouping;
+mod json_edit;
+mod license;
+mod lockfile;
+mod manifest;
+#[cfg(test)]
+mod mock_registry;
+mod node_modules;
+mod plan;
+mod progress;
 mod registry;
 mod resolver;
 mod semver_utils;
+mod throttle;
+mod transitive;
 mod types;
+mod workspace;
 
+use std::sync::Arc;
+
+use cancel::CancelToken;
+use napi::threadsafe_function::{ErrorStrategy, ThreadsafeFunction, ThreadsafeFunctionCallMode};
+use napi::{Env, JsFunction, JsObject, Ref};
 use napi_derive::napi;
-use types::{CheckOptions, CheckResult, PackageInfo};
+use progress::ProgressCallback;
+use resolver::{CheckHooks, UpdateCallback};
+use types::{
+    AppliedPlan, ApplyPlanOptions, CatalogEntry, CheckOptions, DoctorOptions, DoctorStep,
+    GlobalPackage, InstalledIssue, Manifest, PackageInfo, PlanInput, PlanOptions, ProgressEvent,
+    UpdateResult, WorkspaceGraph,
+};
 
-#[napi]
-pub async fn check_updates(
+/// Check packages for updates. Aborting `signal` stops outstanding requests and
+/// resolves with the partial result (`cancelled: true`)
+#[napi(ts_return_type = "Promise<CheckResult>")]
+pub fn check_updates(
+    env: Env,
+    packages: Vec<PackageInfo>,
+    options: CheckOptions,
+    #[napi(ts_arg_type = "(event: ProgressEvent) => void")] on_progress: Option<
+        ThreadsafeFunction<ProgressEvent, ErrorStrategy::Fatal>,
+    >,
+    #[napi(ts_arg_type = "AbortSignal")] signal: Option<JsObject>,
+) -> napi::Result<JsObject> {
+    let (cancel, listener) = cancel_on_abort(&env, signal)?;
+    let hooks = CheckHooks {
+        on_progress: on_progress.map(progress_callback),
+        cancel,
+        ..Default::default()
+    };
+    spawn_check(&env, packages, options, hooks, listener)
+}
+
+/// Like `check_updates`, but calls `on_update` with each update as soon as its
+/// package resolves instead of waiting for the whole batch. With `group`,
+/// updates that may join a group follow once the groups are settled
+#[napi(ts_return_type = "Promise<CheckResult>")]
+pub fn stream_updates(
+    env: Env,
+    packages: Vec<PackageInfo>,
+    options: CheckOptions,
+    #[napi(ts_arg_type = "(update: UpdateResult) => void")] on_update: ThreadsafeFunction<
+        UpdateResult,
+        ErrorStrategy::Fatal,
+    >,
+    #[napi(ts_arg_type = "(event: ProgressEvent) => void")] on_progress: Option<
+        ThreadsafeFunction<ProgressEvent, ErrorStrategy::Fatal>,
+    >,
+    #[napi(ts_arg_type = "AbortSignal")] signal: Option<JsObject>,
+) -> napi::Result<JsObject> {
+    let on_update: UpdateCallback = Arc::new(move |update| {
+        on_update.call(update, ThreadsafeFunctionCallMode::NonBlocking);
+    });
+    let (cancel, listener) = cancel_on_abort(&env, signal)?;
+    let hooks = CheckHooks {
+        on_progress: on_progress.map(progress_callback),
+        on_update: Some(on_update),
+        cancel,
+    };
+    spawn_check(&env, packages, options, hooks, listener)
+}
+
+/// Run a check on the tokio runtime. The abort listener is removed once the
+/// check settles, so a signal reused across checks does not collect them
+fn spawn_check(
+    env: &Env,
     packages: Vec<PackageInfo>,
     options: CheckOptions,
-) -> napi::Result<CheckResult> {
-    let result = resolver::resolve_updates(packages, &options).await;
-    Ok(result)
+    hooks: CheckHooks,
+    listener: Option<AbortListener>,
+) -> napi::Result<JsObject> {
+    env.execute_tokio_future(
+        async move { Ok(resolver::resolve_updates(packages, &options, hooks).await) },
+        move |env, result| {
+            if let Some(listener) = listener {
+                listener.remove(*env)?;
+            }
+            result.map_err(napi::Error::from_reason)
+        },
+    )
+}
+
+fn progress_callback(
+    tsfn: ThreadsafeFunction<ProgressEvent, ErrorStrategy::Fatal>,
+) -> ProgressCallback {
+    Arc::new(move |event| {
+        tsfn.call(event, ThreadsafeFunctionCallMode::NonBlocking);
+    })
+}
+
+/// The `abort` listener added by `cancel_on_abort`
+struct AbortListener {
+    signal: Ref<()>,
+    listener: Ref<()>,
+}
+
+impl AbortListener {
+    fn remove(mut self, env: Env) -> napi::Result<()> {
+        let signal: JsObject = env.get_reference_value(&self.signal)?;
+        let listener: JsFunction = env.get_reference_value(&self.listener)?;
+        self.signal.unref(env)?;
+        self.listener.unref(env)?;
+        let remove_event_listener: JsFunction = signal.get_named_property("removeEventListener")?;
+        remove_event_listener.call(
+            Some(&signal),
+            &[
+                env.create_string("abort")?.into_unknown(),
+                listener.into_unknown(),
+            ],
+        )?;
+        Ok(())
+    }
+}
+
+/// Create a token that is cancelled when the given `AbortSignal` fires, with
+/// the listener to remove once the check is done
+fn cancel_on_abort(
+    env: &Env,
+    signal: Option<JsObject>,
+) -> napi::Result<(CancelToken, Option<AbortListener>)> {
+    let token = CancelToken::new();
+    let Some(signal) = signal else {
+        return Ok((token, None));
+    };
+
+    if signal.get_named_property::<bool>("aborted")? {
+        token.cancel();
+        return Ok((token, None));
+    }
+
+    let listener_token = token.clone();
+    let listener = env.create_function_from_closure("onabort", move |_ctx| {
+        listener_token.cancel();
+        Ok(())
+    })?;
+    let added = AbortListener {
+        signal: env.create_reference(&signal)?,
+        listener: env.create_reference(&listener)?,
+    };
+    let add_event_listener: JsFunction = signal.get_named_property("addEventListener")?;
+    add_event_listener.call(
+        Some(&signal),
+        &[
+            env.create_string("abort")?.into_unknown(),
+            listener.into_unknown(),
+        ],
+    )?;
+    Ok((token, Some(added)))
+}
+
+/// Write each update's `new_range` into the package.json at `path`, changing
+/// only the affected version strings so formatting survives byte for byte.
+/// Returns how many strings were replaced
+#[napi]
+pub fn apply_updates(path: String, updates: Vec<UpdateResult>) -> napi::Result<u32> {
+    json_edit::apply_updates(&path, &updates).map_err(napi::Error::from_reason)
+}
+
+/// Apply `updates` to the package.json at `path`, then install and run the
+/// tests, reverting the upgrades they fail with
+#[napi(ts_return_type = "Promise<DoctorReport>")]
+pub fn doctor(
+    env: Env,
+    path: String,
+    updates: Vec<UpdateResult>,
+    options: Option<DoctorOptions>,
+    #[napi(ts_arg_type = "(step: DoctorStep) => void")] on_step: Option<
+        ThreadsafeFunction<DoctorStep, ErrorStrategy::Fatal>,
+    >,
+) -> napi::Result<JsObject> {
+    let on_step: Option<doctor::DoctorCallback> = on_step.map(|tsfn| {
+        Arc::new(move |step| {
+            tsfn.call(step, ThreadsafeFunctionCallMode::NonBlocking);
+        }) as doctor::DoctorCallback
+    });
+    env.spawn_future(async move {
+        doctor::run(&path, updates, &options.unwrap_or_default(), on_step)
+            .await
+            .map_err(napi::Error::from_reason)
+    })
+}
+
+/// Read the package.json at `path` and classify every dependency specifier
+#[napi]
+pub fn read_manifest(path: String) -> napi::Result<Manifest> {
+    manifest::read_manifest(&path).map_err(napi::Error::from_reason)
+}
+
+/// Like `read_manifest`, for package.json text that is already in memory
+#[napi]
+pub fn parse_manifest(text: String) -> napi::Result<Manifest> {
+    manifest::parse_manifest(&text).map_err(napi::Error::from_reason)
+}
+
+/// Read the catalogs of the pnpm-workspace.yaml at `path`, listing for each
+/// entry which of the `manifests` (package.json paths) reference it
+#[napi]
+pub fn read_catalogs(
+    path: String,
+    manifests: Option<Vec<String>>,
+) -> napi::Result<Vec<CatalogEntry>> {
+    catalog::read_catalogs(&path, &manifests.unwrap_or_default()).map_err(napi::Error::from_reason)
+}
+
+/// Write catalog updates back into the pnpm-workspace.yaml at `path`, keeping
+/// comments and quoting. Updates are matched by `pointer`. Returns how many
+/// specifiers were replaced
+#[napi]
+pub fn apply_catalog_updates(path: String, updates: Vec<UpdateResult>) -> napi::Result<u32> {
+    catalog::apply_catalog_updates(&path, &updates).map_err(napi::Error::from_reason)
+}
+
+/// Discover the workspaces of the monorepo rooted at `root`, with the
+/// dependencies between them
+#[napi]
+pub fn discover_workspaces(root: String) -> napi::Result<WorkspaceGraph> {
+    workspace::discover(&root).map_err(napi::Error::from_reason)
+}
+
+/// Compare the node_modules under `root` with the ranges `packages` declare,
+/// reporting missing, mismatched and extraneous installs
+#[napi]
+pub fn check_installed(root: String, packages: Vec<PackageInfo>) -> Vec<InstalledIssue> {
+    node_modules::check(std::path::Path::new(&root), &packages)
+}
+
+/// Export the updates and groups of a check as a versioned update plan (JSON)
+/// with one batch per group or package, for tools that open pull requests
+#[napi]
+pub fn export_plan(input: PlanInput, options: Option<PlanOptions>) -> String {
+    let groups = input.groups.unwrap_or_default();
+    plan::to_json(&plan::export(
+        &input.updates,
+        &groups,
+        &options.unwrap_or_default(),
+    ))
+}
+
+/// Apply an update plan written by `exportPlan`, skipping edits whose file no
+/// longer holds the value the plan expects
+#[napi]
+pub fn apply_plan(plan: String, options: Option<ApplyPlanOptions>) -> napi::Result<AppliedPlan> {
+    let options = options.unwrap_or_default();
+    let plan = plan::from_json(&plan).map_err(napi::Error::from_reason)?;
+    let root = std::path::PathBuf::from(options.root.as_deref().unwrap_or("."));
+    plan::apply_plan(&plan, &root, options.batches.as_deref()).map_err(napi::Error::from_reason)
+}
+
+/// List globally installed packages of the given package managers (default:
+/// npm, pnpm, yarn and bun), reading their global folders from disk
+#[napi]
+pub fn global_packages(managers: Option<Vec<String>>) -> napi::Result<Vec<GlobalPackage>> {
+    let managers =
+        managers.unwrap_or_else(|| global::MANAGERS.iter().map(|m| m.to_string()).collect());
+    global::global_packages(&global::Environment::from_process(), &managers)
+        .map_err(napi::Error::from_reason)
 }
 
 #[napi]
@@ -0,0 +1,369 @@
+use std::fmt;
+
+/// A parsed SPDX license expression
+#[derive(Debug, Clone, PartialEq)]
+pub enum LicenseExpr {
+    /// A license identifier (including any `+` suffix), optionally with a
+    /// `WITH` exception
+    License {
+        id: String,
+        exception: Option<String>,
+    },
+    And(Vec<LicenseExpr>),
+    Or(Vec<LicenseExpr>),
+}
+
+impl LicenseExpr {
+    /// Parse an expression such as `(MIT OR Apache-2.0) AND BSD-3-Clause`.
+    /// Operators are accepted in any case since registry data is not always
+    /// strict about it
+    pub fn parse(expression: &str) -> Result<Self, String> {
+        let tokens = tokenize(expression);
+        let mut parser = Parser { tokens, pos: 0 };
+        let expr = parser.or()?;
+        match parser.peek() {
+            None => Ok(expr),
+            Some(token) => Err(format!(
+                "Unexpected \"{}\" in license expression \"{}\"",
+                token, expression
+            )),
+        }
+    }
+
+    /// Form that compares equal for equivalent expressions: identifiers are
+    /// lower-cased, nested operators flattened and operands sorted
+    pub fn canonical(&self) -> String {
+        match self {
+            LicenseExpr::License { id, exception } => match exception {
+                Some(exception) => format!(
+                    "{} with {}",
+                    id.to_ascii_lowercase(),
+                    exception.to_ascii_lowercase()
+                ),
+                None => id.to_ascii_lowercase(),
+            },
+            LicenseExpr::And(operands) => join_canonical(operands, " and "),
+            LicenseExpr::Or(operands) => join_canonical(operands, " or "),
+        }
+    }
+
+    fn licenses(&self) -> Vec<(&str, Option<&str>)> {
+        match self {
+            LicenseExpr::License { id, exception } => vec![(id, exception.as_deref())],
+            LicenseExpr::And(operands) | LicenseExpr::Or(operands) => {
+                operands.iter().flat_map(|o| o.licenses()).collect()
+            }
+        }
+    }
+}
+
+impl fmt::Display for LicenseExpr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let write_operands = |f: &mut fmt::Formatter<'_>, operands: &[LicenseExpr], op: &str| {
+            for (i, operand) in operands.iter().enumerate() {
+                if i > 0 {
+                    write!(f, " {} ", op)?;
+                }
+                // AND binds tighter than OR, so only OR inside AND needs parens
+                if op == "AND" && matches!(operand, LicenseExpr::Or(_)) {
+                    write!(f, "({})", operand)?;
+                } else {
+                    write!(f, "{}", operand)?;
+                }
+            }
+            Ok(())
+        };
+        match self {
+            LicenseExpr::License { id, exception } => match exception {
+                Some(exception) => write!(f, "{} WITH {}", id, exception),
+                None => write!(f, "{}", id),
+            },
+            LicenseExpr::And(operands) => write_operands(f, operands, "AND"),
+            LicenseExpr::Or(operands) => write_operands(f, operands, "OR"),
+        }
+    }
+}
+
+fn join_canonical(operands: &[LicenseExpr], separator: &str) -> String {
+    let mut parts: Vec<String> = operands
+        .iter()
+        .map(|o| match o {
+            LicenseExpr::License { .. } => o.canonical(),
+            _ => format!("({})", o.canonical()),
+        })
+        .collect();
+    parts.sort();
+    parts.dedup();
+    parts.join(separator)
+}
+
+fn tokenize(expression: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut word = String::new();
+    for c in expression.chars() {
+        match c {
+            '(' | ')' => {
+                if !word.is_empty() {
+                    tokens.push(std::mem::take(&mut word));
+                }
+                tokens.push(c.to_string());
+            }
+            c if c.is_whitespace() => {
+                if !word.is_empty() {
+                    tokens.push(std::mem::take(&mut word));
+                }
+            }
+            c => word.push(c),
+        }
+    }
+    if !word.is_empty() {
+        tokens.push(word);
+    }
+    tokens
+}
+
+struct Parser {
+    tokens: Vec<String>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&str> {
+        self.tokens.get(self.pos).map(|t| t.as_str())
+    }
+
+    fn next(&mut self) -> Option<String> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn peek_keyword(&self, keyword: &str) -> bool {
+        self.peek().is_some_and(|t| t.eq_ignore_ascii_case(keyword))
+    }
+
+    fn or(&mut self) -> Result<LicenseExpr, String> {
+        let mut operands = vec![self.and()?];
+        while self.peek_keyword("OR") {
+            self.pos += 1;
+            operands.push(self.and()?);
+        }
+        let operands = operands
+            .into_iter()
+            .flat_map(|o| match o {
+                LicenseExpr::Or(inner) => inner,
+                other => vec![other],
+            })
+            .collect();
+        Ok(collapse(operands, LicenseExpr::Or))
+    }
+
+    fn and(&mut self) -> Result<LicenseExpr, String> {
+        let mut operands = vec![self.primary()?];
+        while self.peek_keyword("AND") {
+            self.pos += 1;
+            operands.push(self.primary()?);
+        }
+        let operands = operands
+            .into_iter()
+            .flat_map(|o| match o {
+                LicenseExpr::And(inner) => inner,
+                other => vec![other],
+            })
+            .collect();
+        Ok(collapse(operands, LicenseExpr::And))
+    }
+
+    fn primary(&mut self) -> Result<LicenseExpr, String> {
+        match self.next() {
+            Some(token) if token == "(" => {
+                let expr = self.or()?;
+                match self.next() {
+                    Some(token) if token == ")" => Ok(expr),
+                    _ => Err("Unbalanced parentheses in license expression".to_string()),
+                }
+            }
+            Some(token) if is_operand(&token) => {
+                let exception = if self.peek_keyword("WITH") {
+                    self.pos += 1;
+                    match self.next() {
+                        Some(exception) if is_operand(&exception) => Some(exception),
+                        _ => return Err("Missing exception after WITH".to_string()),
+                    }
+                } else {
+                    None
+                };
+                Ok(LicenseExpr::License {
+                    id: token,
+                    exception,
+                })
+            }
+            Some(token) => Err(format!("Expected a license, found \"{}\"", token)),
+            None => Err("Unexpected end of license expression".to_string()),
+        }
+    }
+}
+
+fn is_operand(token: &str) -> bool {
+    token != "("
+        && token != ")"
+        && !["AND", "OR", "WITH"].contains(&token.to_ascii_uppercase().as_str())
+}
+
+fn collapse(
+    mut operands: Vec<LicenseExpr>,
+    op: fn(Vec<LicenseExpr>) -> LicenseExpr,
+) -> LicenseExpr {
+    if operands.len() == 1 {
+        operands.remove(0)
+    } else {
+        op(operands)
+    }
+}
+
+/// Whether two license strings describe the same terms. Strings that are not
+/// valid SPDX (e.g. `SEE LICENSE IN LICENSE.md`) are compared verbatim
+pub fn same_license(a: Option<&str>, b: Option<&str>) -> bool {
+    let key = |license: Option<&str>| {
+        license.map(|l| {
+            LicenseExpr::parse(l)
+                .map(|e| e.canonical())
+                .unwrap_or_else(|_| l.trim().to_string())
+        })
+    };
+    key(a) == key(b)
+}
+
+/// Allow-list of licenses an upgrade may move to
+pub struct LicensePolicy {
+    /// Lower-cased `(id, exception)` pairs
+    allowed: Vec<(String, Option<String>)>,
+    /// Entries that are not valid SPDX, matched verbatim
+    raw: Vec<String>,
+}
+
+impl LicensePolicy {
+    /// Entries may be identifiers or whole expressions; every license an
+    /// entry mentions is allowed
+    pub fn new(entries: &[String]) -> Self {
+        let mut allowed = Vec::new();
+        let mut raw = Vec::new();
+        for entry in entries {
+            match LicenseExpr::parse(entry) {
+                Ok(expr) => allowed.extend(expr.licenses().into_iter().map(|(id, exception)| {
+                    (
+                        id.to_ascii_lowercase(),
+                        exception.map(|e| e.to_ascii_lowercase()),
+                    )
+                })),
+                Err(_) => raw.push(entry.trim().to_string()),
+            }
+        }
+        Self { allowed, raw }
+    }
+
+    /// Whether `license` can be used under the policy: any branch of an OR
+    /// and every part of an AND must be allowed. Unknown licenses are not
+    pub fn allows(&self, license: Option<&str>) -> bool {
+        let Some(license) = license else {
+            return false;
+        };
+        match LicenseExpr::parse(license) {
+            Ok(expr) => self.allows_expr(&expr),
+            Err(_) => self.raw.iter().any(|r| r == license.trim()),
+        }
+    }
+
+    fn allows_expr(&self, expr: &LicenseExpr) -> bool {
+        match expr {
+            LicenseExpr::License { id, exception } => {
+                let id = id.to_ascii_lowercase();
+                let exception = exception.as_ref().map(|e| e.to_ascii_lowercase());
+                // An exception only grants extra permissions, so allowing the
+                // bare license also allows it with any exception
+                self.allowed
+                    .iter()
+                    .any(|(a, e)| *a == id && (e.is_none() || *e == exception))
+            }
+            LicenseExpr::And(operands) => operands.iter().all(|o| self.allows_expr(o)),
+            LicenseExpr::Or(operands) => operands.iter().any(|o| self.allows_expr(o)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(entries: &[&str]) -> LicensePolicy {
+        LicensePolicy::new(&entries.iter().map(|e| e.to_string()).collect::<Vec<_>>())
+    }
+
+    #[test]
+    fn test_parse_precedence() {
+        let expr = LicenseExpr::parse("MIT OR Apache-2.0 AND BSD-3-Clause").unwrap();
+        assert_eq!(expr.to_string(), "MIT OR Apache-2.0 AND BSD-3-Clause");
+        assert!(matches!(&expr, LicenseExpr::Or(operands) if operands.len() == 2));
+
+        let grouped = LicenseExpr::parse("(MIT OR Apache-2.0) AND BSD-3-Clause").unwrap();
+        assert_eq!(grouped.to_string(), "(MIT OR Apache-2.0) AND BSD-3-Clause");
+
+        let exception = LicenseExpr::parse("GPL-2.0-only WITH Classpath-exception-2.0").unwrap();
+        assert_eq!(
+            exception,
+            LicenseExpr::License {
+                id: "GPL-2.0-only".to_string(),
+                exception: Some("Classpath-exception-2.0".to_string()),
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(LicenseExpr::parse("").is_err());
+        assert!(LicenseExpr::parse("(MIT OR").is_err());
+        assert!(LicenseExpr::parse("MIT AND").is_err());
+        assert!(LicenseExpr::parse("SEE LICENSE IN LICENSE.md").is_err());
+    }
+
+    #[test]
+    fn test_same_license() {
+        assert!(same_license(Some("MIT"), Some("mit")));
+        assert!(same_license(
+            Some("(MIT OR ISC) OR Apache-2.0"),
+            Some("MIT OR (Apache-2.0 OR ISC)")
+        ));
+        assert!(same_license(
+            Some("(MIT OR Apache-2.0)"),
+            Some("Apache-2.0 or MIT")
+        ));
+        assert!(!same_license(Some("MIT"), Some("BUSL-1.1")));
+        assert!(!same_license(Some("MIT"), None));
+        assert!(same_license(None, None));
+        assert!(same_license(
+            Some("SEE LICENSE IN LICENSE"),
+            Some("SEE LICENSE IN LICENSE")
+        ));
+    }
+
+    #[test]
+    fn test_policy_allows() {
+        let policy = policy(&["MIT", "Apache-2.0", "GPL-2.0-only", "SEE LICENSE IN EULA"]);
+        assert!(policy.allows(Some("MIT")));
+        assert!(policy.allows(Some("mit")));
+        assert!(policy.allows(Some("MIT OR BUSL-1.1")));
+        assert!(!policy.allows(Some("MIT AND BUSL-1.1")));
+        assert!(!policy.allows(Some("BUSL-1.1")));
+        assert!(policy.allows(Some("GPL-2.0-only WITH Classpath-exception-2.0")));
+        assert!(policy.allows(Some("SEE LICENSE IN EULA")));
+        assert!(!policy.allows(None));
+    }
+
+    #[test]
+    fn test_policy_exception_must_match() {
+        let policy = policy(&["GPL-2.0-only WITH Classpath-exception-2.0"]);
+        assert!(policy.allows(Some("GPL-2.0-only WITH Classpath-exception-2.0")));
+        assert!(!policy.allows(Some("GPL-2.0-only")));
+        assert!(!policy.allows(Some("GPL-2.0-only WITH LLVM-exception")));
+    }
+}
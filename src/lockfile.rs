@@ -0,0 +1,758 @@
+use node_semver::Version;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// One installed package instance
+#[derive(Debug, Clone, PartialEq)]
+pub struct LockedPackage {
+    pub name: String,
+    pub version: String,
+    pub dependencies: Vec<LockedDependency>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LockedDependency {
+    pub name: String,
+    /// Range the dependent declares; pnpm lockfiles only record the resolved
+    /// version, so it is unknown there
+    pub range: Option<String>,
+    /// Index of the instance it resolved to
+    pub resolved: Option<usize>,
+}
+
+/// The installed dependency graph read from a lockfile. npm, pnpm and bun
+/// record what each importer (the root or a workspace directory) depends on;
+/// yarn classic only records a version per `name@range` descriptor, shared by
+/// every workspace
+#[derive(Debug, Default)]
+pub struct Lockfile {
+    packages: Vec<LockedPackage>,
+    /// Importer path ("" for the root) → dependency name → package
+    importers: HashMap<String, HashMap<String, usize>>,
+    /// (name, range as written in the manifest) → package
+    descriptors: HashMap<(String, String), usize>,
+}
+
+impl Lockfile {
+    /// Read a lockfile, picking the format from its file name
+    pub fn read(path: &str) -> Result<Self, String> {
+        let text =
+            std::fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+        let file_name = Path::new(path)
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or_default();
+        let parsed = match file_name {
+            "package-lock.json" | "npm-shrinkwrap.json" => parse_npm(&text),
+            "pnpm-lock.yaml" => parse_pnpm(&text),
+            "yarn.lock" if text.contains("__metadata:") => parse_yarn_berry(&text),
+            "yarn.lock" => Ok(parse_yarn_classic(&text)),
+            "bun.lock" => parse_bun(&text),
+            "bun.lockb" => Err("binary bun.lockb is not supported; use bun.lock".to_string()),
+            _ => Err("unknown lockfile format".to_string()),
+        };
+        parsed.map_err(|e| format!("Failed to parse {}: {}", path, e))
+    }
+
+    pub fn package(&self, id: usize) -> &LockedPackage {
+        &self.packages[id]
+    }
+
+    /// The package `importer` gets for its dependency `name`, where `specifier`
+    /// is the range its manifest declares
+    pub fn resolve(&self, importer: &str, name: &str, specifier: &str) -> Option<usize> {
+        let importer = normalize_importer(importer);
+        self.importers
+            .get(importer)
+            .and_then(|deps| deps.get(name))
+            .or_else(|| {
+                self.descriptors
+                    .get(&(name.to_string(), normalize_range(specifier).to_string()))
+            })
+            .copied()
+    }
+
+    /// Version of `name` installed for `importer`
+    pub fn installed_version(&self, importer: &str, name: &str, specifier: &str) -> Option<&str> {
+        self.resolve(importer, name, specifier)
+            .map(|id| self.packages[id].version.as_str())
+    }
+
+    fn push(&mut self, name: &str, version: &str) -> usize {
+        self.packages.push(LockedPackage {
+            name: name.to_string(),
+            version: version.to_string(),
+            dependencies: Vec::new(),
+        });
+        self.packages.len() - 1
+    }
+}
+
+fn normalize_importer(importer: &str) -> &str {
+    let importer = importer.trim_start_matches("./").trim_end_matches('/');
+    if importer == "." {
+        ""
+    } else {
+        importer
+    }
+}
+
+/// A range as a manifest writes it: yarn berry's `npm:` protocol prefix is
+/// dropped unless it introduces an alias
+fn normalize_range(range: &str) -> &str {
+    match range.strip_prefix("npm:") {
+        Some(rest) if !rest.contains('@') => rest,
+        _ => range,
+    }
+}
+
+const IMPORTER_SECTIONS: [&str; 4] = [
+    "dependencies",
+    "devDependencies",
+    "optionalDependencies",
+    "peerDependencies",
+];
+
+/// Dev dependencies of installed packages are never installed
+const PACKAGE_SECTIONS: [&str; 3] = ["dependencies", "optionalDependencies", "peerDependencies"];
+
+/// `(name, range)` pairs declared by a manifest-like JSON object
+fn declared(manifest: &Value, sections: &[&str]) -> Vec<(String, String)> {
+    sections
+        .iter()
+        .filter_map(|section| manifest.get(section)?.as_object())
+        .flatten()
+        .filter_map(|(name, range)| Some((name.clone(), range.as_str()?.to_string())))
+        .collect()
+}
+
+/// package-lock.json v2/v3: `packages` is keyed by install path, and a
+/// dependency of `dir` is found at `dir/node_modules/<name>` or in the nearest
+/// ancestor's `node_modules`, as Node resolves it. For v1 only the root's
+/// top-level `dependencies` are read
+fn parse_npm(text: &str) -> Result<Lockfile, String> {
+    let root: Value = serde_json::from_str(text).map_err(|e| e.to_string())?;
+    let mut lockfile = Lockfile::default();
+    let Some(packages) = root.get("packages").and_then(Value::as_object) else {
+        let mut deps = HashMap::new();
+        for (name, entry) in root
+            .get("dependencies")
+            .and_then(Value::as_object)
+            .into_iter()
+            .flatten()
+        {
+            if let Some(version) = entry.get("version").and_then(Value::as_str) {
+                deps.insert(name.clone(), lockfile.push(name, version));
+            }
+        }
+        lockfile.importers.insert(String::new(), deps);
+        return Ok(lockfile);
+    };
+
+    let mut ids: HashMap<&str, usize> = HashMap::new();
+    for (path, entry) in packages {
+        let Some(at) = path.rfind("node_modules/") else {
+            continue;
+        };
+        let Some(version) = entry.get("version").and_then(Value::as_str) else {
+            continue;
+        };
+        let name = entry
+            .get("name")
+            .and_then(Value::as_str)
+            .unwrap_or(&path[at + "node_modules/".len()..]);
+        ids.insert(path, lockfile.push(name, version));
+    }
+    let resolve = |from: &str, dep: &str| -> Option<usize> {
+        let mut base = from;
+        loop {
+            let key = if base.is_empty() {
+                format!("node_modules/{}", dep)
+            } else {
+                format!("{}/node_modules/{}", base, dep)
+            };
+            if let Some(id) = ids.get(key.as_str()) {
+                return Some(*id);
+            }
+            if base.is_empty() {
+                return None;
+            }
+            base = base
+                .rfind("node_modules/")
+                .map_or("", |i| base[..i].trim_end_matches('/'));
+        }
+    };
+
+    for (path, entry) in packages {
+        match ids.get(path.as_str()) {
+            Some(&id) => {
+                lockfile.packages[id].dependencies = declared(entry, &PACKAGE_SECTIONS)
+                    .into_iter()
+                    .map(|(name, range)| LockedDependency {
+                        resolved: resolve(path, &name),
+                        range: Some(range),
+                        name,
+                    })
+                    .collect();
+            }
+            None if !path.contains("node_modules") => {
+                let deps = declared(entry, &IMPORTER_SECTIONS)
+                    .into_iter()
+                    .filter_map(|(name, _)| Some((name.clone(), resolve(path, &name)?)))
+                    .collect();
+                lockfile.importers.insert(path.clone(), deps);
+            }
+            None => {}
+        }
+    }
+    Ok(lockfile)
+}
+
+/// pnpm-lock.yaml. Importers (or the top level, for single-project v5
+/// lockfiles) map each dependency to a reference, which may carry peer
+/// suffixes or name an aliased package. Installed instances are keyed
+/// `name@version(peers)` under `snapshots` (v9), `/name@version(peers)` under
+/// `packages` (v6) or `/name/version_peers` (v5)
+fn parse_pnpm(text: &str) -> Result<Lockfile, String> {
+    let root: serde_yaml::Value = serde_yaml::from_str(text).map_err(|e| e.to_string())?;
+    let mut lockfile = Lockfile::default();
+
+    let instances = match root.get("snapshots") {
+        Some(serde_yaml::Value::Mapping(snapshots)) => Some(snapshots),
+        _ => root.get("packages").and_then(serde_yaml::Value::as_mapping),
+    };
+    let mut ids: HashMap<&str, usize> = HashMap::new();
+    for (key, _) in instances.into_iter().flatten() {
+        let Some(key) = key.as_str() else {
+            continue;
+        };
+        if let Some((name, version)) = pnpm_key(key) {
+            ids.insert(key, lockfile.push(name, &version));
+        }
+    }
+    let resolve = |name: &str, reference: &str| -> Option<usize> {
+        [
+            reference.to_string(),
+            format!("{}@{}", name, reference),
+            format!("/{}@{}", name, reference),
+            format!("/{}/{}", name, reference),
+            format!("/{}", reference),
+        ]
+        .iter()
+        .find_map(|key| ids.get(key.as_str()).copied())
+    };
+    // name → reference, from `{ specifier, version }` (v6+) or inline (v5)
+    let references = |node: &serde_yaml::Value, sections: &[&str]| -> Vec<(String, String)> {
+        sections
+            .iter()
+            .filter_map(|section| node.get(section)?.as_mapping())
+            .flatten()
+            .filter_map(|(name, entry)| {
+                let reference = entry.get("version").unwrap_or(entry).as_str()?;
+                Some((name.as_str()?.to_string(), reference.to_string()))
+            })
+            .collect()
+    };
+
+    for (key, entry) in instances.into_iter().flatten() {
+        let Some(&id) = key.as_str().and_then(|key| ids.get(key)) else {
+            continue;
+        };
+        lockfile.packages[id].dependencies = references(entry, &PACKAGE_SECTIONS)
+            .into_iter()
+            .map(|(name, reference)| LockedDependency {
+                resolved: resolve(&name, &reference),
+                range: None,
+                name,
+            })
+            .collect();
+    }
+
+    let importers: Vec<(String, &serde_yaml::Value)> = match root.get("importers") {
+        Some(serde_yaml::Value::Mapping(importers)) => importers
+            .iter()
+            .filter_map(|(k, v)| Some((k.as_str()?.to_string(), v)))
+            .collect(),
+        _ => vec![(String::new(), &root)],
+    };
+    for (importer, project) in importers {
+        let mut deps = HashMap::new();
+        for (name, reference) in references(project, &IMPORTER_SECTIONS[..3]) {
+            let id = match resolve(&name, &reference) {
+                Some(id) => id,
+                // Lockfiles without instances still record the version
+                None => match pnpm_version(&reference) {
+                    Some(version) => lockfile.push(&name, &version),
+                    None => continue,
+                },
+            };
+            deps.insert(name, id);
+        }
+        lockfile
+            .importers
+            .insert(normalize_importer(&importer).to_string(), deps);
+    }
+    Ok(lockfile)
+}
+
+/// Name and version of an installed-instance key
+fn pnpm_key(key: &str) -> Option<(&str, String)> {
+    let key = key.trim_start_matches('/');
+    let key = key.split('(').next()?;
+    let (name, version) = match key[1..].rfind('@') {
+        Some(at) => (&key[..at + 1], &key[at + 2..]),
+        None => key.rsplit_once('/')?,
+    };
+    let version = version.split('_').next()?;
+    version
+        .parse::<Version>()
+        .ok()
+        .map(|v| (name, v.to_string()))
+}
+
+/// Plain version from a pnpm reference such as `1.0.0(react@18.2.0)`,
+/// `1.0.0_react@18.2.0`, `/strip-ansi@6.0.1` or (v5) `/strip-ansi/6.0.1`;
+/// `link:` and `file:` references have none
+fn pnpm_version(reference: &str) -> Option<String> {
+    let reference = reference.split(['(', '_']).next()?;
+    let version = match reference.rfind('@') {
+        Some(at) if at > 0 => &reference[at + 1..],
+        _ => reference.rsplit('/').next()?,
+    };
+    version.parse::<Version>().ok().map(|v| v.to_string())
+}
+
+/// yarn.lock v1: blocks headed by comma-separated descriptors, with an
+/// indented `version "x.y.z"` line and `dependencies:` listing `name "range"`
+fn parse_yarn_classic(text: &str) -> Lockfile {
+    struct Block {
+        descriptors: Vec<(String, String)>,
+        version: Option<String>,
+        dependencies: Vec<(String, String)>,
+    }
+    let mut blocks: Vec<Block> = Vec::new();
+    let mut in_dependencies = false;
+    for line in text.lines() {
+        if line.starts_with('#') || line.trim().is_empty() {
+            continue;
+        }
+        if !line.starts_with(' ') {
+            blocks.push(Block {
+                descriptors: line
+                    .trim_end_matches(':')
+                    .split(", ")
+                    .filter_map(|d| split_descriptor(d.trim_matches('"')))
+                    .collect(),
+                version: None,
+                dependencies: Vec::new(),
+            });
+            in_dependencies = false;
+            continue;
+        }
+        let Some(block) = blocks.last_mut() else {
+            continue;
+        };
+        if let Some(field) = line.strip_prefix("  ").filter(|f| !f.starts_with(' ')) {
+            in_dependencies = matches!(field, "dependencies:" | "optionalDependencies:");
+            if let Some(version) = field.strip_prefix("version ") {
+                block.version = Some(version.trim_matches('"').to_string());
+            }
+        } else if in_dependencies {
+            let entry = line.trim();
+            let split = if let Some(quoted) = entry.strip_prefix('"') {
+                quoted.split_once("\" ")
+            } else {
+                entry.split_once(' ')
+            };
+            if let Some((name, range)) = split {
+                block
+                    .dependencies
+                    .push((name.to_string(), range.trim_matches('"').to_string()));
+            }
+        }
+    }
+
+    let mut lockfile = Lockfile::default();
+    let mut ids = Vec::new();
+    for block in &blocks {
+        let (Some(version), Some((name, _))) = (&block.version, block.descriptors.first()) else {
+            ids.push(None);
+            continue;
+        };
+        let id = lockfile.push(name, version);
+        for descriptor in &block.descriptors {
+            lockfile.descriptors.insert(descriptor.clone(), id);
+        }
+        ids.push(Some(id));
+    }
+    for (block, id) in blocks.into_iter().zip(ids) {
+        let Some(id) = id else {
+            continue;
+        };
+        lockfile.packages[id].dependencies = block
+            .dependencies
+            .into_iter()
+            .map(|(name, range)| LockedDependency {
+                resolved: lockfile
+                    .descriptors
+                    .get(&(name.clone(), range.clone()))
+                    .copied(),
+                range: Some(range),
+                name,
+            })
+            .collect();
+    }
+    lockfile
+}
+
+/// yarn.lock v2+ is YAML keyed by `name@npm:range` descriptors; workspaces
+/// appear as `name@workspace:path` entries
+fn parse_yarn_berry(text: &str) -> Result<Lockfile, String> {
+    let root: serde_yaml::Mapping = serde_yaml::from_str(text).map_err(|e| e.to_string())?;
+    let mut lockfile = Lockfile::default();
+    let mut entries = Vec::new();
+    for (key, entry) in &root {
+        let (Some(key), Some(version)) = (
+            key.as_str(),
+            entry.get("version").and_then(serde_yaml::Value::as_str),
+        ) else {
+            continue;
+        };
+        let descriptors: Vec<(String, String)> =
+            key.split(", ").filter_map(split_descriptor).collect();
+        let Some((name, _)) = descriptors.first() else {
+            continue;
+        };
+        let id = lockfile.push(name, version);
+        for descriptor in descriptors {
+            lockfile.descriptors.insert(descriptor, id);
+        }
+        entries.push((key, id, entry));
+    }
+
+    for (key, id, entry) in entries {
+        let dependencies: Vec<LockedDependency> = ["dependencies", "optionalDependencies"]
+            .iter()
+            .filter_map(|section| entry.get(section)?.as_mapping())
+            .flatten()
+            .filter_map(|(name, range)| {
+                let name = name.as_str()?.to_string();
+                let range = normalize_range(range.as_str()?).to_string();
+                Some(LockedDependency {
+                    resolved: lockfile
+                        .descriptors
+                        .get(&(name.clone(), range.clone()))
+                        .copied(),
+                    range: Some(range),
+                    name,
+                })
+            })
+            .collect();
+        if let Some((_, path)) = key.split_once("@workspace:") {
+            let deps = dependencies
+                .iter()
+                .filter_map(|dep| Some((dep.name.clone(), dep.resolved?)))
+                .collect();
+            lockfile
+                .importers
+                .insert(normalize_importer(path).to_string(), deps);
+        }
+        lockfile.packages[id].dependencies = dependencies;
+    }
+    Ok(lockfile)
+}
+
+/// Split a `name@range` descriptor into name and normalized range
+fn split_descriptor(descriptor: &str) -> Option<(String, String)> {
+    let at = descriptor.get(1..)?.find('@')? + 1;
+    let name = &descriptor[..at];
+    let range = normalize_range(&descriptor[at + 1..]);
+    Some((name.to_string(), range.to_string()))
+}
+
+/// bun.lock: JSON with trailing commas. `workspaces` lists each importer's
+/// dependencies; `packages` maps a dependency to `["name@version", ...]`,
+/// keyed by its name or, for a nested copy, `<parent key>/<name>`
+fn parse_bun(text: &str) -> Result<Lockfile, String> {
+    let root: Value =
+        serde_json::from_str(&strip_trailing_commas(text)).map_err(|e| e.to_string())?;
+    let packages = root.get("packages").and_then(Value::as_object);
+    let mut lockfile = Lockfile::default();
+
+    let mut ids: HashMap<&str, usize> = HashMap::new();
+    for (key, entry) in packages.into_iter().flatten() {
+        let Some(resolution) = entry.get(0).and_then(Value::as_str) else {
+            continue;
+        };
+        let Some(at) = resolution.rfind('@').filter(|at| *at > 0) else {
+            continue;
+        };
+        let version = &resolution[at + 1..];
+        if version.contains(':') {
+            // workspace:, github:, file: and other non-registry sources
+            continue;
+        }
+        ids.insert(key, lockfile.push(&resolution[..at], version));
+    }
+    // Nested copies shadow hoisted ones, as in node_modules
+    let resolve = |from: &str, dep: &str| -> Option<usize> {
+        let mut prefix = from;
+        loop {
+            let key = if prefix.is_empty() {
+                dep.to_string()
+            } else {
+                format!("{}/{}", prefix, dep)
+            };
+            if let Some(id) = ids.get(key.as_str()) {
+                return Some(*id);
+            }
+            if prefix.is_empty() {
+                return None;
+            }
+            prefix = prefix.rsplit_once('/').map_or("", |(parent, _)| parent);
+        }
+    };
+
+    for (key, entry) in packages.into_iter().flatten() {
+        let Some(&id) = ids.get(key.as_str()) else {
+            continue;
+        };
+        let info = entry
+            .as_array()
+            .and_then(|items| items.iter().find(|item| item.is_object()));
+        lockfile.packages[id].dependencies = info
+            .map(|info| declared(info, &PACKAGE_SECTIONS))
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(name, range)| LockedDependency {
+                resolved: resolve(key, &name),
+                range: Some(range),
+                name,
+            })
+            .collect();
+    }
+
+    if let Some(workspaces) = root.get("workspaces").and_then(Value::as_object) {
+        for (path, manifest) in workspaces {
+            let ws_name = manifest.get("name").and_then(Value::as_str).unwrap_or(path);
+            let deps = declared(manifest, &IMPORTER_SECTIONS)
+                .into_iter()
+                .filter_map(|(name, _)| Some((name.clone(), resolve(ws_name, &name)?)))
+                .collect();
+            lockfile.importers.insert(path.clone(), deps);
+        }
+    }
+    Ok(lockfile)
+}
+
+/// Remove commas directly before `}` or `]`, outside strings
+pub fn strip_trailing_commas(text: &str) -> String {
+    let mut output = String::with_capacity(text.len());
+    let mut in_string = false;
+    let mut escaped = false;
+    let mut pending_comma: Option<usize> = None;
+    for c in text.chars() {
+        if in_string {
+            in_string = c != '"' || escaped;
+            escaped = c == '\\' && !escaped;
+            output.push(c);
+            continue;
+        }
+        match c {
+            '"' => {
+                in_string = true;
+                pending_comma = None;
+            }
+            ',' => pending_comma = Some(output.len()),
+            '}' | ']' => {
+                if let Some(at) = pending_comma.take() {
+                    output.remove(at);
+                }
+            }
+            c if c.is_whitespace() => {}
+            _ => pending_comma = None,
+        }
+        output.push(c);
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(dir: &str, name: &str, text: &str) -> String {
+        let dir =
+            std::env::temp_dir().join(format!("turbo-ncu-lock-{}-{}", dir, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(name);
+        std::fs::write(&path, text).unwrap();
+        path.to_string_lossy().to_string()
+    }
+
+    fn cleanup(path: &str) {
+        let _ = std::fs::remove_dir_all(Path::new(path).parent().unwrap());
+    }
+
+    #[test]
+    fn test_package_lock() {
+        let path = write(
+            "npm",
+            "package-lock.json",
+            r#"{
+                "lockfileVersion": 3,
+                "packages": {
+                    "": { "dependencies": { "lodash": "^4.17.0", "strip-ansi-cjs": "npm:strip-ansi@^6.0.1" } },
+                    "packages/a": { "devDependencies": { "lodash": "^3.0.0", "chalk": "^5.0.0" } },
+                    "node_modules/lodash": { "version": "4.17.21" },
+                    "node_modules/strip-ansi-cjs": { "name": "strip-ansi", "version": "6.0.1" },
+                    "node_modules/chalk": { "version": "5.3.0" },
+                    "packages/a/node_modules/lodash": { "version": "3.10.1" }
+                }
+            }"#,
+        );
+        let lockfile = Lockfile::read(&path).unwrap();
+        assert_eq!(
+            lockfile.installed_version(".", "lodash", "^4.17.0"),
+            Some("4.17.21")
+        );
+        assert_eq!(
+            lockfile.installed_version("", "strip-ansi-cjs", "npm:strip-ansi@^6.0.1"),
+            Some("6.0.1")
+        );
+        assert_eq!(
+            lockfile.installed_version("packages/a", "lodash", "^3.0.0"),
+            Some("3.10.1")
+        );
+        assert_eq!(
+            lockfile.installed_version("packages/a", "chalk", "^5.0.0"),
+            Some("5.3.0")
+        );
+        assert_eq!(lockfile.installed_version("", "missing", "^1.0.0"), None);
+        cleanup(&path);
+    }
+
+    #[test]
+    fn test_pnpm_lock() {
+        let path = write(
+            "pnpm",
+            "pnpm-lock.yaml",
+            "lockfileVersion: '9.0'\n\nimporters:\n\n  .:\n    dependencies:\n      react-dom:\n        specifier: ^18.0.0\n        version: 18.3.1(react@18.3.1)\n      strip-ansi-cjs:\n        specifier: npm:strip-ansi@^6.0.1\n        version: strip-ansi@6.0.1\n      local:\n        specifier: link:../local\n        version: link:../local\n\n  packages/a:\n    devDependencies:\n      typescript:\n        specifier: ^5.0.0\n        version: 5.4.5\n",
+        );
+        let lockfile = Lockfile::read(&path).unwrap();
+        assert_eq!(
+            lockfile.installed_version("", "react-dom", "^18.0.0"),
+            Some("18.3.1")
+        );
+        assert_eq!(
+            lockfile.installed_version("", "strip-ansi-cjs", ""),
+            Some("6.0.1")
+        );
+        assert_eq!(lockfile.installed_version("", "local", ""), None);
+        assert_eq!(
+            lockfile.installed_version("./packages/a/", "typescript", ""),
+            Some("5.4.5")
+        );
+        cleanup(&path);
+
+        // v5 single-project lockfiles list versions at the top level
+        let path = write(
+            "pnpm5",
+            "pnpm-lock.yaml",
+            "lockfileVersion: 5.4\n\nspecifiers:\n  react-dom: ^18.0.0\n\ndependencies:\n  react-dom: 18.2.0_react@18.2.0\n",
+        );
+        let lockfile = Lockfile::read(&path).unwrap();
+        assert_eq!(
+            lockfile.installed_version("", "react-dom", "^18.0.0"),
+            Some("18.2.0")
+        );
+        cleanup(&path);
+
+        assert_eq!(pnpm_version("/strip-ansi/6.0.1").as_deref(), Some("6.0.1"));
+    }
+
+    #[test]
+    fn test_yarn_classic_lock() {
+        let path = write(
+            "yarn1",
+            "yarn.lock",
+            "# THIS IS AN AUTOGENERATED FILE.\n# yarn lockfile v1\n\n\n\"@types/node@^20.0.0\", \"@types/node@^20.1.0\":\n  version \"20.11.5\"\n  resolved \"https://registry.yarnpkg.com/@types/node/-/node-20.11.5.tgz\"\n\nlodash@^4.17.0:\n  version \"4.17.21\"\n\n\"strip-ansi-cjs@npm:strip-ansi@^6.0.1\":\n  version \"6.0.1\"\n",
+        );
+        let lockfile = Lockfile::read(&path).unwrap();
+        assert_eq!(
+            lockfile.installed_version("", "@types/node", "^20.1.0"),
+            Some("20.11.5")
+        );
+        assert_eq!(
+            lockfile.installed_version("", "lodash", "^4.17.0"),
+            Some("4.17.21")
+        );
+        assert_eq!(lockfile.installed_version("", "lodash", "^3.0.0"), None);
+        assert_eq!(
+            lockfile.installed_version("", "strip-ansi-cjs", "npm:strip-ansi@^6.0.1"),
+            Some("6.0.1")
+        );
+        cleanup(&path);
+    }
+
+    #[test]
+    fn test_yarn_berry_lock() {
+        let path = write(
+            "berry",
+            "yarn.lock",
+            "__metadata:\n  version: 8\n  cacheKey: 10c0\n\n\"@types/node@npm:^20.0.0\":\n  version: 20.11.5\n  resolution: \"@types/node@npm:20.11.5\"\n\n\"lodash@npm:^4.17.0, lodash@npm:^4.17.21\":\n  version: 4.17.21\n\n\"strip-ansi-cjs@npm:strip-ansi@^6.0.1\":\n  version: 6.0.1\n",
+        );
+        let lockfile = Lockfile::read(&path).unwrap();
+        assert_eq!(
+            lockfile.installed_version("", "@types/node", "^20.0.0"),
+            Some("20.11.5")
+        );
+        assert_eq!(
+            lockfile.installed_version("", "lodash", "^4.17.21"),
+            Some("4.17.21")
+        );
+        assert_eq!(
+            lockfile.installed_version("", "strip-ansi-cjs", "npm:strip-ansi@^6.0.1"),
+            Some("6.0.1")
+        );
+        cleanup(&path);
+    }
+
+    #[test]
+    fn test_bun_lock() {
+        let path = write(
+            "bun",
+            "bun.lock",
+            r#"{
+              "lockfileVersion": 1,
+              "workspaces": {
+                "": { "name": "app", "dependencies": { "lodash": "^4.17.0", }, },
+                "packages/a": { "name": "a", "devDependencies": { "lodash": "^3.0.0" } },
+              },
+              "packages": {
+                "lodash": ["lodash@4.17.21", "", {}, "sha512-x"],
+                "a/lodash": ["lodash@3.10.1", "", {}, "sha512-y"],
+              },
+            }"#,
+        );
+        let lockfile = Lockfile::read(&path).unwrap();
+        assert_eq!(
+            lockfile.installed_version("", "lodash", "^4.17.0"),
+            Some("4.17.21")
+        );
+        assert_eq!(
+            lockfile.installed_version("packages/a", "lodash", "^3.0.0"),
+            Some("3.10.1")
+        );
+        cleanup(&path);
+    }
+
+    #[test]
+    fn test_strip_trailing_commas_keeps_strings() {
+        assert_eq!(
+            strip_trailing_commas(r#"{"a": ["x,]", 1,], }"#),
+            r#"{"a": ["x,]", 1] }"#
+        );
+    }
+}
@@ -0,0 +1,515 @@
+use node_semver::Range;
+
+use crate::json_edit::{self, pointer_token, Node};
+use crate::types::{Manifest, ManifestDependency, PackageInfo};
+
+/// Dependency sections and the dep type each one is reported under
+pub const DEPENDENCY_SECTIONS: [(&str, &str); 4] = [
+    ("dependencies", "prod"),
+    ("devDependencies", "dev"),
+    ("peerDependencies", "peer"),
+    ("optionalDependencies", "optional"),
+];
+
+/// What a dependency specifier points at, following npm-package-arg's rules
+#[derive(Debug, Clone, PartialEq)]
+pub enum Specifier {
+    /// A semver range resolved against the registry
+    Registry {
+        range: String,
+    },
+    /// `npm:<package>@<range>`, installing another package under this name
+    Alias {
+        package: String,
+        range: String,
+    },
+    Git,
+    /// `file:` and relative or absolute paths, plus yarn `patch:` protocols
+    File,
+    /// `link:` and yarn `portal:`
+    Link,
+    Workspace,
+    /// `catalog:` or `catalog:<name>` (pnpm); an empty name is the default
+    Catalog {
+        catalog: String,
+    },
+    /// A dist-tag such as `latest` or `next`
+    Tag,
+    /// A tarball URL
+    Url,
+}
+
+impl Specifier {
+    pub fn parse(spec: &str) -> Self {
+        let spec = spec.trim();
+        let lower = spec.to_ascii_lowercase();
+
+        if let Some(rest) = spec.strip_prefix("npm:") {
+            let (package, range) = split_package_spec(rest);
+            return Specifier::Alias {
+                package: package.to_string(),
+                range: if range.is_empty() { "latest" } else { range }.to_string(),
+            };
+        }
+        if spec.starts_with("workspace:") {
+            return Specifier::Workspace;
+        }
+        if let Some(catalog) = spec.strip_prefix("catalog:") {
+            return Specifier::Catalog {
+                catalog: catalog.trim().to_string(),
+            };
+        }
+        if spec.starts_with("link:") || spec.starts_with("portal:") {
+            return Specifier::Link;
+        }
+        if spec.starts_with("file:")
+            || spec.starts_with("patch:")
+            || spec.starts_with("./")
+            || spec.starts_with("../")
+            || spec.starts_with('/')
+            || spec.starts_with("~/")
+        {
+            return Specifier::File;
+        }
+        let git_prefixes = [
+            "git:",
+            "git+",
+            "git@",
+            "github:",
+            "gitlab:",
+            "bitbucket:",
+            "gist:",
+        ];
+        if git_prefixes.iter().any(|p| lower.starts_with(p)) {
+            return Specifier::Git;
+        }
+        if lower.starts_with("http:") || lower.starts_with("https:") {
+            let path = lower.split(['#', '?']).next().unwrap_or_default();
+            return if path.ends_with(".git") {
+                Specifier::Git
+            } else {
+                Specifier::Url
+            };
+        }
+        // GitHub shorthand: user/repo, optionally with #ref
+        if !spec.starts_with('@') && !spec.contains(':') && !spec.contains(' ') {
+            if let Some((user, repo)) = spec.split('#').next().unwrap_or_default().split_once('/') {
+                if !user.is_empty() && !repo.is_empty() && !repo.contains('/') {
+                    return Specifier::Git;
+                }
+            }
+        }
+        if spec.parse::<Range>().is_ok() {
+            return Specifier::Registry {
+                range: spec.to_string(),
+            };
+        }
+        Specifier::Tag
+    }
+
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Specifier::Registry { .. } => "registry",
+            Specifier::Alias { .. } => "alias",
+            Specifier::Git => "git",
+            Specifier::File => "file",
+            Specifier::Link => "link",
+            Specifier::Workspace => "workspace",
+            Specifier::Catalog { .. } => "catalog",
+            Specifier::Tag => "tag",
+            Specifier::Url => "url",
+        }
+    }
+
+    /// Registry package and semver range to check, when there is one
+    pub fn registry_range<'a>(&'a self, name: &'a str) -> Option<(&'a str, &'a str)> {
+        match self {
+            Specifier::Registry { range } => Some((name, range)),
+            Specifier::Alias { package, range } if range.parse::<Range>().is_ok() => {
+                Some((package, range))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Split `pkg@range` / `@scope/pkg@range` into name and range
+fn split_package_spec(spec: &str) -> (&str, &str) {
+    let search_from = if spec.starts_with('@') { 1 } else { 0 };
+    match spec[search_from..].find('@') {
+        Some(i) => (&spec[..search_from + i], &spec[search_from + i + 1..]),
+        None => (spec, ""),
+    }
+}
+
+/// Package an override selector applies to: the last package of the path,
+/// without its version constraint. Handles npm/pnpm (`foo@1>bar@^2`) and
+/// yarn (`foo/**/@scope/bar`) selectors
+pub fn override_target(selector: &str) -> &str {
+    let mut last = selector.rsplit('>').next().unwrap_or(selector);
+    // Yarn separates path segments with '/', which scoped names also contain
+    loop {
+        let scope = if last.starts_with('@') {
+            last.find('/').map_or(last.len(), |i| i + 1)
+        } else {
+            0
+        };
+        match last[scope..].find('/') {
+            Some(i) => last = &last[scope + i + 1..],
+            None => break,
+        }
+    }
+    split_package_spec(last).0
+}
+
+/// Package to fetch for a dependency named `name`: override selectors name
+/// the package they pin last
+fn package_name<'a>(name: &'a str, dep_type: &str) -> &'a str {
+    if dep_type == "override" {
+        override_target(name)
+    } else {
+        name
+    }
+}
+
+/// Package to fetch and range to resolve for `pkg`, looking through `npm:`
+/// aliases and override selectors
+pub fn registry_lookup(pkg: &PackageInfo) -> PackageInfo {
+    match Specifier::parse(&pkg.version_range) {
+        Specifier::Alias { package, range } => PackageInfo {
+            name: package,
+            version_range: range,
+            ..pkg.clone()
+        },
+        _ => PackageInfo {
+            name: package_name(&pkg.name, &pkg.dep_type).to_string(),
+            ..pkg.clone()
+        },
+    }
+}
+
+pub fn read_manifest(path: &str) -> Result<Manifest, String> {
+    let text =
+        std::fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+    parse_manifest(&text).map_err(|e| format!("Failed to parse {}: {}", path, e))
+}
+
+/// Classify every dependency of a package.json, in source order
+pub fn parse_manifest(text: &str) -> Result<Manifest, String> {
+    let root = json_edit::parse(text)?;
+    let string = |key: &str| root.get(key).and_then(Node::as_str).map(String::from);
+
+    let mut dependencies = Vec::new();
+    for (section, dep_type) in DEPENDENCY_SECTIONS {
+        for (name, value) in members(root.get(section)) {
+            if let Some(specifier) = value.as_str() {
+                let pointer = format!("/{}/{}", section, pointer_token(name));
+                dependencies.push(dependency(name, specifier, dep_type, pointer));
+            }
+        }
+    }
+
+    // npm overrides nest by dependency path, with "." pinning the parent
+    // itself; selectors are reported with `>` between path segments
+    npm_overrides(root.get("overrides"), "", "/overrides", &mut dependencies);
+    for (section, pointer) in [
+        (root.get("resolutions"), "/resolutions".to_string()),
+        (
+            root.get("pnpm").and_then(|pnpm| pnpm.get("overrides")),
+            "/pnpm/overrides".to_string(),
+        ),
+    ] {
+        for (selector, value) in members(section) {
+            if let Some(specifier) = value.as_str() {
+                let pointer = format!("{}/{}", pointer, pointer_token(selector));
+                dependencies.push(dependency(selector, specifier, "override", pointer));
+            }
+        }
+    }
+
+    // Bundled packages are versioned by their regular entry, so only listed
+    for key in ["bundleDependencies", "bundledDependencies"] {
+        if let Some(Node::Array { items }) = root.get(key) {
+            for name in items.iter().filter_map(Node::as_str) {
+                let specifier = dependencies
+                    .iter()
+                    .find(|d| d.name == name)
+                    .map(|d| d.specifier.clone())
+                    .unwrap_or_default();
+                let pointer = format!("/{}", key);
+                dependencies.push(ManifestDependency {
+                    checkable: false,
+                    ..dependency(name, &specifier, "bundle", pointer)
+                });
+            }
+        }
+    }
+
+    // devEngines entries are one object or a list of them; runtimes such as
+    // node are not registry packages, and neither kind can be rewritten yet
+    if let Some(dev_engines) = root.get("devEngines") {
+        for key in ["runtime", "packageManager"] {
+            let entries: Vec<&Node> = match dev_engines.get(key) {
+                Some(Node::Array { items }) => items.iter().collect(),
+                Some(entry) => vec![entry],
+                None => Vec::new(),
+            };
+            for entry in entries {
+                let Some(name) = entry.get("name").and_then(Node::as_str) else {
+                    continue;
+                };
+                let specifier = entry.get("version").and_then(Node::as_str).unwrap_or("*");
+                let pointer = format!("/devEngines/{}", key);
+                dependencies.push(ManifestDependency {
+                    checkable: false,
+                    ..dependency(name, specifier, "devEngines", pointer)
+                });
+            }
+        }
+    }
+
+    Ok(Manifest {
+        name: string("name"),
+        version: string("version"),
+        dependencies,
+    })
+}
+
+/// Members of an object in source order; of duplicate keys only the last is
+/// kept, matching `JSON.parse`
+fn members(node: Option<&Node>) -> Vec<(&str, &Node)> {
+    match node {
+        Some(Node::Object { members }) => members
+            .iter()
+            .enumerate()
+            .filter(|(i, m)| !members[i + 1..].iter().any(|later| later.key == m.key))
+            .map(|(_, m)| (m.key.as_str(), &m.value))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn npm_overrides(
+    node: Option<&Node>,
+    parent: &str,
+    pointer: &str,
+    out: &mut Vec<ManifestDependency>,
+) {
+    for (key, value) in members(node) {
+        let pointer = format!("{}/{}", pointer, pointer_token(key));
+        let selector = match (key, parent) {
+            (".", "") => continue,
+            (".", parent) => parent.to_string(),
+            (key, "") => key.to_string(),
+            (key, parent) => format!("{}>{}", parent, key),
+        };
+        match value {
+            Node::String { value, .. } => {
+                out.push(dependency(&selector, value, "override", pointer))
+            }
+            Node::Object { .. } => npm_overrides(Some(value), &selector, &pointer, out),
+            _ => {}
+        }
+    }
+}
+
+fn dependency(name: &str, specifier: &str, dep_type: &str, pointer: String) -> ManifestDependency {
+    let parsed = Specifier::parse(specifier);
+    let lookup = parsed.registry_range(package_name(name, dep_type));
+    ManifestDependency {
+        name: name.to_string(),
+        specifier: specifier.to_string(),
+        dep_type: dep_type.to_string(),
+        pointer,
+        kind: parsed.kind().to_string(),
+        package_name: lookup.map(|(package, _)| package.to_string()),
+        range: lookup.map(|(_, range)| range.to_string()),
+        checkable: lookup.is_some(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kind(spec: &str) -> &'static str {
+        Specifier::parse(spec).kind()
+    }
+
+    #[test]
+    fn test_classify_specifiers() {
+        assert_eq!(kind("^1.2.3"), "registry");
+        assert_eq!(kind(">=1.0.0 <2"), "registry");
+        assert_eq!(kind("*"), "registry");
+        assert_eq!(kind("1.x || 2.x"), "registry");
+        assert_eq!(kind("npm:@scope/pkg@^1"), "alias");
+        assert_eq!(kind("git+https://github.com/u/r.git"), "git");
+        assert_eq!(kind("github:u/r"), "git");
+        assert_eq!(kind("u/r#v1.0.0"), "git");
+        assert_eq!(kind("https://example.com/r.git#main"), "git");
+        assert_eq!(kind("https://example.com/pkg.tgz"), "url");
+        assert_eq!(kind("file:../local"), "file");
+        assert_eq!(kind("./vendor/pkg"), "file");
+        assert_eq!(kind("link:../other"), "link");
+        assert_eq!(kind("portal:../other"), "link");
+        assert_eq!(kind("workspace:^"), "workspace");
+        assert_eq!(kind("catalog:"), "catalog");
+        assert_eq!(kind("latest"), "tag");
+        assert_eq!(kind("next"), "tag");
+    }
+
+    #[test]
+    fn test_alias_registry_range() {
+        let scoped = Specifier::parse("npm:@scope/pkg@^1.0.0");
+        assert_eq!(
+            scoped.registry_range("alias"),
+            Some(("@scope/pkg", "^1.0.0"))
+        );
+        assert_eq!(
+            Specifier::parse("npm:string-width@4"),
+            Specifier::Alias {
+                package: "string-width".to_string(),
+                range: "4".to_string(),
+            }
+        );
+        // Aliases of a tag cannot be checked
+        assert_eq!(Specifier::parse("npm:pkg").registry_range("alias"), None);
+        assert_eq!(
+            Specifier::parse("catalog:react18"),
+            Specifier::Catalog {
+                catalog: "react18".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_manifest() {
+        let manifest = parse_manifest(
+            r#"{
+                "name": "app",
+                "version": "1.0.0",
+                "dependencies": {
+                    "@types/node": "^20.0.0",
+                    "strip-ansi-cjs": "npm:strip-ansi@^6.0.1",
+                    "local": "file:../local",
+                    "internal": "workspace:*"
+                },
+                "devDependencies": { "vitest": "next" },
+                "bundleDependencies": ["local"],
+                "devEngines": {
+                    "runtime": { "name": "node", "version": ">=20" },
+                    "packageManager": [{ "name": "pnpm", "version": "^9.0.0" }]
+                }
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(manifest.name.as_deref(), Some("app"));
+        let summary: Vec<(&str, &str, &str, bool)> = manifest
+            .dependencies
+            .iter()
+            .map(|d| {
+                (
+                    d.name.as_str(),
+                    d.dep_type.as_str(),
+                    d.kind.as_str(),
+                    d.checkable,
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("@types/node", "prod", "registry", true),
+                ("strip-ansi-cjs", "prod", "alias", true),
+                ("local", "prod", "file", false),
+                ("internal", "prod", "workspace", false),
+                ("vitest", "dev", "tag", false),
+                ("local", "bundle", "file", false),
+                ("node", "devEngines", "registry", false),
+                ("pnpm", "devEngines", "registry", false),
+            ]
+        );
+        let alias = &manifest.dependencies[1];
+        assert_eq!(alias.package_name.as_deref(), Some("strip-ansi"));
+        assert_eq!(alias.range.as_deref(), Some("^6.0.1"));
+    }
+
+    #[test]
+    fn test_override_target() {
+        assert_eq!(override_target("foo"), "foo");
+        assert_eq!(override_target("foo>bar"), "bar");
+        assert_eq!(override_target("pkg@^2"), "pkg");
+        assert_eq!(override_target("foo@1>@scope/bar@^2"), "@scope/bar");
+        assert_eq!(override_target("**/bar"), "bar");
+        assert_eq!(override_target("@scope/foo@1/**/@scope/bar"), "@scope/bar");
+        assert_eq!(override_target("@scope/foo"), "@scope/foo");
+    }
+
+    #[test]
+    fn test_parse_overrides() {
+        let manifest = parse_manifest(
+            r#"{
+                "dependencies": { "foo": "^1.0.0" },
+                "overrides": {
+                    "bar": "2.0.0",
+                    "foo@1": { ".": "1.2.0", "@scope/baz": "^3.0.0" },
+                    "qux": "$foo"
+                },
+                "resolutions": { "**/left-pad": "1.1.0" },
+                "pnpm": { "overrides": { "a>b": "^4.0.0", "c@^2": "-" } }
+            }"#,
+        )
+        .unwrap();
+
+        let overrides: Vec<(&str, &str, Option<&str>, &str)> = manifest
+            .dependencies
+            .iter()
+            .filter(|d| d.dep_type == "override")
+            .map(|d| {
+                (
+                    d.name.as_str(),
+                    d.pointer.as_str(),
+                    d.package_name.as_deref(),
+                    d.kind.as_str(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            overrides,
+            vec![
+                ("bar", "/overrides/bar", Some("bar"), "registry"),
+                ("foo@1", "/overrides/foo@1/.", Some("foo"), "registry"),
+                (
+                    "foo@1>@scope/baz",
+                    "/overrides/foo@1/@scope~1baz",
+                    Some("@scope/baz"),
+                    "registry"
+                ),
+                ("qux", "/overrides/qux", None, "tag"),
+                (
+                    "**/left-pad",
+                    "/resolutions/**~1left-pad",
+                    Some("left-pad"),
+                    "registry"
+                ),
+                ("a>b", "/pnpm/overrides/a>b", Some("b"), "registry"),
+                ("c@^2", "/pnpm/overrides/c@^2", None, "tag"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_registry_lookup_unwraps_alias() {
+        let pkg = PackageInfo {
+            name: "strip-ansi-cjs".to_string(),
+            version_range: "npm:strip-ansi@^6.0.1".to_string(),
+            dep_type: "prod".to_string(),
+            pointer: None,
+            workspace: None,
+        };
+        let lookup = registry_lookup(&pkg);
+        assert_eq!(lookup.name, "strip-ansi");
+        assert_eq!(lookup.version_range, "^6.0.1");
+    }
+}
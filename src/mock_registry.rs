@@ -0,0 +1,532 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, HashMap};
+use std::hash::{Hash, Hasher};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+const ABBREVIATED: &str = "application/vnd.npm.install-v1+json";
+
+/// Per-version fields kept in abbreviated packuments
+const ABBREVIATED_VERSION_FIELDS: [&str; 16] = [
+    "name",
+    "version",
+    "deprecated",
+    "dependencies",
+    "optionalDependencies",
+    "devDependencies",
+    "bundleDependencies",
+    "peerDependencies",
+    "peerDependenciesMeta",
+    "bin",
+    "directories",
+    "dist",
+    "engines",
+    "cpu",
+    "os",
+    "hasInstallScript",
+];
+
+/// A request as seen by the mock registry
+#[derive(Debug, Clone)]
+pub struct MockRequest {
+    pub method: String,
+    pub path: String,
+    /// Header names are lower-cased
+    pub headers: HashMap<String, String>,
+    pub body: Vec<u8>,
+}
+
+impl MockRequest {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(name).map(|v| v.as_str())
+    }
+}
+
+/// Local stand-in for an npm registry, so retries, auth and concurrency can be
+/// tested without network access. Full packuments are read from
+/// `<dir>/<name>.json` (`<dir>/@scope/name.json` when scoped); the abbreviated
+/// form is derived from them for `application/vnd.npm.install-v1+json` requests
+pub struct MockRegistry {
+    dir: PathBuf,
+    /// Advisories by package name, served from the bulk advisory endpoint
+    advisories: Option<PathBuf>,
+    auth_token: Option<String>,
+    latency: Duration,
+    rate_limited: usize,
+    retry_after: Option<u64>,
+}
+
+/// Handle to a running mock registry
+pub struct MockServer {
+    pub url: String,
+    state: Arc<ServerState>,
+}
+
+struct ServerState {
+    registry: MockRegistry,
+    requests: Mutex<Vec<MockRequest>>,
+    in_flight: AtomicUsize,
+    peak_in_flight: AtomicUsize,
+}
+
+impl MockRegistry {
+    pub fn new(dir: &str) -> Self {
+        Self {
+            dir: PathBuf::from(dir),
+            advisories: None,
+            auth_token: None,
+            latency: Duration::ZERO,
+            rate_limited: 0,
+            retry_after: None,
+        }
+    }
+
+    /// Serve the packuments under `__test__/fixtures/packuments` and the
+    /// advisories in `__test__/fixtures/advisories.json`
+    pub fn fixtures() -> Self {
+        Self::new(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/__test__/fixtures/packuments"
+        ))
+        .with_advisories(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/__test__/fixtures/advisories.json"
+        ))
+    }
+
+    /// Answer bulk advisory requests from a JSON file of advisories by package
+    pub fn with_advisories(mut self, path: &str) -> Self {
+        self.advisories = Some(PathBuf::from(path));
+        self
+    }
+
+    /// Answer 401 unless requests carry `Authorization: Bearer <token>`
+    pub fn with_auth_token(mut self, token: &str) -> Self {
+        self.auth_token = Some(token.to_string());
+        self
+    }
+
+    /// Delay every response, e.g. to keep requests in flight concurrently
+    pub fn with_latency(mut self, latency: Duration) -> Self {
+        self.latency = latency;
+        self
+    }
+
+    /// Answer the first `count` requests with 429, optionally with a
+    /// `Retry-After` of `retry_after` seconds
+    pub fn with_rate_limit(mut self, count: usize, retry_after: Option<u64>) -> Self {
+        self.rate_limited = count;
+        self.retry_after = retry_after;
+        self
+    }
+
+    pub async fn start(self) -> MockServer {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let state = Arc::new(ServerState {
+            registry: self,
+            requests: Mutex::new(Vec::new()),
+            in_flight: AtomicUsize::new(0),
+            peak_in_flight: AtomicUsize::new(0),
+        });
+
+        let server = state.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(server.clone().handle(stream));
+            }
+        });
+        MockServer { url, state }
+    }
+}
+
+impl MockServer {
+    /// Every request received so far, in arrival order
+    pub fn requests(&self) -> Vec<MockRequest> {
+        self.state.requests.lock().unwrap().clone()
+    }
+
+    /// Highest number of requests that were being handled at the same time
+    pub fn peak_in_flight(&self) -> usize {
+        self.state.peak_in_flight.load(Ordering::SeqCst)
+    }
+}
+
+/// A response before it is written to the wire
+struct Reply {
+    status: &'static str,
+    headers: Vec<(&'static str, String)>,
+    body: String,
+}
+
+impl Reply {
+    fn new(status: &'static str, body: String) -> Self {
+        Self {
+            status,
+            headers: Vec::new(),
+            body,
+        }
+    }
+
+    fn error(status: &'static str, message: &str) -> Self {
+        Self::new(status, serde_json::json!({ "error": message }).to_string())
+    }
+}
+
+impl ServerState {
+    async fn handle(self: Arc<Self>, mut stream: TcpStream) {
+        let Some(request) = read_request(&mut stream).await else {
+            return;
+        };
+        let index = {
+            let mut requests = self.requests.lock().unwrap();
+            requests.push(request.clone());
+            requests.len()
+        };
+
+        let now = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+        self.peak_in_flight.fetch_max(now, Ordering::SeqCst);
+        if !self.registry.latency.is_zero() {
+            tokio::time::sleep(self.registry.latency).await;
+        }
+        let reply = self.reply(&request, index);
+        self.in_flight.fetch_sub(1, Ordering::SeqCst);
+
+        let _ = write_reply(&mut stream, reply).await;
+    }
+
+    fn reply(&self, request: &MockRequest, index: usize) -> Reply {
+        let registry = &self.registry;
+        if index <= registry.rate_limited {
+            let mut reply = Reply::error("429 Too Many Requests", "rate limited");
+            if let Some(seconds) = registry.retry_after {
+                reply.headers.push(("Retry-After", seconds.to_string()));
+            }
+            return reply;
+        }
+
+        if let Some(token) = &registry.auth_token {
+            let expected = format!("Bearer {}", token);
+            if request.header("authorization") != Some(expected.as_str()) {
+                return Reply::error("401 Unauthorized", "authentication required");
+            }
+        }
+
+        let path = request.path.split('?').next().unwrap_or_default();
+        if request.method == "POST" && path == "/-/npm/v1/security/advisories/bulk" {
+            return self.bulk_advisories(request);
+        }
+        if request.method != "GET" {
+            return Reply::error("405 Method Not Allowed", "method not allowed");
+        }
+
+        let (name, dist_tags_only) = match path.strip_prefix("/-/package/") {
+            Some(rest) => match rest.strip_suffix("/dist-tags") {
+                Some(name) => (decode_name(name), true),
+                None => (None, false),
+            },
+            None => (decode_name(path.trim_start_matches('/')), false),
+        };
+        let Some(packument) = name.and_then(|n| self.load(&n)) else {
+            return Reply::error("404 Not Found", "not found");
+        };
+
+        let body = if dist_tags_only {
+            packument["dist-tags"].to_string()
+        } else if request
+            .header("accept")
+            .is_some_and(|a| a.contains(ABBREVIATED))
+        {
+            abbreviate(&packument).to_string()
+        } else {
+            packument.to_string()
+        };
+
+        let etag = etag(&body);
+        if request.header("if-none-match") == Some(etag.as_str()) {
+            let mut reply = Reply::new("304 Not Modified", String::new());
+            reply.headers.push(("ETag", etag));
+            return reply;
+        }
+        let mut reply = Reply::new("200 OK", body);
+        reply.headers.push(("ETag", etag));
+        reply
+    }
+
+    /// Advisories affecting any of the requested versions, by package
+    fn bulk_advisories(&self, request: &MockRequest) -> Reply {
+        let Ok(query) = serde_json::from_slice::<HashMap<String, Vec<String>>>(&request.body)
+        else {
+            return Reply::error("400 Bad Request", "invalid body");
+        };
+        let known: HashMap<String, Vec<serde_json::Value>> = self
+            .registry
+            .advisories
+            .as_ref()
+            .and_then(|path| std::fs::read_to_string(path).ok())
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default();
+
+        let mut found = serde_json::Map::new();
+        for (name, versions) in query {
+            let affecting: Vec<serde_json::Value> = known
+                .get(&name)
+                .into_iter()
+                .flatten()
+                .filter(|advisory| {
+                    let Some(Ok(range)) = advisory["vulnerable_versions"]
+                        .as_str()
+                        .map(|r| r.parse::<node_semver::Range>())
+                    else {
+                        return false;
+                    };
+                    versions
+                        .iter()
+                        .filter_map(|v| v.parse::<node_semver::Version>().ok())
+                        .any(|v| range.satisfies(&v))
+                })
+                .cloned()
+                .collect();
+            if !affecting.is_empty() {
+                found.insert(name, serde_json::Value::Array(affecting));
+            }
+        }
+        Reply::new("200 OK", serde_json::Value::Object(found).to_string())
+    }
+
+    fn load(&self, name: &str) -> Option<serde_json::Value> {
+        let path = self.registry.dir.join(format!("{}.json", name));
+        let content = std::fs::read_to_string(path).ok()?;
+        serde_json::from_str(&content).ok()
+    }
+}
+
+/// Turn a request path segment (`lodash`, `@scope%2fname`, `@scope/name`)
+/// into a package name, rejecting anything that could escape the directory
+fn decode_name(segment: &str) -> Option<String> {
+    let name = segment.replacen("%2f", "/", 1).replacen("%2F", "/", 1);
+    let valid = match name.strip_prefix('@') {
+        Some(scoped) => scoped.split('/').count() == 2,
+        None => !name.contains('/'),
+    };
+    if !valid || name.is_empty() || name.contains("..") || name.contains('%') {
+        return None;
+    }
+    Some(name)
+}
+
+/// Reduce a full packument to the corgi form npm serves to installers
+fn abbreviate(packument: &serde_json::Value) -> serde_json::Value {
+    let versions: serde_json::Map<String, serde_json::Value> = packument["versions"]
+        .as_object()
+        .map(|versions| {
+            versions
+                .iter()
+                .map(|(version, manifest)| {
+                    let kept: serde_json::Map<String, serde_json::Value> = manifest
+                        .as_object()
+                        .map(|fields| {
+                            fields
+                                .iter()
+                                .filter(|(k, _)| ABBREVIATED_VERSION_FIELDS.contains(&k.as_str()))
+                                .map(|(k, v)| (k.clone(), v.clone()))
+                                .collect()
+                        })
+                        .unwrap_or_default();
+                    (version.clone(), serde_json::Value::Object(kept))
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    serde_json::json!({
+        "name": packument["name"],
+        "modified": packument["time"]["modified"],
+        "dist-tags": packument["dist-tags"],
+        "versions": versions,
+    })
+}
+
+fn etag(body: &str) -> String {
+    let mut hasher = DefaultHasher::new();
+    body.hash(&mut hasher);
+    format!("\"{:016x}\"", hasher.finish())
+}
+
+async fn read_request(stream: &mut TcpStream) -> Option<MockRequest> {
+    let mut data = Vec::new();
+    let mut buf = [0u8; 4096];
+    let head_end = loop {
+        if let Some(pos) = data.windows(4).position(|w| w == b"\r\n\r\n") {
+            break pos;
+        }
+        let n = stream.read(&mut buf).await.ok()?;
+        if n == 0 {
+            return None;
+        }
+        data.extend_from_slice(&buf[..n]);
+    };
+
+    let head = String::from_utf8_lossy(&data[..head_end]).to_string();
+    let mut lines = head.split("\r\n");
+    let mut request_line = lines.next()?.split_whitespace();
+    let method = request_line.next()?.to_string();
+    let path = request_line.next()?.to_string();
+    let headers: HashMap<String, String> = lines
+        .filter_map(|line| {
+            let (name, value) = line.split_once(':')?;
+            Some((name.trim().to_ascii_lowercase(), value.trim().to_string()))
+        })
+        .collect();
+
+    let length: usize = headers
+        .get("content-length")
+        .and_then(|l| l.parse().ok())
+        .unwrap_or(0);
+    let mut body = data[head_end + 4..].to_vec();
+    while body.len() < length {
+        let n = stream.read(&mut buf).await.ok()?;
+        if n == 0 {
+            break;
+        }
+        body.extend_from_slice(&buf[..n]);
+    }
+
+    Some(MockRequest {
+        method,
+        path,
+        headers,
+        body,
+    })
+}
+
+async fn write_reply(stream: &mut TcpStream, reply: Reply) -> std::io::Result<()> {
+    let mut headers = BTreeMap::new();
+    headers.insert("Content-Type", "application/json".to_string());
+    headers.insert("Content-Length", reply.body.len().to_string());
+    headers.insert("Connection", "close".to_string());
+    headers.extend(reply.headers);
+
+    let mut response = format!("HTTP/1.1 {}\r\n", reply.status);
+    for (name, value) in headers {
+        response.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    response.push_str("\r\n");
+    response.push_str(&reply.body);
+
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn get(url: &str, headers: &[(&str, &str)]) -> reqwest::Response {
+        let mut request = reqwest::Client::new().get(url);
+        for (name, value) in headers {
+            request = request.header(*name, *value);
+        }
+        request.send().await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_serves_full_and_abbreviated_packuments() {
+        let server = MockRegistry::fixtures().start().await;
+
+        let full: serde_json::Value = get(&format!("{}/lodash", server.url), &[])
+            .await
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(full["dist-tags"]["latest"], "4.17.21");
+        assert_eq!(full["versions"]["4.17.21"]["license"], "MIT");
+        assert!(full["time"]["4.17.21"].is_string());
+
+        let abbreviated: serde_json::Value = get(
+            &format!("{}/lodash", server.url),
+            &[("Accept", ABBREVIATED)],
+        )
+        .await
+        .json()
+        .await
+        .unwrap();
+        assert_eq!(abbreviated["modified"], full["time"]["modified"]);
+        assert!(abbreviated["time"].is_null());
+        assert!(abbreviated["versions"]["4.17.21"]["license"].is_null());
+        assert_eq!(
+            abbreviated["versions"]["4.0.0"]["deprecated"],
+            "Upgrade to 4.17.21"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_scoped_names_and_dist_tags() {
+        let server = MockRegistry::fixtures().start().await;
+
+        for path in ["@types%2fnode", "@types%2Fnode", "@types/node"] {
+            let response = get(&format!("{}/{}", server.url, path), &[]).await;
+            assert_eq!(response.status(), 200, "{}", path);
+        }
+
+        let tags: serde_json::Value = get(
+            &format!("{}/-/package/@types%2fnode/dist-tags", server.url),
+            &[],
+        )
+        .await
+        .json()
+        .await
+        .unwrap();
+        assert_eq!(tags["ts5.0"], "20.11.0");
+
+        let missing = get(&format!("{}/left-pad", server.url), &[]).await;
+        assert_eq!(missing.status(), 404);
+        let escape = get(&format!("{}/..%2fCargo", server.url), &[]).await;
+        assert_eq!(escape.status(), 404);
+    }
+
+    #[tokio::test]
+    async fn test_conditional_request() {
+        let server = MockRegistry::fixtures().start().await;
+        let url = format!("{}/chalk", server.url);
+
+        let first = get(&url, &[]).await;
+        let etag = first.headers()["etag"].to_str().unwrap().to_string();
+        let second = get(&url, &[("If-None-Match", &etag)]).await;
+        assert_eq!(second.status(), 304);
+        assert!(second.bytes().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_auth_and_rate_limit() {
+        let server = MockRegistry::fixtures()
+            .with_rate_limit(1, Some(2))
+            .with_auth_token("s3cret")
+            .start()
+            .await;
+        let url = format!("{}/lodash", server.url);
+
+        let limited = get(&url, &[("Authorization", "Bearer s3cret")]).await;
+        assert_eq!(limited.status(), 429);
+        assert_eq!(limited.headers()["retry-after"], "2");
+
+        assert_eq!(get(&url, &[]).await.status(), 401);
+        assert_eq!(
+            get(&url, &[("Authorization", "Bearer wrong")])
+                .await
+                .status(),
+            401
+        );
+        assert_eq!(
+            get(&url, &[("Authorization", "Bearer s3cret")])
+                .await
+                .status(),
+            200
+        );
+        assert_eq!(server.requests().len(), 4);
+    }
+}
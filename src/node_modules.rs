@@ -0,0 +1,240 @@
+use serde_json::Value;
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use crate::global;
+use crate::manifest::{self, Specifier};
+use crate::semver_utils;
+use crate::types::{InstalledIssue, PackageInfo};
+
+/// Dependency types that end up in node_modules
+const INSTALLED_DEP_TYPES: [&str; 4] = ["prod", "dev", "optional", "peer"];
+
+/// Sections of an installed package whose dependencies are installed with it
+const INSTALLED_SECTIONS: [&str; 3] = ["dependencies", "optionalDependencies", "peerDependencies"];
+
+/// Compare what is installed under `root` with the ranges `packages` declare:
+/// packages that are missing, installed at a version outside their range, or
+/// installed without anything depending on them. Packages with a `workspace`
+/// are resolved from that directory below `root`, walking up like Node does.
+/// Whatever the package.json of `root` or a workspace declares counts as
+/// depended on, checked or not
+pub fn check(root: &Path, packages: &[PackageInfo]) -> Vec<InstalledIssue> {
+    let mut issues = Vec::new();
+    // Directories whose node_modules are looked at for extraneous packages
+    let mut bases: BTreeMap<PathBuf, Option<String>> = BTreeMap::new();
+    bases.insert(root.to_path_buf(), None);
+    let mut reached = HashSet::new();
+
+    for pkg in packages {
+        if !INSTALLED_DEP_TYPES.contains(&pkg.dep_type.as_str()) {
+            continue;
+        }
+        let base = match pkg.workspace.as_deref() {
+            Some(workspace) if !workspace.is_empty() => root.join(workspace),
+            _ => root.to_path_buf(),
+        };
+        bases
+            .entry(base.clone())
+            .or_insert_with(|| pkg.workspace.clone());
+
+        let found = resolve(&base, &pkg.name);
+        if let Some(dir) = &found {
+            visit(dir, &mut reached);
+        }
+
+        let specifier = Specifier::parse(&pkg.version_range);
+        let Some((_, range)) = specifier.registry_range(&pkg.name) else {
+            continue;
+        };
+        let issue = |kind: &str, installed: Option<String>, path: &Path| InstalledIssue {
+            name: pkg.name.clone(),
+            kind: kind.to_string(),
+            range: Some(range.to_string()),
+            installed,
+            dep_type: Some(pkg.dep_type.clone()),
+            workspace: pkg.workspace.clone(),
+            path: path.to_string_lossy().to_string(),
+        };
+        match found
+            .as_deref()
+            .and_then(|dir| Some((dir, global::installed(dir)?.1)))
+        {
+            Some((dir, version)) => {
+                if semver_utils::satisfies(range, &version) == Some(false) {
+                    issues.push(issue("mismatched", Some(version), dir));
+                }
+            }
+            // Optional dependencies may fail to install, and peers are left
+            // to the dependent's own dependencies
+            None if pkg.dep_type == "optional" || pkg.dep_type == "peer" => {}
+            None => issues.push(issue(
+                "missing",
+                None,
+                &base.join("node_modules").join(&pkg.name),
+            )),
+        }
+    }
+
+    for (base, workspace) in bases {
+        let declared = manifest::read_manifest(&base.join("package.json").to_string_lossy())
+            .map(|m| m.dependencies)
+            .unwrap_or_default();
+        for dependency in declared {
+            if INSTALLED_DEP_TYPES.contains(&dependency.dep_type.as_str()) {
+                if let Some(dir) = resolve(&base, &dependency.name) {
+                    visit(&dir, &mut reached);
+                }
+            }
+        }
+        for (name, version, dir) in global::scan_node_modules(&base.join("node_modules")) {
+            let Ok(real) = dir.canonicalize() else {
+                continue;
+            };
+            // Workspaces and `link:` dependencies are symlinked in from
+            // outside any node_modules
+            let linked = !real.components().any(|c| c.as_os_str() == "node_modules");
+            if linked || reached.contains(&real) {
+                continue;
+            }
+            issues.push(InstalledIssue {
+                name,
+                kind: "extraneous".to_string(),
+                range: None,
+                installed: Some(version),
+                dep_type: None,
+                workspace: workspace.clone(),
+                path: dir.to_string_lossy().to_string(),
+            });
+        }
+    }
+    issues
+}
+
+/// Directory `name` resolves to from `from`, looking in each ancestor's
+/// node_modules in turn
+fn resolve(from: &Path, name: &str) -> Option<PathBuf> {
+    from.ancestors()
+        .filter(|dir| dir.file_name().is_none_or(|n| n != "node_modules"))
+        .map(|dir| dir.join("node_modules").join(name))
+        .find(|candidate| candidate.is_dir())
+}
+
+/// Mark the package installed at `dir` and everything it depends on as
+/// reached, following symlinks so pnpm's layout resolves too
+fn visit(dir: &Path, reached: &mut HashSet<PathBuf>) {
+    let mut stack = vec![dir.to_path_buf()];
+    while let Some(dir) = stack.pop() {
+        let Ok(real) = dir.canonicalize() else {
+            continue;
+        };
+        if !reached.insert(real.clone()) {
+            continue;
+        }
+        let Ok(text) = std::fs::read_to_string(real.join("package.json")) else {
+            continue;
+        };
+        let Ok(manifest) = serde_json::from_str::<Value>(&text) else {
+            continue;
+        };
+        for section in INSTALLED_SECTIONS {
+            let names = manifest
+                .get(section)
+                .and_then(Value::as_object)
+                .into_iter()
+                .flat_map(|deps| deps.keys());
+            stack.extend(names.filter_map(|name| resolve(&real, name)));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn install(node_modules: &Path, name: &str, manifest: &str) {
+        let dir = node_modules.join(name);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("package.json"), manifest).unwrap();
+    }
+
+    fn pkg(name: &str, range: &str, dep_type: &str, workspace: Option<&str>) -> PackageInfo {
+        PackageInfo {
+            name: name.to_string(),
+            version_range: range.to_string(),
+            dep_type: dep_type.to_string(),
+            pointer: None,
+            workspace: workspace.map(String::from),
+        }
+    }
+
+    #[test]
+    fn test_reports_missing_mismatched_and_extraneous() {
+        let root = std::env::temp_dir().join(format!("turbo-ncu-installed-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        let modules = root.join("node_modules");
+        install(
+            &modules,
+            "express",
+            r#"{"name":"express","version":"4.18.2","dependencies":{"qs":"6.11.0"}}"#,
+        );
+        install(&modules, "qs", r#"{"name":"qs","version":"6.11.0"}"#);
+        install(
+            &modules,
+            "lodash",
+            r#"{"name":"lodash","version":"4.17.20"}"#,
+        );
+        install(
+            &modules,
+            "@types/node",
+            r#"{"name":"@types/node","version":"20.1.0"}"#,
+        );
+        install(
+            &modules,
+            "left-pad",
+            r#"{"name":"left-pad","version":"1.3.0"}"#,
+        );
+        install(
+            &modules,
+            "strip",
+            r#"{"name":"strip-ansi","version":"6.0.1"}"#,
+        );
+        // Hoisted for the workspace, which declares a newer range
+        install(&modules, "react", r#"{"name":"react","version":"17.0.2"}"#);
+        // Declared, but not among the checked packages
+        install(&modules, "tool", r#"{"name":"tool","version":"1.0.0"}"#);
+        std::fs::write(
+            root.join("package.json"),
+            r#"{"devDependencies":{"tool":"github:acme/tool"}}"#,
+        )
+        .unwrap();
+        std::fs::create_dir_all(root.join("packages/web")).unwrap();
+
+        let packages = vec![
+            pkg("express", "^4.18.0", "prod", None),
+            pkg("lodash", "^4.17.21", "prod", None),
+            pkg("@types/node", "^20.0.0", "dev", None),
+            pkg("strip", "npm:strip-ansi@^6.0.0", "prod", None),
+            pkg("chalk", "^5.0.0", "prod", None),
+            pkg("fsevents", "^2.3.0", "optional", None),
+            pkg("react", "^18.2.0", "prod", Some("packages/web")),
+        ];
+        let issues = check(&root, &packages);
+        let found: Vec<(&str, &str, Option<&str>)> = issues
+            .iter()
+            .map(|i| (i.kind.as_str(), i.name.as_str(), i.installed.as_deref()))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                ("mismatched", "lodash", Some("4.17.20")),
+                ("missing", "chalk", None),
+                ("mismatched", "react", Some("17.0.2")),
+                ("extraneous", "left-pad", Some("1.3.0")),
+            ]
+        );
+        assert_eq!(issues[2].workspace.as_deref(), Some("packages/web"));
+        assert_eq!(issues[2].range.as_deref(), Some("^18.2.0"));
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,428 @@
+use std::collections::{BTreeMap, HashSet};
+use std::path::Path;
+
+use crate::catalog;
+use crate::grouping;
+use crate::json_edit::{self, pointer_token, Edit};
+use crate::types::{
+    AppliedPlan, PlanBatch, PlanEdit, PlanOptions, PlanPackage, UpdateGroup, UpdatePlan,
+    UpdateResult,
+};
+
+/// Version of the plan format written by `export`
+pub const PLAN_VERSION: u32 = 1;
+
+/// Build the plan for `updates`: one batch per group, taking its members'
+/// manifest changes, and one per remaining package. Updates that need no
+/// manifest change are left out
+pub fn export(
+    updates: &[UpdateResult],
+    groups: &[UpdateGroup],
+    options: &PlanOptions,
+) -> UpdatePlan {
+    let prefix = options.branch_prefix.as_deref().unwrap_or("turbo-ncu/");
+    let mut batches: Vec<PlanBatch> = Vec::new();
+    let mut grouped = HashSet::new();
+
+    for group in groups {
+        let members: Vec<&UpdateResult> = group
+            .updates
+            .iter()
+            .filter(|u| u.requires_manifest_change())
+            .collect();
+        if members.is_empty() {
+            continue;
+        }
+        grouped.extend(members.iter().map(|u| key(u)));
+        let (slug, label) = match group.source.as_str() {
+            "scope" => (slug(&group.name), format!("{} packages", group.name)),
+            "repository" => {
+                let mut names: Vec<&str> = members.iter().map(|u| u.name.as_str()).collect();
+                names.sort();
+                names.dedup();
+                let repository = group.name.rsplit('/').next().unwrap_or(&group.name);
+                (slug(repository), names.join(", "))
+            }
+            _ => (slug(&group.name), format!("{} group", group.name)),
+        };
+        let id = format!("group-{}", slug);
+        let (branch, commit_title) = match &group.version {
+            Some(version) => (
+                format!("{}{}-{}", prefix, id, version),
+                format!("Update {} to {}", label, version),
+            ),
+            None => (format!("{}{}", prefix, id), format!("Update {}", label)),
+        };
+        batches.push(PlanBatch {
+            id,
+            group: Some(group.name.clone()),
+            branch,
+            commit_title,
+            packages: packages(&members),
+            edits: edits(&members, options),
+        });
+    }
+
+    // Every other package is a batch of its own, covering all its manifests
+    let mut singles: BTreeMap<(&str, &str), Vec<&UpdateResult>> = BTreeMap::new();
+    for update in updates {
+        if update.requires_manifest_change() && !grouped.contains(&key(update)) {
+            singles
+                .entry((update.name.as_str(), update.latest.as_str()))
+                .or_default()
+                .push(update);
+        }
+    }
+    let versions = |name: &str| singles.keys().filter(|(n, _)| *n == name).count();
+    for ((name, version), members) in &singles {
+        let id = if versions(name) > 1 {
+            format!("{}-{}", slug(name), slug(version))
+        } else {
+            slug(name)
+        };
+        batches.push(PlanBatch {
+            branch: format!("{}{}-{}", prefix, slug(name), version),
+            commit_title: format!("Update {} to {}", name, version),
+            id,
+            group: None,
+            packages: packages(members),
+            edits: edits(members, options),
+        });
+    }
+
+    batches.sort_by(|a, b| a.id.cmp(&b.id));
+    UpdatePlan {
+        version: PLAN_VERSION,
+        batches,
+    }
+}
+
+/// The plan as pretty-printed JSON; field order is fixed, so equal plans
+/// serialize identically
+pub fn to_json(plan: &UpdatePlan) -> String {
+    serde_json::to_string_pretty(plan).unwrap_or_default()
+}
+
+/// Parse a plan written by `to_json`, rejecting format versions this build
+/// does not know
+pub fn from_json(text: &str) -> Result<UpdatePlan, String> {
+    let plan: UpdatePlan =
+        serde_json::from_str(text).map_err(|e| format!("Invalid update plan: {}", e))?;
+    if plan.version != PLAN_VERSION {
+        return Err(format!(
+            "Unsupported update plan version {}; expected {}",
+            plan.version, PLAN_VERSION
+        ));
+    }
+    Ok(plan)
+}
+
+/// Apply the edits of `plan` (or of the batches named in `batches`) to the
+/// files below `root`. Each edit is only written where the file still holds
+/// its old value, so a plan can be applied again, or after other changes
+pub fn apply_plan(
+    plan: &UpdatePlan,
+    root: &Path,
+    batches: Option<&[String]>,
+) -> Result<AppliedPlan, String> {
+    if let Some(ids) = batches {
+        if let Some(unknown) = ids
+            .iter()
+            .find(|id| !plan.batches.iter().any(|b| b.id == **id))
+        {
+            return Err(format!("Unknown batch \"{}\" in update plan", unknown));
+        }
+    }
+    let mut by_file: BTreeMap<&str, Vec<&PlanEdit>> = BTreeMap::new();
+    for batch in &plan.batches {
+        if batches.is_none_or(|ids| ids.contains(&batch.id)) {
+            for edit in &batch.edits {
+                by_file.entry(edit.path.as_str()).or_default().push(edit);
+            }
+        }
+    }
+
+    let mut applied = AppliedPlan {
+        applied: 0,
+        unchanged: 0,
+        conflicts: Vec::new(),
+    };
+    for (file, file_edits) in by_file {
+        let path = root.join(file);
+        let text = std::fs::read_to_string(&path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        let yaml = file.ends_with(".yaml") || file.ends_with(".yml");
+        let json = if yaml {
+            None
+        } else {
+            Some(
+                json_edit::parse(&text)
+                    .map_err(|e| format!("Failed to parse {}: {}", path.display(), e))?,
+            )
+        };
+
+        let mut pending = Vec::new();
+        for edit in file_edits {
+            let current = match &json {
+                Some(root) => root
+                    .pointer_all(&edit.pointer)
+                    .first()
+                    .and_then(|node| node.as_str())
+                    .map(String::from),
+                None => catalog::specifier_at(&text, &edit.pointer),
+            };
+            match current {
+                Some(value) if value == edit.new_value => applied.unchanged += 1,
+                Some(value) if value == edit.old_value => pending.push(Edit {
+                    pointer: edit.pointer.clone(),
+                    value: edit.new_value.clone(),
+                }),
+                _ => applied.conflicts.push(edit.clone()),
+            }
+        }
+        if pending.is_empty() {
+            continue;
+        }
+
+        let (output, count) = if yaml {
+            catalog::replace(&text, &pending)
+        } else {
+            json_edit::apply_edits(&text, &pending)?
+        };
+        let tmp_path = path.with_extension("tmp");
+        std::fs::write(&tmp_path, output).map_err(|e| format!("Write tmp: {}", e))?;
+        std::fs::rename(&tmp_path, &path).map_err(|e| format!("Rename: {}", e))?;
+        applied.applied += count;
+    }
+    Ok(applied)
+}
+
+/// Identifies one occurrence of an update across groups and the update list
+fn key(update: &UpdateResult) -> (String, Option<String>, Option<String>, String) {
+    (
+        update.name.clone(),
+        update.workspace.clone(),
+        update.pointer.clone(),
+        update.dep_type.clone(),
+    )
+}
+
+fn packages(members: &[&UpdateResult]) -> Vec<PlanPackage> {
+    let mut packages: Vec<PlanPackage> = Vec::new();
+    for update in members {
+        let name = registry_name(update);
+        if packages
+            .iter()
+            .any(|p| p.name == name && p.version == update.latest)
+        {
+            continue;
+        }
+        let metadata = update.metadata.as_ref();
+        let repository_url = metadata
+            .and_then(|m| m.repository.as_deref())
+            .and_then(grouping::normalize_repository);
+        let releases_url = repository_url.as_deref().and_then(|url| {
+            if url.starts_with("https://github.com/") {
+                Some(format!("{}/releases", url))
+            } else if url.starts_with("https://gitlab.com/") {
+                Some(format!("{}/-/releases", url))
+            } else {
+                None
+            }
+        });
+        packages.push(PlanPackage {
+            npm_url: format!("https://www.npmjs.com/package/{}/v/{}", name, update.latest),
+            name,
+            version: update.latest.clone(),
+            update_type: update.update_type.clone(),
+            repository_url,
+            releases_url,
+            homepage: metadata.and_then(|m| m.homepage.clone()),
+        });
+    }
+    packages.sort_by(|a, b| a.name.cmp(&b.name));
+    packages
+}
+
+/// Registry package an update installs; `npm:` aliases name another one
+fn registry_name(update: &UpdateResult) -> String {
+    update
+        .new_range
+        .strip_prefix("npm:")
+        .and_then(|spec| {
+            let at = spec[1..].find('@')? + 1;
+            Some(spec[..at].to_string())
+        })
+        .unwrap_or_else(|| update.name.clone())
+}
+
+fn edits(members: &[&UpdateResult], options: &PlanOptions) -> Vec<PlanEdit> {
+    let mut edits: Vec<PlanEdit> = members
+        .iter()
+        .filter_map(|update| {
+            let path = if update.dep_type == "catalog" {
+                options
+                    .catalog
+                    .clone()
+                    .unwrap_or_else(|| "pnpm-workspace.yaml".to_string())
+            } else {
+                match update.workspace.as_deref() {
+                    Some("") => "package.json".to_string(),
+                    Some(workspace) => format!("{}/package.json", workspace),
+                    None => options
+                        .manifest
+                        .clone()
+                        .unwrap_or_else(|| "package.json".to_string()),
+                }
+            };
+            let pointer = match &update.pointer {
+                Some(pointer) => pointer.clone(),
+                None => {
+                    let section = json_edit::section_for_dep_type(&update.dep_type)?;
+                    format!("/{}/{}", section, pointer_token(&update.name))
+                }
+            };
+            Some(PlanEdit {
+                path,
+                pointer,
+                old_value: update.current.clone(),
+                new_value: update.new_range.clone(),
+            })
+        })
+        .collect();
+    edits.sort_by(|a, b| (&a.path, &a.pointer).cmp(&(&b.path, &b.pointer)));
+    edits.dedup();
+    edits
+}
+
+/// Lowercase branch-safe form of a name: "@babel/core" becomes "babel-core"
+fn slug(name: &str) -> String {
+    let mut slug = String::new();
+    for c in name.chars() {
+        if c.is_ascii_alphanumeric() || c == '.' {
+            slug.push(c.to_ascii_lowercase());
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    slug.trim_end_matches('-').to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::VersionDetails;
+
+    fn update(name: &str, current: &str, new_range: &str, workspace: Option<&str>) -> UpdateResult {
+        UpdateResult {
+            workspace: workspace.map(String::from),
+            ..UpdateResult::for_test(name, current, new_range)
+        }
+    }
+
+    fn sample() -> (Vec<UpdateResult>, Vec<UpdateGroup>) {
+        let core = update("@babel/core", "^7.23.0", "^7.24.0", Some("apps/web"));
+        let preset = update("@babel/preset-env", "^7.23.0", "^7.24.0", Some("apps/web"));
+        let mut lodash = update("lodash", "^4.17.20", "^4.17.21", Some(""));
+        lodash.metadata = Some(VersionDetails {
+            repository: Some("git+https://github.com/lodash/lodash.git".to_string()),
+            ..Default::default()
+        });
+        let lodash_web = update("lodash", "^4.17.0", "^4.17.21", Some("apps/web"));
+        let mut catalog = update("react", "^18.2.0", "^18.3.1", None);
+        catalog.dep_type = "catalog".to_string();
+        catalog.pointer = Some("/catalog/react".to_string());
+        let group = UpdateGroup {
+            name: "@babel".to_string(),
+            source: "scope".to_string(),
+            version: Some("7.24.0".to_string()),
+            fell_back: false,
+            updates: vec![core.clone(), preset.clone()],
+        };
+        (vec![core, preset, lodash, lodash_web, catalog], vec![group])
+    }
+
+    #[test]
+    fn test_exports_batches() {
+        let (updates, groups) = sample();
+        let plan = export(&updates, &groups, &PlanOptions::default());
+        let ids: Vec<&str> = plan.batches.iter().map(|b| b.id.as_str()).collect();
+        assert_eq!(ids, vec!["group-babel", "lodash", "react"]);
+
+        let babel = &plan.batches[0];
+        assert_eq!(babel.branch, "turbo-ncu/group-babel-7.24.0");
+        assert_eq!(babel.commit_title, "Update @babel packages to 7.24.0");
+        assert_eq!(babel.edits.len(), 2);
+        assert_eq!(babel.edits[0].path, "apps/web/package.json");
+        assert_eq!(babel.edits[0].pointer, "/dependencies/@babel~1core");
+
+        let lodash = &plan.batches[1];
+        assert_eq!(lodash.branch, "turbo-ncu/lodash-4.17.21");
+        let paths: Vec<&str> = lodash.edits.iter().map(|e| e.path.as_str()).collect();
+        assert_eq!(paths, vec!["apps/web/package.json", "package.json"]);
+        assert_eq!(lodash.packages.len(), 1);
+        assert_eq!(
+            lodash.packages[0].releases_url.as_deref(),
+            Some("https://github.com/lodash/lodash/releases")
+        );
+        assert_eq!(
+            lodash.packages[0].npm_url,
+            "https://www.npmjs.com/package/lodash/v/4.17.21"
+        );
+        assert_eq!(plan.batches[2].edits[0].path, "pnpm-workspace.yaml");
+
+        let json = to_json(&plan);
+        assert!(json.contains("\"commitTitle\": \"Update lodash to 4.17.21\""));
+        assert_eq!(from_json(&json).unwrap(), plan);
+        let newer = json.replacen("\"version\": 1", "\"version\": 2", 1);
+        assert!(from_json(&newer).unwrap_err().contains("version 2"));
+    }
+
+    #[test]
+    fn test_applies_plan_once() {
+        let root = std::env::temp_dir().join(format!("turbo-ncu-plan-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(root.join("apps/web")).unwrap();
+        std::fs::write(
+            root.join("package.json"),
+            "{\n  \"dependencies\": { \"lodash\": \"^4.17.20\" }\n}\n",
+        )
+        .unwrap();
+        std::fs::write(
+            root.join("apps/web/package.json"),
+            "{\n  \"dependencies\": {\n    \"@babel/core\": \"^7.23.0\",\n    \"@babel/preset-env\": \"^7.22.0\",\n    \"lodash\": \"^4.17.0\"\n  }\n}\n",
+        )
+        .unwrap();
+        std::fs::write(
+            root.join("pnpm-workspace.yaml"),
+            "catalog:\n  react: ^18.2.0 # shared\n",
+        )
+        .unwrap();
+        let (updates, groups) = sample();
+        let plan = export(&updates, &groups, &PlanOptions::default());
+
+        let only = vec!["lodash".to_string(), "react".to_string()];
+        let result = apply_plan(&plan, &root, Some(&only)).unwrap();
+        assert_eq!((result.applied, result.unchanged), (3, 0));
+        assert_eq!(
+            std::fs::read_to_string(root.join("pnpm-workspace.yaml")).unwrap(),
+            "catalog:\n  react: ^18.3.1 # shared\n"
+        );
+
+        // preset-env moved on since the plan was made
+        let result = apply_plan(&plan, &root, None).unwrap();
+        assert_eq!((result.applied, result.unchanged), (1, 3));
+        assert_eq!(result.conflicts.len(), 1);
+        assert_eq!(
+            result.conflicts[0].pointer,
+            "/dependencies/@babel~1preset-env"
+        );
+        let web = std::fs::read_to_string(root.join("apps/web/package.json")).unwrap();
+        assert!(web.contains("\"@babel/core\": \"^7.24.0\""));
+        assert!(web.contains("\"lodash\": \"^4.17.21\""));
+
+        let missing = vec!["nope".to_string()];
+        assert!(apply_plan(&plan, &root, Some(&missing)).is_err());
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
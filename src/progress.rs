@@ -0,0 +1,88 @@
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+
+use crate::types::ProgressEvent;
+
+pub type ProgressCallback = Arc<dyn Fn(ProgressEvent) + Send + Sync>;
+
+/// Tracks how many packages of a check have finished and reports each step
+/// to an optional callback
+pub struct Progress {
+    callback: Option<ProgressCallback>,
+    total: u32,
+    completed: AtomicU32,
+}
+
+impl Progress {
+    pub fn new(total: u32, callback: Option<ProgressCallback>) -> Self {
+        Self {
+            callback,
+            total,
+            completed: AtomicU32::new(0),
+        }
+    }
+
+    /// Report an intermediate step (request start, retry) without counting the
+    /// package as finished
+    pub fn emit(&self, kind: &str, name: &str, attempt: u32, bytes: u32) {
+        let completed = self.completed.load(Ordering::SeqCst);
+        self.send(kind, name, attempt, bytes, completed);
+    }
+
+    /// Report a terminal step (cache hit, success, failure) and count the
+    /// package as finished
+    pub fn complete(&self, kind: &str, name: &str, attempt: u32, bytes: u32) {
+        let completed = self.completed.fetch_add(1, Ordering::SeqCst) + 1;
+        self.send(kind, name, attempt, bytes, completed);
+    }
+
+    fn send(&self, kind: &str, name: &str, attempt: u32, bytes: u32, completed: u32) {
+        if let Some(callback) = &self.callback {
+            callback(ProgressEvent {
+                kind: kind.to_string(),
+                name: name.to_string(),
+                completed,
+                total: self.total,
+                attempt,
+                bytes,
+            });
+        }
+    }
+}
+
+impl Default for Progress {
+    fn default() -> Self {
+        Self::new(0, None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[test]
+    fn test_progress_counts_terminal_events() {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let sink = events.clone();
+        let progress = Progress::new(
+            2,
+            Some(Arc::new(move |ev: ProgressEvent| {
+                sink.lock().unwrap().push(ev);
+            })),
+        );
+
+        progress.complete("cache-hit", "lodash", 0, 0);
+        progress.emit("start", "chalk", 0, 0);
+        progress.emit("retry", "chalk", 1, 0);
+        progress.complete("success", "chalk", 1, 512);
+
+        let events = events.lock().unwrap();
+        let kinds: Vec<&str> = events.iter().map(|e| e.kind.as_str()).collect();
+        assert_eq!(kinds, vec!["cache-hit", "start", "retry", "success"]);
+        let completed: Vec<u32> = events.iter().map(|e| e.completed).collect();
+        assert_eq!(completed, vec![1, 1, 1, 2]);
+        assert!(events.iter().all(|e| e.total == 2));
+        assert_eq!(events[3].bytes, 512);
+    }
+}
@@ -1,117 +1,1294 @@
-use reqwest::Client;
+use futures::future::join_all;
+use futures::stream::{FuturesUnordered, Stream};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, RETRY_AFTER};
+use reqwest::{Certificate, Client, Identity, NoProxy, Proxy, StatusCode};
 use serde::Deserialize;
-use std::collections::HashMap;
+use std::collections::hash_map::RandomState;
+use std::collections::{BTreeMap, HashMap};
+use std::hash::{BuildHasher, Hasher};
+use std::sync::atomic::{AtomicU32, Ordering};
 use std::sync::Arc;
-use std::time::Duration;
-use tokio::sync::Semaphore;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
-use crate::types::RegistryVersionInfo;
+use crate::advisories::AdvisoryIndex;
+use crate::cancel::CancelToken;
+use crate::fixtures::{FixtureMode, FixtureStore, RecordedResponse};
+use crate::global::Environment;
+use crate::progress::Progress;
+use crate::throttle::AdaptiveLimiter;
+use crate::types::{CheckOptions, RegistryVersionInfo, VersionDetails};
 
+/// Upper bound on how long a `Retry-After` header can make us wait
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(60);
+
+/// The parts of a packument we read; `time` is only sent in the full form
 #[derive(Deserialize)]
-struct AbbreviatedPackument {
+struct Packument {
     name: Option<String>,
     versions: Option<HashMap<String, serde_json::Value>>,
+    time: Option<HashMap<String, serde_json::Value>>,
+}
+
+/// Why a single request attempt failed
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum FailureKind {
+    /// Connection errors, timeouts and 408
+    Transport,
+    /// 429 Too Many Requests
+    RateLimited,
+    /// Any 5xx response
+    ServerError,
+    /// 404, e.g. a mirror that does not proxy the package
+    NotFound,
+    /// Other 4xx responses and malformed bodies
+    Fatal,
+    /// The check was aborted
+    Cancelled,
+}
+
+/// Outcome of a single failed request attempt
+#[derive(Debug, PartialEq)]
+struct FetchError {
+    kind: FailureKind,
+    message: String,
+    retry_after: Option<Duration>,
+}
+
+impl FetchError {
+    fn new(kind: FailureKind, message: String) -> Self {
+        Self {
+            kind,
+            message,
+            retry_after: None,
+        }
+    }
+
+    fn from_status(status: StatusCode, headers: &HeaderMap) -> Self {
+        let message = format!("HTTP {}", status);
+        let kind = match status {
+            StatusCode::TOO_MANY_REQUESTS => FailureKind::RateLimited,
+            StatusCode::REQUEST_TIMEOUT => FailureKind::Transport,
+            StatusCode::NOT_FOUND => FailureKind::NotFound,
+            s if s.is_server_error() => FailureKind::ServerError,
+            _ => FailureKind::Fatal,
+        };
+        let retry_after = match kind {
+            FailureKind::RateLimited | FailureKind::ServerError => {
+                parse_retry_after(headers, SystemTime::now())
+            }
+            _ => None,
+        };
+        Self {
+            kind,
+            message,
+            retry_after,
+        }
+    }
+
+    /// Whether another attempt against the same registry can succeed
+    fn is_retryable(&self) -> bool {
+        matches!(
+            self.kind,
+            FailureKind::Transport | FailureKind::RateLimited | FailureKind::ServerError
+        )
+    }
+
+    /// Whether `policy` says to try the next registry after this error
+    fn triggers_failover(&self, policy: &[String]) -> bool {
+        let class = match self.kind {
+            FailureKind::Transport => "timeout",
+            FailureKind::RateLimited => "429",
+            FailureKind::ServerError => "5xx",
+            FailureKind::NotFound => "404",
+            FailureKind::Fatal | FailureKind::Cancelled => return false,
+        };
+        policy.iter().any(|p| p == class)
+    }
+}
+
+/// Proxy and TLS settings for registry connections, mirroring npm's `proxy`,
+/// `https-proxy`, `noproxy`, `cafile`, `strict-ssl`, `cert` and `key` config
+#[derive(Debug, Clone)]
+pub struct NetworkOptions {
+    pub proxy: Option<String>,
+    pub https_proxy: Option<String>,
+    pub no_proxy: Option<String>,
+    pub ca_file: Option<String>,
+    pub strict_ssl: bool,
+    pub cert_file: Option<String>,
+    pub key_file: Option<String>,
+}
+
+impl NetworkOptions {
+    /// Take settings from `options`, falling back to npm's `proxy`,
+    /// `https-proxy`, `noproxy`, `cafile` and `strict-ssl` (see
+    /// `Environment::npm_setting`), then to the conventional `HTTP_PROXY` /
+    /// `HTTPS_PROXY` / `NO_PROXY` environment variables
+    pub fn from_options(options: &CheckOptions, env: &Environment) -> Self {
+        let var = |names: &[&str]| names.iter().find_map(|n| env.var(n)).map(String::from);
+
+        Self {
+            proxy: options
+                .proxy
+                .clone()
+                .or_else(|| env.npm_setting("proxy"))
+                .or_else(|| var(&["HTTP_PROXY", "http_proxy"])),
+            https_proxy: options
+                .https_proxy
+                .clone()
+                .or_else(|| env.npm_setting("https-proxy"))
+                .or_else(|| var(&["HTTPS_PROXY", "https_proxy"])),
+            no_proxy: options
+                .no_proxy
+                .clone()
+                .or_else(|| env.npm_setting("noproxy"))
+                .or_else(|| var(&["NO_PROXY", "no_proxy"])),
+            ca_file: options
+                .ca_file
+                .clone()
+                .or_else(|| env.npm_setting("cafile")),
+            strict_ssl: options
+                .strict_ssl
+                .or_else(|| env.npm_setting("strict-ssl").map(|v| v != "false"))
+                .unwrap_or(true),
+            cert_file: options.cert_file.clone(),
+            key_file: options.key_file.clone(),
+        }
+    }
+
+    fn build_client(&self, concurrency: u32, timeout_ms: u32) -> Result<Client, String> {
+        // Proxies are configured explicitly below, never picked up implicitly
+        let mut builder = Client::builder()
+            .timeout(Duration::from_millis(timeout_ms as u64))
+            .pool_max_idle_per_host(concurrency as usize)
+            .no_proxy();
+
+        let no_proxy = self.no_proxy.as_deref().and_then(NoProxy::from_string);
+        if let Some(url) = &self.proxy {
+            let proxy = Proxy::http(url).map_err(|e| format!("Invalid proxy {}: {}", url, e))?;
+            builder = builder.proxy(proxy.no_proxy(no_proxy.clone()));
+        }
+        // Like npm, HTTPS requests use `proxy` when no `https-proxy` is set
+        if let Some(url) = self.https_proxy.as_ref().or(self.proxy.as_ref()) {
+            let proxy = Proxy::https(url).map_err(|e| format!("Invalid proxy {}: {}", url, e))?;
+            builder = builder.proxy(proxy.no_proxy(no_proxy));
+        }
+
+        if let Some(path) = &self.ca_file {
+            let pem = read_pem(path)?;
+            let certs = Certificate::from_pem_bundle(&pem)
+                .map_err(|e| format!("Invalid CA file {}: {}", path, e))?;
+            for cert in certs {
+                builder = builder.add_root_certificate(cert);
+            }
+        }
+
+        match (&self.cert_file, &self.key_file) {
+            (Some(cert), Some(key)) => {
+                let mut pem = read_pem(cert)?;
+                pem.push(b'\n');
+                pem.extend(read_pem(key)?);
+                let identity = Identity::from_pem(&pem)
+                    .map_err(|e| format!("Invalid client certificate {}: {}", cert, e))?;
+                builder = builder.identity(identity);
+            }
+            (None, None) => {}
+            _ => return Err("Client certificate and key must be set together".to_string()),
+        }
+
+        if !self.strict_ssl {
+            builder = builder.danger_accept_invalid_certs(true);
+        }
+
+        builder
+            .build()
+            .map_err(|e| format!("Failed to create HTTP client: {}", e))
+    }
+}
+
+impl Default for NetworkOptions {
+    fn default() -> Self {
+        Self {
+            proxy: None,
+            https_proxy: None,
+            no_proxy: None,
+            ca_file: None,
+            strict_ssl: true,
+            cert_file: None,
+            key_file: None,
+        }
+    }
+}
+
+fn read_pem(path: &str) -> Result<Vec<u8>, String> {
+    std::fs::read(path).map_err(|e| format!("Failed to read {}: {}", path, e))
+}
+
+/// Consecutive failures after which a registry is only tried as a last resort
+const UNHEALTHY_AFTER: u32 = 3;
+
+/// Failover policy used when none is configured
+pub const DEFAULT_FAILOVER_ON: [&str; 3] = ["5xx", "timeout", "404"];
+
+/// One registry of the fallback chain and its health over the current run
+struct Endpoint {
+    url: String,
+    failures: AtomicU32,
 }
 
 pub struct RegistryClient {
     client: Client,
-    registry: String,
-    semaphore: Arc<Semaphore>,
+    endpoints: Vec<Endpoint>,
+    failover_on: Vec<String>,
+    limiter: AdaptiveLimiter,
     retries: u32,
+    progress: Arc<Progress>,
+    cancel: CancelToken,
+    fixtures: Option<FixtureStore>,
+    full_metadata: bool,
 }
 
 impl RegistryClient {
-    pub fn new(registry: &str, concurrency: u32, timeout_ms: u32, retries: u32) -> Self {
-        let client = Client::builder()
-            .timeout(Duration::from_millis(timeout_ms as u64))
-            .pool_max_idle_per_host(concurrency as usize)
-            .build()
-            .expect("Failed to create HTTP client");
+    /// Create a client for an ordered chain of registries; later entries are
+    /// only used when an earlier one fails in a way listed in the failover policy
+    pub fn new(
+        registries: &[String],
+        concurrency: u32,
+        timeout_ms: u32,
+        retries: u32,
+        network: &NetworkOptions,
+    ) -> Result<Self, String> {
+        if registries.is_empty() {
+            return Err("At least one registry is required".to_string());
+        }
+        let client = network.build_client(concurrency, timeout_ms)?;
 
-        let registry_url = registry.trim_end_matches('/').to_string();
+        let endpoints = registries
+            .iter()
+            .map(|url| Endpoint {
+                url: url.trim_end_matches('/').to_string(),
+                failures: AtomicU32::new(0),
+            })
+            .collect();
 
-        Self {
+        Ok(Self {
             client,
-            registry: registry_url,
-            semaphore: Arc::new(Semaphore::new(concurrency as usize)),
+            endpoints,
+            failover_on: DEFAULT_FAILOVER_ON.iter().map(|s| s.to_string()).collect(),
+            limiter: AdaptiveLimiter::new(concurrency as usize),
             retries,
-        }
+            progress: Arc::new(Progress::default()),
+            cancel: CancelToken::new(),
+            fixtures: None,
+            full_metadata: false,
+        })
+    }
+
+    pub fn with_progress(mut self, progress: Arc<Progress>) -> Self {
+        self.progress = progress;
+        self
+    }
+
+    pub fn with_cancel(mut self, cancel: CancelToken) -> Self {
+        self.cancel = cancel;
+        self
+    }
+
+    /// Record responses to, or replay them from, a fixture directory
+    pub fn with_fixtures(mut self, fixtures: FixtureStore) -> Self {
+        self.fixtures = Some(fixtures);
+        self
+    }
+
+    /// Request full packuments and collect per-version details, rather than
+    /// the much smaller abbreviated form that only lists versions
+    pub fn with_full_metadata(mut self, full_metadata: bool) -> Self {
+        self.full_metadata = full_metadata;
+        self
+    }
+
+    /// Set which failures move on to the next registry: any of "5xx",
+    /// "timeout" (including connection errors), "404" and "429"
+    pub fn with_failover_on(mut self, failover_on: Vec<String>) -> Self {
+        self.failover_on = failover_on;
+        self
     }
 
     pub async fn fetch_package(&self, name: &str) -> Result<RegistryVersionInfo, String> {
         let _permit = self
-            .semaphore
-            .acquire()
-            .await
+            .cancel
+            .run(self.limiter.acquire())
+            .await?
             .map_err(|e| format!("Semaphore error: {}", e))?;
 
+        self.progress.emit("start", name, 0, 0);
+
+        let mut attempt = 0;
+        let mut errors = Vec::new();
+        for endpoint in self.endpoints_by_health() {
+            let error = match self.fetch_from(endpoint, name, &mut attempt).await {
+                Ok((info, bytes)) => {
+                    endpoint.failures.store(0, Ordering::Relaxed);
+                    self.progress.complete("success", name, attempt, bytes);
+                    return Ok(info);
+                }
+                Err(e) => e,
+            };
+
+            if error.kind == FailureKind::Cancelled {
+                return Err(error.message);
+            }
+            if error.kind != FailureKind::NotFound {
+                endpoint.failures.fetch_add(1, Ordering::Relaxed);
+            }
+
+            let failover = error.triggers_failover(&self.failover_on);
+            if self.endpoints.len() > 1 {
+                errors.push(format!("{}: {}", endpoint.url, error.message));
+            } else {
+                errors.push(error.message);
+            }
+            if !failover {
+                break;
+            }
+        }
+
+        self.progress.complete("failure", name, attempt, 0);
+        Err(format!(
+            "Failed to fetch {} after {} retries: {}",
+            name,
+            attempt,
+            errors.join("; ")
+        ))
+    }
+
+    /// Healthy registries in configured order, then those that keep failing
+    fn endpoints_by_health(&self) -> Vec<&Endpoint> {
+        let (healthy, unhealthy): (Vec<&Endpoint>, Vec<&Endpoint>) = self
+            .endpoints
+            .iter()
+            .partition(|e| e.failures.load(Ordering::Relaxed) < UNHEALTHY_AFTER);
+        healthy.into_iter().chain(unhealthy).collect()
+    }
+
+    /// Fetch from one registry, retrying errors that may go away
+    async fn fetch_from(
+        &self,
+        endpoint: &Endpoint,
+        name: &str,
+        attempt: &mut u32,
+    ) -> Result<(RegistryVersionInfo, u32), FetchError> {
         let url = if name.starts_with('@') {
             // Scoped package: @scope/name -> @scope%2fname
             let encoded = name.replacen('/', "%2f", 1);
-            format!("{}/{}", self.registry, encoded)
+            format!("{}/{}", endpoint.url, encoded)
         } else {
-            format!("{}/{}", self.registry, name)
+            format!("{}/{}", endpoint.url, name)
         };
 
-        let mut last_error = String::new();
-        for attempt in 0..=self.retries {
-            if attempt > 0 {
-                let delay = Duration::from_millis(100 * 2u64.pow(attempt - 1));
-                tokio::time::sleep(delay).await;
-            }
-
-            match self.do_fetch(&url).await {
-                Ok(info) => return Ok(info),
-                Err(e) => {
-                    last_error = e;
+        let mut retries = 0;
+        loop {
+            let error = match self.cancel.run(self.do_fetch(&url)).await {
+                Ok(Ok((mut info, bytes))) => {
+                    self.limiter.on_success();
+                    info.registry = endpoint.url.clone();
+                    return Ok((info, bytes));
                 }
+                Ok(Err(e)) => e,
+                Err(cancelled) => return Err(FetchError::new(FailureKind::Cancelled, cancelled)),
+            };
+
+            if error.kind == FailureKind::RateLimited {
+                self.limiter.on_rate_limited();
+            }
+            if !error.is_retryable() || retries >= self.retries {
+                return Err(error);
             }
+
+            retries += 1;
+            *attempt += 1;
+            let delay = backoff_delay(retries).max(error.retry_after.unwrap_or_default());
+            self.cancel
+                .run(tokio::time::sleep(delay))
+                .await
+                .map_err(|e| FetchError::new(FailureKind::Cancelled, e))?;
+            self.progress.emit("retry", name, *attempt, 0);
         }
+    }
 
-        Err(format!(
-            "Failed to fetch {} after {} retries: {}",
+    /// Fetch and parse a packument, returning it with the response body size
+    async fn do_fetch(&self, url: &str) -> Result<(RegistryVersionInfo, u32), FetchError> {
+        let (status, headers, body) = self.send(url, None).await?;
+
+        if !status.is_success() {
+            return Err(FetchError::from_status(status, &headers));
+        }
+
+        let packument: Packument = serde_json::from_slice(&body)
+            .map_err(|e| FetchError::new(FailureKind::Fatal, format!("JSON parse error: {}", e)))?;
+
+        let name = packument.name.unwrap_or_default();
+        let manifests = packument.versions.unwrap_or_default();
+        let versions: Vec<String> = manifests.keys().cloned().collect();
+
+        let details = if self.full_metadata {
+            let time = packument.time.unwrap_or_default();
+            manifests
+                .iter()
+                .map(|(version, manifest)| {
+                    (
+                        version.clone(),
+                        version_details(manifest, time.get(version)),
+                    )
+                })
+                .collect()
+        } else {
+            HashMap::new()
+        };
+
+        let info = RegistryVersionInfo {
             name,
-            self.retries,
-            last_error
-        ))
+            versions,
+            registry: String::new(),
+            details,
+        };
+        Ok((info, body.len() as u32))
     }
 
-    async fn do_fetch(&self, url: &str) -> Result<RegistryVersionInfo, String> {
-        let response = self
-            .client
-            .get(url)
-            .header("Accept", "application/vnd.npm.install-v1+json")
-            .send()
-            .await
-            .map_err(|e| format!("HTTP error: {}", e))?;
+    /// Perform a GET (or a POST of `body`), or serve/record it through the
+    /// fixture store when one is set
+    async fn send(
+        &self,
+        url: &str,
+        body: Option<&serde_json::Value>,
+    ) -> Result<(StatusCode, HeaderMap, Vec<u8>), FetchError> {
+        let (accept, fixture_key) = match body {
+            Some(body) => (
+                "application/json",
+                format!("{}#{:016x}", url, fingerprint(body.to_string().as_bytes())),
+            ),
+            None if self.full_metadata => ("application/json", format!("{}#full", url)),
+            None => ("application/vnd.npm.install-v1+json", url.to_string()),
+        };
 
-        if !response.status().is_success() {
-            return Err(format!("HTTP {}", response.status()));
+        if let Some(fixtures) = &self.fixtures {
+            if fixtures.mode() == FixtureMode::Replay {
+                let recorded = fixtures
+                    .load(&fixture_key)
+                    .map_err(|e| FetchError::new(FailureKind::Fatal, e))?;
+                return Ok(replayed(recorded));
+            }
         }
 
-        let packument: AbbreviatedPackument = response
-            .json()
+        let request = match body {
+            Some(body) => self.client.post(url).json(body),
+            None => self.client.get(url),
+        };
+        let response = request
+            .header("Accept", accept)
+            .send()
             .await
-            .map_err(|e| format!("JSON parse error: {}", e))?;
+            .map_err(transport_error)?;
+        let status = response.status();
+        let headers = response.headers().clone();
+        let body = response.bytes().await.map_err(transport_error)?.to_vec();
 
-        let name = packument.name.unwrap_or_default();
-        let versions: Vec<String> = packument
-            .versions
-            .map(|v| v.keys().cloned().collect())
-            .unwrap_or_default();
+        if let Some(fixtures) = &self.fixtures {
+            let recorded = RecordedResponse {
+                url: url.to_string(),
+                status: status.as_u16(),
+                headers: headers
+                    .iter()
+                    .filter_map(|(k, v)| Some((k.to_string(), v.to_str().ok()?.to_string())))
+                    .collect(),
+                body: String::from_utf8_lossy(&body).to_string(),
+            };
+            fixtures
+                .save(&fixture_key, &recorded)
+                .map_err(|e| FetchError::new(FailureKind::Fatal, e))?;
+        }
 
-        Ok(RegistryVersionInfo { name, versions })
+        Ok((status, headers, body))
     }
 
-    pub async fn fetch_many(
+    /// Look up advisories affecting the given versions of each package through
+    /// the primary registry's bulk advisory endpoint
+    pub async fn fetch_advisories(
         &self,
-        names: &[String],
-    ) -> Vec<Result<RegistryVersionInfo, String>> {
-        let futures: Vec<_> = names
+        versions: &BTreeMap<String, Vec<String>>,
+    ) -> Result<AdvisoryIndex, String> {
+        let endpoint = &self.endpoints[0];
+        let url = format!("{}/-/npm/v1/security/advisories/bulk", endpoint.url);
+        let body = serde_json::to_value(versions).map_err(|e| format!("Serialize: {}", e))?;
+
+        let mut retries = 0;
+        loop {
+            let error = match self.cancel.run(self.send(&url, Some(&body))).await? {
+                Ok((status, _, body)) if status.is_success() => {
+                    return serde_json::from_slice(&body)
+                        .map_err(|e| format!("Invalid advisory response: {}", e));
+                }
+                Ok((status, headers, _)) => FetchError::from_status(status, &headers),
+                Err(e) => e,
+            };
+
+            if !error.is_retryable() || retries >= self.retries {
+                return Err(format!("Advisory lookup failed: {}", error.message));
+            }
+            retries += 1;
+            let delay = backoff_delay(retries).max(error.retry_after.unwrap_or_default());
+            self.cancel.run(tokio::time::sleep(delay)).await?;
+        }
+    }
+
+    /// Fetch several packages concurrently, returning results in input order
+    pub async fn fetch_many(&self, names: &[String]) -> Vec<Result<RegistryVersionInfo, String>> {
+        join_all(names.iter().map(|name| self.fetch_package(name))).await
+    }
+
+    /// Fetch several packages concurrently, yielding `(index, result)` pairs in
+    /// completion order so callers can act on each package as soon as it lands
+    pub fn fetch_unordered<'a>(
+        &'a self,
+        names: &'a [String],
+    ) -> impl Stream<Item = (usize, Result<RegistryVersionInfo, String>)> + 'a {
+        names
             .iter()
-            .map(|name| self.fetch_package(name))
+            .enumerate()
+            .map(|(i, name)| async move { (i, self.fetch_package(name).await) })
+            .collect::<FuturesUnordered<_>>()
+    }
+}
+
+/// Pull the details we report out of one version's manifest, accepting the
+/// legacy shapes still found in old packuments
+fn version_details(
+    manifest: &serde_json::Value,
+    published: Option<&serde_json::Value>,
+) -> VersionDetails {
+    let string = |value: &serde_json::Value| value.as_str().map(|s| s.to_string());
+    let string_map = |value: &serde_json::Value| {
+        value.as_object().map(|fields| {
+            fields
+                .iter()
+                .filter_map(|(k, v)| Some((k.clone(), v.as_str()?.to_string())))
+                .collect()
+        })
+    };
+
+    let repository = match &manifest["repository"] {
+        serde_json::Value::Object(fields) => fields.get("url").and_then(string),
+        other => string(other),
+    };
+    let license = match &manifest["license"] {
+        serde_json::Value::Object(fields) => fields.get("type").and_then(string),
+        serde_json::Value::String(license) => Some(license.clone()),
+        _ => manifest["licenses"].as_array().and_then(|licenses| {
+            let types: Vec<String> = licenses
+                .iter()
+                .filter_map(|l| string(l).or_else(|| string(&l["type"])))
+                .collect();
+            Some(types.join(" OR ")).filter(|t| !t.is_empty())
+        }),
+    };
+
+    VersionDetails {
+        published: published.and_then(string),
+        repository,
+        homepage: string(&manifest["homepage"]),
+        license,
+        deprecated: string(&manifest["deprecated"]),
+        engines: string_map(&manifest["engines"]),
+        peer_dependencies: string_map(&manifest["peerDependencies"]),
+        unpacked_size: manifest["dist"]["unpackedSize"]
+            .as_u64()
+            .map(|size| size.min(u32::MAX as u64) as u32),
+        integrity: string(&manifest["dist"]["integrity"]),
+    }
+}
+
+fn replayed(recorded: RecordedResponse) -> (StatusCode, HeaderMap, Vec<u8>) {
+    let status = StatusCode::from_u16(recorded.status).unwrap_or(StatusCode::OK);
+    let headers = recorded
+        .headers
+        .iter()
+        .filter_map(|(k, v)| {
+            Some((
+                HeaderName::from_bytes(k.as_bytes()).ok()?,
+                HeaderValue::from_str(v).ok()?,
+            ))
+        })
+        .collect();
+    (status, headers, recorded.body.into_bytes())
+}
+
+/// FNV-1a hash, stable across runs and platforms, for naming POST fixtures
+fn fingerprint(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+fn transport_error(e: reqwest::Error) -> FetchError {
+    FetchError::new(FailureKind::Transport, format!("HTTP error: {}", e))
+}
+
+/// Exponential backoff (100ms·2ⁿ⁻¹) with equal jitter, so clients that failed
+/// together do not all retry at the same instant
+fn backoff_delay(attempt: u32) -> Duration {
+    let base = 100 * 2u64.pow(attempt.saturating_sub(1).min(10));
+    let half = base / 2;
+    let jitter = RandomState::new().build_hasher().finish() % (half + 1);
+    Duration::from_millis(half + jitter)
+}
+
+/// Parse a `Retry-After` header given either as delay-seconds or an HTTP-date
+fn parse_retry_after(headers: &HeaderMap, now: SystemTime) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+
+    let delay = if let Ok(seconds) = value.parse::<u64>() {
+        Duration::from_secs(seconds)
+    } else {
+        let at = parse_http_date(value)?;
+        let now = now.duration_since(UNIX_EPOCH).ok()?.as_secs();
+        Duration::from_secs(at.saturating_sub(now))
+    };
+
+    Some(delay.min(MAX_RETRY_AFTER))
+}
+
+/// Parse an IMF-fixdate such as `Wed, 21 Oct 2015 07:28:00 GMT` into seconds
+/// since the Unix epoch
+fn parse_http_date(value: &str) -> Option<u64> {
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+
+    let parts: Vec<&str> = value.split_whitespace().collect();
+    if parts.len() != 6 || parts[5] != "GMT" {
+        return None;
+    }
+    let day: u64 = parts[1].parse().ok()?;
+    let month = MONTHS.iter().position(|m| *m == parts[2])? as u64 + 1;
+    let year: u64 = parts[3].parse().ok()?;
+    let time: Vec<u64> = parts[4]
+        .split(':')
+        .map(|p| p.parse().ok())
+        .collect::<Option<_>>()?;
+    if time.len() != 3 || year < 1970 {
+        return None;
+    }
+
+    // Days from civil date (Howard Hinnant's algorithm)
+    let (y, m) = if month <= 2 {
+        (year - 1, month + 9)
+    } else {
+        (year, month - 3)
+    };
+    let era = y / 400;
+    let yoe = y - era * 400;
+    let doy = (153 * m + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146097 + doe - 719468;
+
+    Some(days * 86400 + time[0] * 3600 + time[1] * 60 + time[2])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_registry::MockRegistry;
+    use futures::StreamExt;
+    use std::sync::Mutex;
+    use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+    use tokio::net::TcpListener;
+    use tokio_rustls::rustls::pki_types::pem::PemObject;
+    use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer};
+    use tokio_rustls::rustls::server::WebPkiClientVerifier;
+    use tokio_rustls::rustls::{RootCertStore, ServerConfig};
+    use tokio_rustls::TlsAcceptor;
+
+    const PACKUMENT: &str = r#"{"name":"lodash","versions":{"4.17.21":{}}}"#;
+
+    fn fixture(name: &str) -> String {
+        format!(
+            "{}/__test__/fixtures/tls/{}",
+            env!("CARGO_MANIFEST_DIR"),
+            name
+        )
+    }
+
+    /// Read one request head and answer it with a packument, returning the head
+    async fn answer<S: AsyncRead + AsyncWrite + Unpin>(stream: S) -> String {
+        respond(stream, "200 OK", PACKUMENT).await
+    }
+
+    async fn respond<S: AsyncRead + AsyncWrite + Unpin>(
+        mut stream: S,
+        status: &str,
+        body: &str,
+    ) -> String {
+        let mut head = Vec::new();
+        let mut buf = [0u8; 1024];
+        while !head.windows(4).any(|w| w == b"\r\n\r\n") {
+            let n = stream.read(&mut buf).await.unwrap();
+            if n == 0 {
+                break;
+            }
+            head.extend_from_slice(&buf[..n]);
+        }
+        let response = format!(
+            "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            status,
+            body.len(),
+            body
+        );
+        stream.write_all(response.as_bytes()).await.unwrap();
+        stream.shutdown().await.ok();
+        String::from_utf8_lossy(&head).to_string()
+    }
+
+    /// Serve plain HTTP, recording each request head
+    async fn spawn_http() -> (String, Arc<Mutex<Vec<String>>>) {
+        spawn_http_status("200 OK").await
+    }
+
+    async fn spawn_http_status(status: &'static str) -> (String, Arc<Mutex<Vec<String>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = format!("http://{}", listener.local_addr().unwrap());
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let log = seen.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let body = if status.starts_with("200") {
+                    PACKUMENT
+                } else {
+                    "{}"
+                };
+                let head = respond(stream, status, body).await;
+                log.lock().unwrap().push(head);
+            }
+        });
+        (addr, seen)
+    }
+
+    /// Serve HTTPS with the fixture certificate, optionally demanding a client
+    /// certificate signed by the fixture CA
+    async fn spawn_https(require_client_cert: bool) -> String {
+        let certs: Vec<CertificateDer<'static>> =
+            CertificateDer::pem_file_iter(fixture("server.pem"))
+                .unwrap()
+                .map(|c| c.unwrap())
+                .collect();
+        let key = PrivateKeyDer::from_pem_file(fixture("server-key.pem")).unwrap();
+        let builder = ServerConfig::builder();
+        let builder = if require_client_cert {
+            let mut roots = RootCertStore::empty();
+            for ca in CertificateDer::pem_file_iter(fixture("ca.pem")).unwrap() {
+                roots.add(ca.unwrap()).unwrap();
+            }
+            builder.with_client_cert_verifier(
+                WebPkiClientVerifier::builder(Arc::new(roots))
+                    .build()
+                    .unwrap(),
+            )
+        } else {
+            builder.with_no_client_auth()
+        };
+        let config = builder.with_single_cert(certs, key).unwrap();
+        let acceptor = TlsAcceptor::from(Arc::new(config));
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = format!(
+            "https://localhost:{}",
+            listener.local_addr().unwrap().port()
+        );
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let acceptor = acceptor.clone();
+                tokio::spawn(async move {
+                    if let Ok(tls) = acceptor.accept(stream).await {
+                        answer(tls).await;
+                    }
+                });
+            }
+        });
+        addr
+    }
+
+    fn client(registry: &str, network: &NetworkOptions) -> RegistryClient {
+        RegistryClient::new(&[registry.to_string()], 4, 5000, 0, network).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_fetches_abbreviated_packuments_from_mock_registry() {
+        let server = MockRegistry::fixtures().start().await;
+        let client = client(&server.url, &NetworkOptions::default());
+
+        let mut info = client.fetch_package("@types/node").await.unwrap();
+        info.versions.sort();
+        assert_eq!(info.versions, vec!["18.0.0", "18.19.0", "20.11.0"]);
+        assert_eq!(info.registry, server.url);
+
+        let requests = server.requests();
+        assert_eq!(requests[0].path, "/@types%2fnode");
+        assert_eq!(
+            requests[0].header("accept"),
+            Some("application/vnd.npm.install-v1+json")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_full_metadata_collects_version_details() {
+        let server = MockRegistry::fixtures().start().await;
+        let client = client(&server.url, &NetworkOptions::default()).with_full_metadata(true);
+
+        let info = client.fetch_package("lodash").await.unwrap();
+        let latest = &info.details["4.17.21"];
+        assert_eq!(
+            latest.published.as_deref(),
+            Some("2021-02-20T15:42:16.891Z")
+        );
+        assert_eq!(latest.license.as_deref(), Some("MIT"));
+        assert_eq!(
+            latest.repository.as_deref(),
+            Some("git+https://github.com/lodash/lodash.git")
+        );
+        assert_eq!(latest.unpacked_size, Some(12000));
+        assert_eq!(
+            info.details["4.0.0"].deprecated.as_deref(),
+            Some("Upgrade to 4.17.21")
+        );
+        assert_eq!(
+            server.requests()[0].header("accept"),
+            Some("application/json")
+        );
+    }
+
+    #[test]
+    fn test_version_details_legacy_shapes() {
+        let manifest = serde_json::json!({
+            "repository": "github:user/repo",
+            "licenses": [{ "type": "MIT" }, { "type": "Apache-2.0" }],
+            "deprecated": false,
+            "engines": ["node >= 0.8"],
+            "peerDependencies": { "react": "^18.0.0" },
+        });
+        let details = version_details(&manifest, None);
+        assert_eq!(details.repository.as_deref(), Some("github:user/repo"));
+        assert_eq!(details.license.as_deref(), Some("MIT OR Apache-2.0"));
+        assert_eq!(details.deprecated, None);
+        assert_eq!(details.engines, None);
+        assert_eq!(
+            details.peer_dependencies.unwrap()["react"],
+            "^18.0.0".to_string()
+        );
+
+        let object_license = serde_json::json!({ "license": { "type": "ISC" } });
+        assert_eq!(
+            version_details(&object_license, None).license.as_deref(),
+            Some("ISC")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_retries_after_rate_limit() {
+        let server = MockRegistry::fixtures()
+            .with_rate_limit(2, Some(0))
+            .start()
+            .await;
+        let client = RegistryClient::new(
+            std::slice::from_ref(&server.url),
+            4,
+            5000,
+            2,
+            &NetworkOptions::default(),
+        )
+        .unwrap();
+
+        let info = client.fetch_package("lodash").await.unwrap();
+        assert_eq!(info.name, "lodash");
+        assert_eq!(server.requests().len(), 3);
+        // Both 429s fall in one window, so the limit is halved once
+        assert_eq!(client.limiter.limit(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit_on_last_attempt_lowers_limit() {
+        let server = MockRegistry::fixtures()
+            .with_rate_limit(1, Some(0))
+            .start()
+            .await;
+        let client = RegistryClient::new(
+            std::slice::from_ref(&server.url),
+            4,
+            5000,
+            0,
+            &NetworkOptions::default(),
+        )
+        .unwrap();
+
+        assert!(client.fetch_package("lodash").await.is_err());
+        assert_eq!(client.limiter.limit(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_unauthorized_is_neither_retried_nor_failed_over() {
+        let primary = MockRegistry::fixtures()
+            .with_auth_token("s3cret")
+            .start()
+            .await;
+        let fallback = MockRegistry::fixtures().start().await;
+
+        let client = RegistryClient::new(
+            &[primary.url.clone(), fallback.url.clone()],
+            4,
+            5000,
+            3,
+            &NetworkOptions::default(),
+        )
+        .unwrap();
+        let err = client.fetch_package("lodash").await.unwrap_err();
+        assert!(err.contains("401"), "{}", err);
+        assert_eq!(primary.requests().len(), 1);
+        assert!(fallback.requests().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_concurrency_limit_holds_end_to_end() {
+        let server = MockRegistry::fixtures()
+            .with_latency(Duration::from_millis(50))
+            .start()
+            .await;
+        let client = RegistryClient::new(
+            std::slice::from_ref(&server.url),
+            2,
+            5000,
+            0,
+            &NetworkOptions::default(),
+        )
+        .unwrap();
+
+        let names: Vec<String> = ["lodash", "chalk", "@types/node", "lodash", "chalk"]
+            .iter()
+            .map(|s| s.to_string())
             .collect();
+        let results: Vec<_> = client.fetch_unordered(&names).collect().await;
+        assert!(results.iter().all(|(_, r)| r.is_ok()));
+        assert_eq!(server.requests().len(), 5);
+        assert_eq!(server.peak_in_flight(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_record_then_replay() {
+        let (registry, seen) = spawn_http().await;
+        let dir = std::env::temp_dir().join(format!("turbo-ncu-record-{}", std::process::id()));
+        let dir = dir.to_string_lossy().to_string();
 
-        futures::future::join_all(futures).await
+        let recorder = client(&registry, &NetworkOptions::default())
+            .with_fixtures(FixtureStore::new(&dir, FixtureMode::Record));
+        let recorded = recorder.fetch_package("lodash").await.unwrap();
+
+        let replayer = client(&registry, &NetworkOptions::default())
+            .with_fixtures(FixtureStore::new(&dir, FixtureMode::Replay));
+        let replayed = replayer.fetch_package("lodash").await.unwrap();
+        assert_eq!(replayed.versions, recorded.versions);
+        assert!(replayer.fetch_package("chalk").await.is_err());
+
+        // Replay never reached the server
+        assert_eq!(seen.lock().unwrap().len(), 1);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_fails_over_to_next_registry() {
+        let (mirror, mirror_seen) = spawn_http_status("404 Not Found").await;
+        let (upstream, _) = spawn_http().await;
+
+        let client = RegistryClient::new(
+            &[mirror.clone(), upstream.clone()],
+            4,
+            5000,
+            0,
+            &NetworkOptions::default(),
+        )
+        .unwrap();
+        let info = client.fetch_package("lodash").await.unwrap();
+        assert_eq!(info.registry, upstream);
+        assert_eq!(mirror_seen.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_failover_respects_policy() {
+        let (mirror, _) = spawn_http_status("404 Not Found").await;
+        let (upstream, upstream_seen) = spawn_http().await;
+
+        let client =
+            RegistryClient::new(&[mirror, upstream], 4, 5000, 0, &NetworkOptions::default())
+                .unwrap()
+                .with_failover_on(vec!["5xx".to_string()]);
+        assert!(client.fetch_package("lodash").await.is_err());
+        assert!(upstream_seen.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_unhealthy_registry_is_tried_last() {
+        let (mirror, mirror_seen) = spawn_http_status("503 Service Unavailable").await;
+        let (upstream, _) = spawn_http().await;
+
+        let client =
+            RegistryClient::new(&[mirror, upstream], 1, 5000, 0, &NetworkOptions::default())
+                .unwrap();
+        for _ in 0..UNHEALTHY_AFTER + 2 {
+            client.fetch_package("lodash").await.unwrap();
+        }
+        assert_eq!(mirror_seen.lock().unwrap().len(), UNHEALTHY_AFTER as usize);
+    }
+
+    #[test]
+    fn test_network_defaults_from_npmrc() {
+        let home = std::env::temp_dir().join(format!("turbo-ncu-npmrc-{}", std::process::id()));
+        std::fs::create_dir_all(&home).unwrap();
+        std::fs::write(
+            home.join(".npmrc"),
+            "https-proxy=http://proxy.corp:3128\nnoproxy=registry.corp\ncafile=~/corp-ca.pem\nstrict-ssl=false\n",
+        )
+        .unwrap();
+        let env = Environment::new(
+            HashMap::from([
+                ("HTTP_PROXY".to_string(), "http://env:8080".to_string()),
+                ("HTTPS_PROXY".to_string(), "http://env:8443".to_string()),
+            ]),
+            Some(home.clone()),
+        );
+
+        let network = NetworkOptions::from_options(&CheckOptions::default(), &env);
+        assert_eq!(network.proxy.as_deref(), Some("http://env:8080"));
+        assert_eq!(
+            network.https_proxy.as_deref(),
+            Some("http://proxy.corp:3128")
+        );
+        assert_eq!(network.no_proxy.as_deref(), Some("registry.corp"));
+        assert_eq!(
+            network.ca_file,
+            Some(home.join("corp-ca.pem").to_string_lossy().to_string())
+        );
+        assert!(!network.strict_ssl);
+
+        // Options still win over .npmrc
+        let options = CheckOptions {
+            https_proxy: Some("http://flag:3128".to_string()),
+            strict_ssl: Some(true),
+            ..Default::default()
+        };
+        let network = NetworkOptions::from_options(&options, &env);
+        assert_eq!(network.https_proxy.as_deref(), Some("http://flag:3128"));
+        assert!(network.strict_ssl);
+
+        let _ = std::fs::remove_dir_all(&home);
+    }
+
+    #[tokio::test]
+    async fn test_requests_go_through_proxy() {
+        let (proxy, seen) = spawn_http().await;
+        let network = NetworkOptions {
+            proxy: Some(proxy),
+            ..Default::default()
+        };
+
+        // The registry host does not resolve; only the proxy can answer
+        let info = client("http://registry.invalid", &network)
+            .fetch_package("lodash")
+            .await
+            .unwrap();
+        assert_eq!(info.versions, vec!["4.17.21"]);
+
+        let seen = seen.lock().unwrap();
+        assert!(seen[0].starts_with("GET http://registry.invalid/lodash HTTP/1.1"));
+    }
+
+    #[tokio::test]
+    async fn test_no_proxy_bypasses_proxy() {
+        let (registry, direct) = spawn_http().await;
+        let (proxy, proxied) = spawn_http().await;
+        let network = NetworkOptions {
+            proxy: Some(proxy),
+            no_proxy: Some("127.0.0.1".to_string()),
+            ..Default::default()
+        };
+
+        client(&registry, &network)
+            .fetch_package("lodash")
+            .await
+            .unwrap();
+        assert_eq!(direct.lock().unwrap().len(), 1);
+        assert!(proxied.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_custom_ca_file() {
+        let registry = spawn_https(false).await;
+
+        let untrusted = client(&registry, &NetworkOptions::default())
+            .fetch_package("lodash")
+            .await;
+        assert!(untrusted.is_err());
+
+        let network = NetworkOptions {
+            ca_file: Some(fixture("ca.pem")),
+            ..Default::default()
+        };
+        let info = client(&registry, &network)
+            .fetch_package("lodash")
+            .await
+            .unwrap();
+        assert_eq!(info.name, "lodash");
+    }
+
+    #[tokio::test]
+    async fn test_strict_ssl_disabled() {
+        let registry = spawn_https(false).await;
+        let network = NetworkOptions {
+            strict_ssl: false,
+            ..Default::default()
+        };
+        assert!(client(&registry, &network)
+            .fetch_package("lodash")
+            .await
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_client_certificate() {
+        let registry = spawn_https(true).await;
+        let trusted = NetworkOptions {
+            ca_file: Some(fixture("ca.pem")),
+            ..Default::default()
+        };
+        assert!(client(&registry, &trusted)
+            .fetch_package("lodash")
+            .await
+            .is_err());
+
+        let network = NetworkOptions {
+            cert_file: Some(fixture("client.pem")),
+            key_file: Some(fixture("client-key.pem")),
+            ..trusted
+        };
+        assert!(client(&registry, &network)
+            .fetch_package("lodash")
+            .await
+            .is_ok());
+    }
+
+    #[test]
+    fn test_invalid_network_options() {
+        let missing_ca = NetworkOptions {
+            ca_file: Some(fixture("missing.pem")),
+            ..Default::default()
+        };
+        assert!(
+            RegistryClient::new(&["http://localhost".to_string()], 1, 1000, 0, &missing_ca)
+                .is_err()
+        );
+
+        let cert_without_key = NetworkOptions {
+            cert_file: Some(fixture("client.pem")),
+            ..Default::default()
+        };
+        assert!(RegistryClient::new(
+            &["http://localhost".to_string()],
+            1,
+            1000,
+            0,
+            &cert_without_key
+        )
+        .is_err());
+    }
+
+    fn retry_after(value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(RETRY_AFTER, HeaderValue::from_str(value).unwrap());
+        headers
+    }
+
+    #[test]
+    fn test_classify_status() {
+        let empty = HeaderMap::new();
+        let not_found = FetchError::from_status(StatusCode::NOT_FOUND, &empty);
+        assert_eq!(not_found.kind, FailureKind::NotFound);
+        assert!(!not_found.is_retryable());
+
+        let unauthorized = FetchError::from_status(StatusCode::UNAUTHORIZED, &empty);
+        assert_eq!(unauthorized.kind, FailureKind::Fatal);
+        assert!(!unauthorized.is_retryable());
+
+        let bad_gateway = FetchError::from_status(StatusCode::BAD_GATEWAY, &empty);
+        assert_eq!(bad_gateway.kind, FailureKind::ServerError);
+        assert!(bad_gateway.is_retryable());
+
+        assert_eq!(
+            FetchError::from_status(StatusCode::TOO_MANY_REQUESTS, &retry_after("3")),
+            FetchError {
+                kind: FailureKind::RateLimited,
+                message: "HTTP 429 Too Many Requests".to_string(),
+                retry_after: Some(Duration::from_secs(3)),
+            }
+        );
+    }
+
+    #[test]
+    fn test_failover_policy() {
+        let policy: Vec<String> = DEFAULT_FAILOVER_ON.iter().map(|s| s.to_string()).collect();
+        let empty = HeaderMap::new();
+        assert!(FetchError::from_status(StatusCode::BAD_GATEWAY, &empty).triggers_failover(&policy));
+        assert!(FetchError::from_status(StatusCode::NOT_FOUND, &empty).triggers_failover(&policy));
+        assert!(!FetchError::from_status(StatusCode::FORBIDDEN, &empty).triggers_failover(&policy));
+        assert!(
+            !FetchError::from_status(StatusCode::TOO_MANY_REQUESTS, &empty)
+                .triggers_failover(&policy)
+        );
+        assert!(FetchError::new(FailureKind::Transport, String::new()).triggers_failover(&policy));
+    }
+
+    #[test]
+    fn test_parse_retry_after_http_date() {
+        // 2015-10-21T07:28:00Z
+        let now = UNIX_EPOCH + Duration::from_secs(1_445_412_470);
+        let headers = retry_after("Wed, 21 Oct 2015 07:28:00 GMT");
+        assert_eq!(
+            parse_retry_after(&headers, now),
+            Some(Duration::from_secs(10))
+        );
+
+        // Dates in the past mean "retry now"
+        let later = now + Duration::from_secs(3600);
+        assert_eq!(parse_retry_after(&headers, later), Some(Duration::ZERO));
+    }
+
+    #[test]
+    fn test_parse_retry_after_is_capped() {
+        let headers = retry_after("86400");
+        assert_eq!(
+            parse_retry_after(&headers, SystemTime::now()),
+            Some(MAX_RETRY_AFTER)
+        );
+        assert_eq!(
+            parse_retry_after(&retry_after("soon"), SystemTime::now()),
+            None
+        );
+    }
+
+    #[test]
+    fn test_backoff_delay_stays_within_jitter_window() {
+        for attempt in 1..=4 {
+            let base = 100 * 2u64.pow(attempt - 1);
+            let delay = backoff_delay(attempt).as_millis() as u64;
+            assert!(
+                delay >= base / 2 && delay <= base,
+                "{} not in window",
+                delay
+            );
+        }
     }
 }
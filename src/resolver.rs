@@ -1,125 +1,1199 @@
-use std::sync::Mutex;
+use futures::StreamExt;
+use node_semver::{Range, Version};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
 use std::time::Instant;
 
+use crate::advisories::{self, Advisory, AdvisoryIndex};
 use crate::cache::Cache;
-use crate::registry::RegistryClient;
+use crate::cancel::CancelToken;
+use crate::consistency::{self, SyncPolicy};
+use crate::fixtures::{FixtureMode, FixtureStore};
+use crate::global::Environment;
+use crate::grouping;
+use crate::json_edit;
+use crate::license::{self, LicensePolicy};
+use crate::lockfile::Lockfile;
+use crate::manifest;
+use crate::node_modules;
+use crate::progress::{Progress, ProgressCallback};
+use crate::registry::{NetworkOptions, RegistryClient};
 use crate::semver_utils;
-use crate::types::{CheckOptions, CheckResult, PackageInfo, UpdateResult};
+use crate::transitive;
+use crate::types::{CheckOptions, CheckResult, PackageInfo, RegistryVersionInfo, UpdateResult};
+
+pub type UpdateCallback = Arc<dyn Fn(UpdateResult) + Send + Sync>;
+
+/// Optional observers of a running check
+#[derive(Default)]
+pub struct CheckHooks {
+    pub on_progress: Option<ProgressCallback>,
+    /// Called with each update as soon as its package has been resolved
+    pub on_update: Option<UpdateCallback>,
+    /// Stops pending and in-flight fetches; whatever was fetched is still cached
+    pub cancel: CancelToken,
+}
 
 pub async fn resolve_updates(
     packages: Vec<PackageInfo>,
     options: &CheckOptions,
-) -> CheckResult {
+    hooks: CheckHooks,
+) -> Result<CheckResult, String> {
     let total_start = Instant::now();
 
-    let registry = options
+    let mut registries = vec![options
         .registry
-        .as_deref()
-        .unwrap_or("https://registry.npmjs.org");
-    let target = options.target.as_deref().unwrap_or("latest");
+        .clone()
+        .unwrap_or_else(|| "https://registry.npmjs.org".to_string())];
+    registries.extend(options.fallback_registries.clone().unwrap_or_default());
     let concurrency = options.concurrency.unwrap_or(24);
     let timeout_ms = options.timeout_ms.unwrap_or(30000);
     let retries = options.retries.unwrap_or(3);
-    let include_prerelease = options.include_prerelease.unwrap_or(false);
     let cache_ttl = options.cache_ttl_seconds.unwrap_or(600) as u64;
+    let rules = TargetRules {
+        target: options.target.as_deref().unwrap_or("latest"),
+        include_prerelease: options.include_prerelease.unwrap_or(false),
+        license_policy: options.allowed_licenses.as_deref().map(LicensePolicy::new),
+    };
+    // License filtering needs each version's license
+    let full_metadata = options.full_metadata.unwrap_or(false) || rules.license_policy.is_some();
+    let check_advisories = options.check_advisories.unwrap_or(false) || rules.target == "security";
 
     let cache_file = options.cache_file.clone().unwrap_or_else(|| {
         let home = std::env::var("HOME").unwrap_or_else(|_| ".".to_string());
         format!("{}/.turbo-ncu-cache.json", home)
     });
 
+    let lockfile = options
+        .lockfile
+        .as_deref()
+        .map(Lockfile::read)
+        .transpose()?;
+    let importer = options.lockfile_importer.as_deref().unwrap_or("");
+    let transitive = if options.include_transitive.unwrap_or(false) {
+        let lockfile = lockfile
+            .as_ref()
+            .ok_or("lockfile is required with includeTransitive")?;
+        transitive::collect(lockfile, importer, &packages)
+    } else {
+        Vec::new()
+    };
+    let transitive_names = transitive::names(&transitive);
+    let installed = options
+        .install_root
+        .as_deref()
+        .map(|root| node_modules::check(std::path::Path::new(root), &packages))
+        .unwrap_or_default();
+
+    // What to fetch for each package; `npm:` aliases name another package
+    let lookups: Vec<PackageInfo> = packages.iter().map(manifest::registry_lookup).collect();
+    // Packages several manifests share are fetched once
+    let mut groups: Vec<(String, Vec<usize>)> = Vec::new();
+    let mut group_of: HashMap<&str, usize> = HashMap::new();
+    for (i, lookup) in lookups.iter().enumerate() {
+        let group = *group_of.entry(lookup.name.as_str()).or_insert_with(|| {
+            groups.push((lookup.name.clone(), Vec::new()));
+            groups.len() - 1
+        });
+        groups[group].1.push(i);
+    }
+
     let cache = Mutex::new(Cache::new(&cache_file, cache_ttl));
+    let progress = Arc::new(Progress::new(
+        (groups.len() + transitive_names.len()) as u32,
+        hooks.on_progress,
+    ));
+    let sync = options
+        .sync
+        .as_deref()
+        .map(|policy| SyncPolicy::parse(policy, options.sync_versions.as_ref()))
+        .transpose()?;
+    // Ranges of each package aligned by `sync`, by the package looked up
+    let aligned: HashMap<String, Vec<String>> = match &sync {
+        Some(policy) => consistency::occurrences(&packages, &lookups)
+            .into_iter()
+            .filter(|(name, found)| policy.applies(name, found.len()))
+            .map(|(name, found)| {
+                let ranges = found.iter().map(|(_, range)| range.to_string()).collect();
+                (name.to_string(), ranges)
+            })
+            .collect(),
+        None => HashMap::new(),
+    };
+    let mut agreed: HashMap<String, Option<Version>> = HashMap::new();
+    let group_rules = options.group_rules.clone().unwrap_or_default();
+    let grouping = options.group.unwrap_or(false) || options.group_rules.is_some();
+    // Version lists groups pick their shared version from, by package looked up
+    let mut infos: HashMap<String, RegistryVersionInfo> = HashMap::new();
+    let network = NetworkOptions::from_options(options, &Environment::from_process());
+    let mut client = RegistryClient::new(&registries, concurrency, timeout_ms, retries, &network)?
+        .with_progress(progress.clone())
+        .with_cancel(hooks.cancel.clone())
+        .with_full_metadata(full_metadata);
+    if let Some(failover_on) = &options.failover_on {
+        client = client.with_failover_on(failover_on.clone());
+    }
+    if let Some(mode) = &options.fixture_mode {
+        let dir = options
+            .fixture_dir
+            .as_deref()
+            .ok_or("fixtureDir is required with fixtureMode")?;
+        client = client.with_fixtures(FixtureStore::new(dir, FixtureMode::parse(mode)?));
+    }
+
+    // Fixture runs must see exactly the recorded responses, so bypass the cache
+    let use_cache = options.fixture_mode.is_none();
     let mut cache_hits: u32 = 0;
     let mut cache_misses: u32 = 0;
 
-    // Separate cached and uncached packages
-    let mut cached_versions: Vec<(usize, Vec<String>)> = Vec::new();
-    let mut to_fetch: Vec<(usize, String)> = Vec::new();
+    let mut resolved: Vec<(usize, UpdateResult)> = Vec::new();
+    // Updates grouping may still change; they are streamed once groups are known
+    let mut held = HashSet::new();
+    let mut emit = |idx: usize, info: &RegistryVersionInfo, advisories: Option<&AdvisoryIndex>| {
+        let pkg = &packages[idx];
+        // Every occurrence of an aligned package moves to the same version
+        let target = match (&sync, aligned.get(&lookups[idx].name)) {
+            (Some(policy), Some(ranges)) => Target::Agreed(
+                agreed
+                    .entry(info.name.clone())
+                    .or_insert_with(|| {
+                        policy.agreed_version(
+                            &info.name,
+                            ranges,
+                            &candidates(info, &rules),
+                            rules.include_prerelease,
+                        )
+                    })
+                    .clone(),
+            ),
+            _ => Target::Rules,
+        };
+        if grouping {
+            infos
+                .entry(lookups[idx].name.clone())
+                .or_insert_with(|| info.clone());
+        }
+        let advisories =
+            advisories.map(|index| index.get(&info.name).map(Vec::as_slice).unwrap_or(&[]));
+        let installed = locked_version(lockfile.as_ref(), importer, pkg);
+        if let Some(update) = build_update(pkg, info, &rules, &target, advisories, installed) {
+            let repository = update
+                .metadata
+                .as_ref()
+                .and_then(|m| m.repository.as_deref());
+            if grouping && grouping::may_group(&lookups[idx].name, repository, &group_rules) {
+                held.insert(idx);
+            } else if let Some(on_update) = &hooks.on_update {
+                on_update(update.clone());
+            }
+            resolved.push((idx, update));
+        }
+    };
+    // With advisories, packages are resolved once a single bulk lookup for all
+    // of them has returned
+    let mut deferred: Vec<(usize, RegistryVersionInfo)> = Vec::new();
+    let mut advisory_index: Option<AdvisoryIndex> = None;
+
+    // Resolve cached packages straight away, queue the rest for fetching
+    let mut to_fetch: Vec<&[usize]> = Vec::new();
+    let mut names: Vec<String> = Vec::new();
 
-    for (i, pkg) in packages.iter().enumerate() {
+    for (name, indices) in &groups {
         let c = cache.lock().unwrap();
-        if let Some(info) = c.get(&pkg.name) {
-            cached_versions.push((i, info.versions));
+        // Entries fetched in abbreviated form cannot serve a full-metadata check
+        let cached = c
+            .get(name)
+            .filter(|info| use_cache && (!full_metadata || !info.details.is_empty()));
+        drop(c);
+        if let Some(info) = cached {
             cache_hits += 1;
+            progress.complete("cache-hit", name, 0, 0);
+            for &i in indices {
+                if check_advisories {
+                    deferred.push((i, info.clone()));
+                } else {
+                    emit(i, &info, None);
+                }
+            }
         } else {
-            to_fetch.push((i, pkg.name.clone()));
+            to_fetch.push(indices);
+            names.push(name.clone());
             cache_misses += 1;
         }
-        drop(c);
     }
 
-    // Fetch uncached packages
+    // Fetch uncached packages, resolving each one as soon as it arrives
     let fetch_start = Instant::now();
-    let client = RegistryClient::new(registry, concurrency, timeout_ms, retries);
-    let names: Vec<String> = to_fetch.iter().map(|(_, n)| n.clone()).collect();
-    let results = client.fetch_many(&names).await;
-    let fetch_time_ms = fetch_start.elapsed().as_secs_f64() * 1000.0;
+    let mut pending = Box::pin(client.fetch_unordered(&names));
 
-    // Store fetched results in cache
-    let mut fetched_versions: Vec<(usize, Vec<String>)> = Vec::new();
-    for (j, result) in results.into_iter().enumerate() {
-        let (original_idx, _) = &to_fetch[j];
+    while let Some((j, result)) = pending.next().await {
         if let Ok(info) = result {
-            let mut c = cache.lock().unwrap();
-            c.set(&info.name, info.versions.clone());
-            drop(c);
-            fetched_versions.push((*original_idx, info.versions));
+            if use_cache {
+                let mut c = cache.lock().unwrap();
+                c.set(&info);
+                drop(c);
+            }
+            for &i in to_fetch[j] {
+                if check_advisories {
+                    deferred.push((i, info.clone()));
+                } else {
+                    emit(i, &info, None);
+                }
+            }
         }
     }
 
-    // Save cache
+    if !deferred.is_empty() {
+        // Advisories are compared against the locked version where known
+        let query = advisories::bulk_query(deferred.iter().map(|(i, info)| {
+            let installed = locked_version(lockfile.as_ref(), importer, &packages[*i]);
+            (lookups[*i].version_range.as_str(), installed, info)
+        }));
+        let index = if hooks.cancel.is_cancelled() {
+            None
+        } else if query.is_empty() {
+            Some(AdvisoryIndex::new())
+        } else {
+            match client.fetch_advisories(&query).await {
+                Ok(index) => Some(index),
+                Err(_) if hooks.cancel.is_cancelled() => None,
+                Err(e) => return Err(e),
+            }
+        };
+        for (i, info) in &deferred {
+            emit(*i, info, index.as_ref());
+        }
+        advisory_index = index;
+    }
+
+    // Indirect packages are fetched once per name, however many copies exist
+    let mut transitive_infos: HashMap<String, RegistryVersionInfo> = HashMap::new();
+    let mut transitive_fetch: Vec<String> = Vec::new();
+    for name in transitive_names {
+        let cached = cache.lock().unwrap().get(&name).filter(|_| use_cache);
+        match cached {
+            Some(info) => {
+                cache_hits += 1;
+                progress.complete("cache-hit", &name, 0, 0);
+                transitive_infos.insert(name, info);
+            }
+            None => {
+                cache_misses += 1;
+                transitive_fetch.push(name);
+            }
+        }
+    }
+    for info in client
+        .fetch_many(&transitive_fetch)
+        .await
+        .into_iter()
+        .flatten()
     {
+        if use_cache {
+            cache.lock().unwrap().set(&info);
+        }
+        transitive_infos.insert(info.name.clone(), info);
+    }
+    let transitive = transitive::outdated(
+        transitive,
+        &transitive_infos,
+        rules.target,
+        rules.include_prerelease,
+    );
+    let fetch_time_ms = fetch_start.elapsed().as_secs_f64() * 1000.0;
+
+    // Save cache
+    if use_cache {
         let mut c = cache.lock().unwrap();
         c.prune();
         let _ = c.save();
     }
 
-    // Merge and resolve
-    let mut all_versions: Vec<(usize, Vec<String>)> = Vec::new();
-    all_versions.extend(cached_versions);
-    all_versions.extend(fetched_versions);
-    all_versions.sort_by_key(|(i, _)| *i);
-
-    let mut updates: Vec<UpdateResult> = Vec::new();
+    // Report updates in input order regardless of completion order
+    resolved.sort_by_key(|(i, _)| *i);
 
-    for (idx, versions) in &all_versions {
-        let pkg = &packages[*idx];
-        let resolved =
-            semver_utils::resolve_target_version(&pkg.version_range, versions, target, include_prerelease);
-
-        if let Some(new_version) = resolved {
-            let current_version = semver_utils::parse_base_version(&pkg.version_range);
-            let current_version_str = current_version
-                .as_ref()
-                .map(|v| format!("{}", v))
-                .unwrap_or_default();
-
-            let update_type = current_version
-                .as_ref()
-                .map(|cv| semver_utils::classify_update(cv, &new_version))
-                .unwrap_or_else(|| "unknown".to_string());
-
-            let new_range = semver_utils::construct_new_range(&pkg.version_range, &new_version);
-
-            updates.push(UpdateResult {
-                name: pkg.name.clone(),
-                current: pkg.version_range.clone(),
-                current_version: current_version_str,
-                latest: format!("{}", new_version),
-                new_range,
-                update_type,
-                dep_type: pkg.dep_type.clone(),
-            });
+    // Related packages move to one shared version, rebuilt from the same inputs
+    let mut update_groups = Vec::new();
+    if grouping {
+        let name = |i: usize| lookups[i].name.as_str();
+        (resolved, update_groups) = grouping::group_updates(
+            resolved,
+            &group_rules,
+            name,
+            // Packages `sync` aligns already have their version
+            |i| {
+                let info = infos
+                    .get(name(i))
+                    .filter(|_| !aligned.contains_key(name(i)))?;
+                Some(candidates(info, &rules))
+            },
+            |i, version| {
+                let pkg = &packages[i];
+                let info = &infos[name(i)];
+                let advisories = advisory_index
+                    .as_ref()
+                    .map(|index| index.get(&info.name).map(Vec::as_slice).unwrap_or(&[]));
+                let installed = locked_version(lockfile.as_ref(), importer, pkg);
+                let target = Target::Grouped(version.clone());
+                build_update(pkg, info, &rules, &target, advisories, installed)
+            },
+        );
+        if let Some(on_update) = &hooks.on_update {
+            for (i, update) in &resolved {
+                if held.contains(i) {
+                    on_update(update.clone());
+                }
+            }
         }
     }
+    // With a known installed version, targets the range already allows only
+    // need a lockfile refresh and are reported apart
+    let (in_range, updates): (Vec<UpdateResult>, Vec<UpdateResult>) = resolved
+        .into_iter()
+        .map(|(_, u)| u)
+        .partition(|u| u.installed_version.is_some() && !u.requires_manifest_change());
+
+    let mut divergences = consistency::divergences(&packages, &lookups);
+    for divergence in &mut divergences {
+        divergence.agreed = agreed
+            .get(&divergence.name)
+            .and_then(|v| v.as_ref().map(|v| v.to_string()));
+    }
 
     let total_time_ms = total_start.elapsed().as_secs_f64() * 1000.0;
 
-    CheckResult {
+    Ok(CheckResult {
         updates,
+        in_range,
         cache_hits,
         cache_misses,
         fetch_time_ms,
         total_time_ms,
+        cancelled: hooks.cancel.is_cancelled(),
+        transitive,
+        divergences,
+        installed,
+        groups: update_groups,
+    })
+}
+
+/// How the target version of each package is chosen
+struct TargetRules<'a> {
+    target: &'a str,
+    include_prerelease: bool,
+    /// Versions under licenses this does not allow are never targeted
+    license_policy: Option<LicensePolicy>,
+}
+
+/// Where a package's new version comes from
+enum Target {
+    /// Chosen by the check's `TargetRules`
+    Rules,
+    /// The version `sync` aligned the package to, if one could be agreed on
+    Agreed(Option<Version>),
+    /// The version shared by the package's group
+    Grouped(Version),
+}
+
+/// Version the lockfile has installed for `pkg`, looked up in the package's
+/// workspace or else `importer`
+fn locked_version<'a>(
+    lockfile: Option<&'a Lockfile>,
+    importer: &str,
+    pkg: &PackageInfo,
+) -> Option<&'a str> {
+    let importer = pkg.workspace.as_deref().unwrap_or(importer);
+    // Only the dependency sections are installed under their own name
+    lockfile
+        .filter(|_| json_edit::section_for_dep_type(&pkg.dep_type).is_some())
+        .and_then(|lockfile| lockfile.installed_version(importer, &pkg.name, &pkg.version_range))
+}
+
+/// Versions the rules allow as targets
+fn candidates(info: &RegistryVersionInfo, rules: &TargetRules) -> Vec<String> {
+    match &rules.license_policy {
+        Some(policy) => info
+            .versions
+            .iter()
+            .filter(|v| policy.allows(license_of(info, v)))
+            .cloned()
+            .collect(),
+        None => info.versions.clone(),
+    }
+}
+
+/// Resolve a single package against its available versions. `advisories` is
+/// set when they were looked up, and drives the "security" target; `installed`
+/// is the lockfile's version, which updates are then classified against
+fn build_update(
+    pkg: &PackageInfo,
+    info: &RegistryVersionInfo,
+    rules: &TargetRules,
+    target: &Target,
+    advisories: Option<&[Advisory]>,
+    installed: Option<&str>,
+) -> Option<UpdateResult> {
+    let lookup = manifest::registry_lookup(pkg);
+    let range = &lookup.version_range;
+    let candidates = candidates(info, rules);
+    let installed = installed.and_then(|v| v.parse::<Version>().ok());
+    let resolved = installed
+        .clone()
+        .or_else(|| semver_utils::resolved_version(range, &info.versions));
+    let new_version = match (target, rules.target, advisories) {
+        (Target::Agreed(agreed), _, _) => agreed.clone()?,
+        (Target::Grouped(version), _, _) => version.clone(),
+        (_, "security", Some(advisories)) => advisories::lowest_safe_version(
+            resolved.as_ref()?,
+            &candidates,
+            advisories,
+            rules.include_prerelease,
+        )?,
+        _ => semver_utils::resolve_target_version(
+            range,
+            &candidates,
+            rules.target,
+            rules.include_prerelease,
+        )?,
+    };
+
+    let mut new_range = semver_utils::construct_new_range(range, &new_version);
+    let aligned = matches!(target, Target::Agreed(_));
+    // Aligned occurrences must match textually, not just allow the version
+    if aligned && new_range == *range {
+        return None;
+    }
+    // A group member already declaring the shared version has nothing to do
+    if matches!(target, Target::Grouped(_)) && new_range == *range && installed.is_none() {
+        return None;
+    }
+    let requires_manifest_change = aligned
+        || !range
+            .parse::<Range>()
+            .is_ok_and(|range| range.satisfies(&new_version));
+    // The range already allows the target and the lockfile has it: nothing to do
+    if !requires_manifest_change && installed.as_ref().is_some_and(|v| *v >= new_version) {
+        return None;
+    }
+
+    let current_version = semver_utils::parse_base_version(range);
+    let current_version_str = current_version
+        .as_ref()
+        .map(|v| format!("{}", v))
+        .unwrap_or_default();
+
+    let update_type = match installed.as_ref().or(current_version.as_ref()) {
+        // A pinned version may lie below what is declared
+        Some(cv) if new_version < *cv => "downgrade".to_string(),
+        Some(cv) => semver_utils::classify_update(cv, &new_version),
+        None => "unknown".to_string(),
+    };
+
+    if pkg.version_range.starts_with("npm:") {
+        new_range = format!("npm:{}@{}", lookup.name, new_range);
+    }
+    let latest = format!("{}", new_version);
+    let metadata = info.details.get(&latest).cloned();
+
+    let (license_from, license_to, license_changed) = if info.details.is_empty() {
+        (None, None, None)
+    } else {
+        let resolved = resolved.as_ref().map(|v| v.to_string()).unwrap_or_default();
+        let from = license_of(info, &resolved);
+        let to = license_of(info, &latest);
+        let changed = !license::same_license(from, to);
+        (from.map(String::from), to.map(String::from), Some(changed))
+    };
+
+    let (fixed_vulnerabilities, introduced_vulnerabilities) = match advisories {
+        Some(advisories) => {
+            let (fixed, introduced) =
+                advisories::compare(advisories, resolved.as_ref(), &new_version);
+            (Some(fixed), Some(introduced))
+        }
+        None => (None, None),
+    };
+
+    Some(UpdateResult {
+        name: pkg.name.clone(),
+        current: pkg.version_range.clone(),
+        current_version: current_version_str,
+        installed_version: installed.map(|v| v.to_string()),
+        requires_manifest_change: Some(requires_manifest_change),
+        latest,
+        new_range,
+        update_type,
+        dep_type: pkg.dep_type.clone(),
+        pointer: pkg.pointer.clone(),
+        workspace: pkg.workspace.clone(),
+        registry: Some(info.registry.clone()).filter(|r| !r.is_empty()),
+        metadata,
+        license_from,
+        license_to,
+        license_changed,
+        fixed_vulnerabilities,
+        introduced_vulnerabilities,
+    })
+}
+
+fn license_of<'a>(info: &'a RegistryVersionInfo, version: &str) -> Option<&'a str> {
+    info.details.get(version)?.license.as_deref()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::RecordedResponse;
+    use crate::mock_registry::MockRegistry;
+    use std::collections::BTreeMap;
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    const REGISTRY: &str = "https://registry.test";
+    static TEST_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+    fn fixture_dir(label: &str) -> String {
+        let id = TEST_COUNTER.fetch_add(1, Ordering::SeqCst);
+        let mut path = std::env::temp_dir();
+        path.push(format!(
+            "turbo-ncu-resolver-{}-{}-{}",
+            label,
+            std::process::id(),
+            id
+        ));
+        path.to_string_lossy().to_string()
+    }
+
+    fn record(dir: &str, name: &str, versions: &[&str]) {
+        let store = FixtureStore::new(dir, FixtureMode::Record);
+        let url = format!("{}/{}", REGISTRY, name.replacen('/', "%2f", 1));
+        let versions: serde_json::Map<String, serde_json::Value> = versions
+            .iter()
+            .map(|v| (v.to_string(), serde_json::json!({})))
+            .collect();
+        let body = serde_json::json!({ "name": name, "versions": versions });
+        let response = RecordedResponse {
+            url: url.clone(),
+            status: 200,
+            headers: BTreeMap::new(),
+            body: body.to_string(),
+        };
+        store.save(&url, &response).unwrap();
+    }
+
+    fn replay_options(dir: &str) -> CheckOptions {
+        CheckOptions {
+            registry: Some(REGISTRY.to_string()),
+            retries: Some(0),
+            fixture_mode: Some("replay".to_string()),
+            fixture_dir: Some(dir.to_string()),
+            ..Default::default()
+        }
+    }
+
+    fn pkg(name: &str, range: &str) -> PackageInfo {
+        PackageInfo {
+            name: name.to_string(),
+            version_range: range.to_string(),
+            dep_type: "prod".to_string(),
+            pointer: None,
+            workspace: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_resolves_from_replayed_fixtures() {
+        let dir = fixture_dir("replay");
+        record(&dir, "lodash", &["4.0.0", "4.17.20", "4.17.21"]);
+        record(&dir, "@types/node", &["18.0.0", "20.1.0"]);
+        record(&dir, "chalk", &["5.3.0"]);
+
+        let packages = vec![
+            pkg("lodash", "^4.0.0"),
+            pkg("chalk", "^5.3.0"),
+            pkg("@types/node", "~18.0.0"),
+        ];
+        let result = resolve_updates(packages, &replay_options(&dir), CheckHooks::default())
+            .await
+            .unwrap();
+
+        let names: Vec<&str> = result.updates.iter().map(|u| u.name.as_str()).collect();
+        assert_eq!(names, vec!["lodash", "@types/node"]);
+        assert_eq!(result.updates[0].new_range, "^4.17.21");
+        assert_eq!(result.updates[1].new_range, "~20.1.0");
+        assert_eq!(result.updates[1].update_type, "major");
+        assert_eq!(result.updates[0].registry.as_deref(), Some(REGISTRY));
+        assert_eq!(result.cache_misses, 3);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_streams_each_update() {
+        let dir = fixture_dir("stream");
+        record(&dir, "lodash", &["4.0.0", "4.17.21"]);
+        record(&dir, "chalk", &["4.0.0", "5.3.0"]);
+
+        let streamed = Arc::new(Mutex::new(Vec::new()));
+        let sink = streamed.clone();
+        let hooks = CheckHooks {
+            on_update: Some(Arc::new(move |update: UpdateResult| {
+                sink.lock().unwrap().push(update.name);
+            })),
+            ..Default::default()
+        };
+        let packages = vec![pkg("lodash", "^4.0.0"), pkg("chalk", "^4.0.0")];
+        let result = resolve_updates(packages, &replay_options(&dir), hooks)
+            .await
+            .unwrap();
+
+        let mut streamed = streamed.lock().unwrap().clone();
+        streamed.sort();
+        assert_eq!(streamed, vec!["chalk", "lodash"]);
+        assert_eq!(result.updates.len(), 2);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_missing_fixture_drops_package() {
+        let dir = fixture_dir("missing");
+        record(&dir, "lodash", &["4.0.0", "4.17.21"]);
+
+        let packages = vec![pkg("lodash", "^4.0.0"), pkg("not-recorded", "^1.0.0")];
+        let result = resolve_updates(packages, &replay_options(&dir), CheckHooks::default())
+            .await
+            .unwrap();
+        assert_eq!(result.updates.len(), 1);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_resolves_npm_alias_against_target_package() {
+        let dir = fixture_dir("alias");
+        record(&dir, "strip-ansi", &["6.0.1", "7.1.0"]);
+
+        let packages = vec![pkg("strip-ansi-cjs", "npm:strip-ansi@^6.0.1")];
+        let result = resolve_updates(packages, &replay_options(&dir), CheckHooks::default())
+            .await
+            .unwrap();
+
+        let update = &result.updates[0];
+        assert_eq!(update.name, "strip-ansi-cjs");
+        assert_eq!(update.current_version, "6.0.1");
+        assert_eq!(update.new_range, "npm:strip-ansi@^7.1.0");
+        assert_eq!(update.update_type, "major");
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_resolves_override_selector_target() {
+        let dir = fixture_dir("override");
+        record(&dir, "lodash", &["4.0.0", "4.17.21"]);
+
+        let pinned = PackageInfo {
+            dep_type: "override".to_string(),
+            pointer: Some("/pnpm/overrides/foo@1>lodash".to_string()),
+            ..pkg("foo@1>lodash", "4.0.0")
+        };
+        let result = resolve_updates(vec![pinned], &replay_options(&dir), CheckHooks::default())
+            .await
+            .unwrap();
+
+        let update = &result.updates[0];
+        assert_eq!(update.name, "foo@1>lodash");
+        assert_eq!(update.new_range, "4.17.21");
+        assert_eq!(update.dep_type, "override");
+        assert_eq!(
+            update.pointer.as_deref(),
+            Some("/pnpm/overrides/foo@1>lodash")
+        );
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_classifies_against_locked_version() {
+        let dir = fixture_dir("lockfile");
+        record(&dir, "lodash", &["4.0.0", "4.17.20", "4.17.21"]);
+        record(&dir, "chalk", &["4.0.0", "5.3.0"]);
+        let lockfile = format!("{}/package-lock.json", dir);
+        std::fs::write(
+            &lockfile,
+            r#"{"lockfileVersion":3,"packages":{"":{"dependencies":{"lodash":"^4.0.0"}},"node_modules/lodash":{"version":"4.17.20"}}}"#,
+        )
+        .unwrap();
+
+        let options = CheckOptions {
+            lockfile: Some(lockfile),
+            ..replay_options(&dir)
+        };
+        let packages = vec![pkg("lodash", "^4.0.0"), pkg("chalk", "^4.0.0")];
+        let result = resolve_updates(packages, &options, CheckHooks::default())
+            .await
+            .unwrap();
+
+        // ^4.0.0 already allows 4.17.21: only the lockfile needs refreshing
+        assert_eq!(result.in_range.len(), 1);
+        let lodash = &result.in_range[0];
+        assert_eq!(lodash.current_version, "4.0.0");
+        assert_eq!(lodash.installed_version.as_deref(), Some("4.17.20"));
+        assert_eq!(lodash.update_type, "patch");
+        assert!(!lodash.requires_manifest_change());
+        // Not in the lockfile: classified against the range floor as before
+        assert_eq!(result.updates.len(), 1);
+        let chalk = &result.updates[0];
+        assert_eq!(chalk.installed_version, None);
+        assert_eq!(chalk.update_type, "major");
+        assert!(chalk.requires_manifest_change());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_semver_target_skips_installed_in_range_version() {
+        let dir = fixture_dir("lockfile-semver");
+        record(&dir, "lodash", &["4.0.0", "4.17.21", "5.0.0"]);
+        let lockfile = format!("{}/package-lock.json", dir);
+        std::fs::write(
+            &lockfile,
+            r#"{"lockfileVersion":3,"packages":{"":{"dependencies":{"lodash":"^4.0.0"}},"node_modules/lodash":{"version":"4.17.21"}}}"#,
+        )
+        .unwrap();
+
+        let options = CheckOptions {
+            target: Some("semver".to_string()),
+            lockfile: Some(lockfile),
+            ..replay_options(&dir)
+        };
+        let result = resolve_updates(
+            vec![pkg("lodash", "^4.0.0")],
+            &options,
+            CheckHooks::default(),
+        )
+        .await
+        .unwrap();
+        assert!(result.updates.is_empty());
+        assert!(result.in_range.is_empty());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_groups_fall_back_to_common_version() {
+        let dir = fixture_dir("groups");
+        record(&dir, "@babel/core", &["7.23.0", "7.24.0", "7.24.5"]);
+        record(&dir, "@babel/preset-env", &["7.23.0", "7.24.0"]);
+        record(&dir, "lodash", &["4.17.20", "4.17.21"]);
+        let packages = vec![
+            pkg("@babel/core", "^7.23.0"),
+            pkg("@babel/preset-env", "^7.23.0"),
+            pkg("lodash", "^4.17.20"),
+        ];
+        let options = CheckOptions {
+            group: Some(true),
+            ..replay_options(&dir)
+        };
+        let result = resolve_updates(packages, &options, CheckHooks::default())
+            .await
+            .unwrap();
+
+        let ranges: Vec<(&str, &str)> = result
+            .updates
+            .iter()
+            .map(|u| (u.name.as_str(), u.new_range.as_str()))
+            .collect();
+        // preset-env never published 7.24.5, so core stays with it on 7.24.0
+        assert_eq!(
+            ranges,
+            vec![
+                ("@babel/core", "^7.24.0"),
+                ("@babel/preset-env", "^7.24.0"),
+                ("lodash", "^4.17.21")
+            ]
+        );
+        assert_eq!(result.groups.len(), 1);
+        let group = &result.groups[0];
+        assert_eq!(
+            (group.name.as_str(), group.source.as_str()),
+            ("@babel", "scope")
+        );
+        assert_eq!(group.version.as_deref(), Some("7.24.0"));
+        assert!(group.fell_back);
+        assert_eq!(group.updates.len(), 2);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_streams_grouped_updates_as_reported() {
+        let dir = fixture_dir("stream-groups");
+        record(&dir, "@babel/core", &["7.23.0", "7.24.0", "7.24.5"]);
+        record(&dir, "@babel/preset-env", &["7.23.0", "7.24.0"]);
+        record(&dir, "lodash", &["4.17.20", "4.17.21"]);
+        let packages = vec![
+            pkg("@babel/core", "^7.23.0"),
+            pkg("@babel/preset-env", "^7.23.0"),
+            pkg("lodash", "^4.17.20"),
+        ];
+        let streamed = Arc::new(Mutex::new(Vec::new()));
+        let sink = streamed.clone();
+        let hooks = CheckHooks {
+            on_update: Some(Arc::new(move |update: UpdateResult| {
+                sink.lock().unwrap().push(update);
+            })),
+            ..Default::default()
+        };
+        let options = CheckOptions {
+            group: Some(true),
+            ..replay_options(&dir)
+        };
+        let result = resolve_updates(packages, &options, hooks).await.unwrap();
+
+        let summary = |updates: &[UpdateResult]| {
+            let mut summary: Vec<(String, String, String)> = updates
+                .iter()
+                .map(|u| (u.name.clone(), u.latest.clone(), u.new_range.clone()))
+                .collect();
+            summary.sort();
+            summary
+        };
+        let streamed = streamed.lock().unwrap().clone();
+        assert_eq!(summary(&streamed), summary(&result.updates));
+        assert!(streamed.iter().any(|u| u.new_range == "^7.24.0"));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_sync_aligns_workspaces() {
+        let dir = fixture_dir("sync");
+        record(&dir, "react", &["18.0.0", "18.2.0", "18.3.1", "19.0.0"]);
+        let in_workspace = |workspace: &str, range: &str| PackageInfo {
+            workspace: Some(workspace.to_string()),
+            ..pkg("react", range)
+        };
+        let packages = vec![
+            in_workspace("web", "^18.2.0"),
+            in_workspace("ui", "^18.0.0"),
+        ];
+        let check = |sync: &str, pins: Option<&[(&str, &str)]>| {
+            let options = CheckOptions {
+                sync: Some(sync.to_string()),
+                sync_versions: pins.map(|pins| {
+                    pins.iter()
+                        .map(|(n, v)| (n.to_string(), v.to_string()))
+                        .collect()
+                }),
+                ..replay_options(&dir)
+            };
+            let packages = packages.clone();
+            async move {
+                resolve_updates(packages, &options, CheckHooks::default())
+                    .await
+                    .unwrap()
+            }
+        };
+        let ranges = |result: &CheckResult| {
+            result
+                .updates
+                .iter()
+                .map(|u| (u.workspace.clone().unwrap(), u.new_range.clone()))
+                .collect::<Vec<_>>()
+        };
+
+        // Without sync each range resolves on its own, but the divergence shows
+        let result = resolve_updates(
+            packages.clone(),
+            &replay_options(&dir),
+            CheckHooks::default(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(result.divergences.len(), 1);
+        assert_eq!(result.divergences[0].agreed, None);
+
+        let result = check("highest", None).await;
+        assert_eq!(
+            ranges(&result),
+            vec![("ui".to_string(), "^18.2.0".to_string())]
+        );
+        assert_eq!(result.divergences[0].agreed.as_deref(), Some("18.2.0"));
+
+        let result = check("latest", None).await;
+        assert_eq!(
+            ranges(&result),
+            vec![
+                ("web".to_string(), "^19.0.0".to_string()),
+                ("ui".to_string(), "^19.0.0".to_string())
+            ]
+        );
+
+        let result = check("pinned", Some(&[("react", "18.0.0")])).await;
+        assert_eq!(
+            ranges(&result),
+            vec![("web".to_string(), "^18.0.0".to_string())]
+        );
+        assert_eq!(result.updates[0].update_type, "downgrade");
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_reports_transitive_updates() {
+        let dir = fixture_dir("transitive");
+        record(&dir, "express", &["4.18.2"]);
+        record(&dir, "qs", &["6.11.0", "6.13.0"]);
+        let lockfile = format!("{}/package-lock.json", dir);
+        std::fs::write(
+            &lockfile,
+            r#"{"lockfileVersion":3,"packages":{"":{"dependencies":{"express":"^4.18.0"}},"node_modules/express":{"version":"4.18.2","dependencies":{"qs":"6.11.0"}},"node_modules/qs":{"version":"6.11.0"}}}"#,
+        )
+        .unwrap();
+        let packages = vec![pkg("express", "^4.18.0")];
+
+        let options = CheckOptions {
+            include_transitive: Some(true),
+            ..replay_options(&dir)
+        };
+        let err = resolve_updates(packages.clone(), &options, CheckHooks::default())
+            .await
+            .unwrap_err();
+        assert_eq!(err, "lockfile is required with includeTransitive");
+
+        let options = CheckOptions {
+            lockfile: Some(lockfile),
+            ..options
+        };
+        let result = resolve_updates(packages, &options, CheckHooks::default())
+            .await
+            .unwrap();
+        assert!(result.updates.is_empty());
+        assert_eq!(result.transitive.len(), 1);
+        let qs = &result.transitive[0];
+        assert_eq!(
+            (qs.installed.as_str(), qs.latest.as_str()),
+            ("6.11.0", "6.13.0")
+        );
+        assert_eq!(qs.dependents[0].path, vec!["express@4.18.2"]);
+        assert_eq!(qs.dependents[0].allows_latest, Some(false));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_full_metadata_bypasses_abbreviated_cache() {
+        let server = MockRegistry::fixtures().start().await;
+        let cache_file = format!("{}.json", fixture_dir("metadata"));
+        let mut options = CheckOptions {
+            registry: Some(server.url.clone()),
+            retries: Some(0),
+            cache_file: Some(cache_file.clone()),
+            ..Default::default()
+        };
+
+        let packages = vec![pkg("lodash", "^4.0.0")];
+        let abbreviated = resolve_updates(packages.clone(), &options, CheckHooks::default())
+            .await
+            .unwrap();
+        assert_eq!(abbreviated.updates[0].metadata, None);
+
+        options.full_metadata = Some(true);
+        let full = resolve_updates(packages.clone(), &options, CheckHooks::default())
+            .await
+            .unwrap();
+        assert_eq!(full.cache_misses, 1);
+        let metadata = full.updates[0].metadata.as_ref().unwrap();
+        assert_eq!(metadata.homepage.as_deref(), Some("https://lodash.com/"));
+
+        // Full entries serve later checks of either kind
+        options.full_metadata = Some(false);
+        let cached = resolve_updates(packages, &options, CheckHooks::default())
+            .await
+            .unwrap();
+        assert_eq!(cached.cache_hits, 1);
+        assert_eq!(server.requests().len(), 2);
+
+        let _ = std::fs::remove_file(&cache_file);
+    }
+
+    #[tokio::test]
+    async fn test_license_change_and_allow_list() {
+        let server = MockRegistry::fixtures().start().await;
+        let cache_file = format!("{}.json", fixture_dir("license"));
+        let mut options = CheckOptions {
+            registry: Some(server.url.clone()),
+            retries: Some(0),
+            cache_file: Some(cache_file.clone()),
+            cache_ttl_seconds: Some(0),
+            full_metadata: Some(true),
+            ..Default::default()
+        };
+        let packages = vec![pkg("relicensed", "^1.0.0"), pkg("lodash", "^4.0.0")];
+
+        let result = resolve_updates(packages.clone(), &options, CheckHooks::default())
+            .await
+            .unwrap();
+        let relicensed = &result.updates[0];
+        assert_eq!(relicensed.latest, "2.0.0");
+        assert_eq!(relicensed.license_from.as_deref(), Some("MIT"));
+        assert_eq!(relicensed.license_to.as_deref(), Some("BUSL-1.1"));
+        assert_eq!(relicensed.license_changed, Some(true));
+        assert_eq!(result.updates[1].license_changed, Some(false));
+
+        options.full_metadata = None;
+        options.allowed_licenses = Some(vec!["MIT".to_string(), "Apache-2.0".to_string()]);
+        let result = resolve_updates(packages, &options, CheckHooks::default())
+            .await
+            .unwrap();
+        assert_eq!(result.updates[0].latest, "1.1.0");
+        assert_eq!(result.updates[0].license_changed, Some(false));
+
+        let _ = std::fs::remove_file(&cache_file);
+    }
+
+    #[tokio::test]
+    async fn test_security_target_picks_lowest_safe_version() {
+        let server = MockRegistry::fixtures().start().await;
+        let cache_file = format!("{}.json", fixture_dir("security"));
+        let options = CheckOptions {
+            registry: Some(server.url.clone()),
+            target: Some("security".to_string()),
+            retries: Some(0),
+            cache_file: Some(cache_file.clone()),
+            cache_ttl_seconds: Some(0),
+            ..Default::default()
+        };
+        let packages = vec![
+            pkg("lodash", "~4.0.0"),
+            pkg("chalk", "^4.1.0"),
+            pkg("relicensed", "^1.0.0"),
+        ];
+
+        let result = resolve_updates(packages, &options, CheckHooks::default())
+            .await
+            .unwrap();
+        assert_eq!(result.updates.len(), 1);
+        let lodash = &result.updates[0];
+        assert_eq!(lodash.new_range, "~4.17.21");
+        let fixed: Vec<i64> = lodash
+            .fixed_vulnerabilities
+            .as_ref()
+            .unwrap()
+            .iter()
+            .map(|v| v.id)
+            .collect();
+        assert_eq!(fixed, vec![1106913, 1106914]);
+        assert_eq!(lodash.introduced_vulnerabilities, Some(vec![]));
+
+        // One bulk lookup covering every package
+        let lookups: Vec<_> = server
+            .requests()
+            .into_iter()
+            .filter(|r| r.method == "POST")
+            .collect();
+        assert_eq!(lookups.len(), 1);
+        assert_eq!(lookups[0].path, "/-/npm/v1/security/advisories/bulk");
+        let query: serde_json::Value = serde_json::from_slice(&lookups[0].body).unwrap();
+        assert_eq!(
+            query["lodash"],
+            serde_json::json!(["4.0.0", "4.17.20", "4.17.21"])
+        );
+        assert_eq!(query["relicensed"], serde_json::json!(["1.1.0", "2.0.0"]));
+
+        let _ = std::fs::remove_file(&cache_file);
+    }
+
+    #[tokio::test]
+    async fn test_security_target_checks_locked_version() {
+        let server = MockRegistry::fixtures().start().await;
+        let dir = fixture_dir("security-lockfile");
+        std::fs::create_dir_all(&dir).unwrap();
+        let lockfile = format!("{}/package-lock.json", dir);
+        std::fs::write(
+            &lockfile,
+            r#"{"lockfileVersion":3,"packages":{"":{"dependencies":{"lodash":"^4.0.0"}},"node_modules/lodash":{"version":"4.17.20"}}}"#,
+        )
+        .unwrap();
+        let options = CheckOptions {
+            registry: Some(server.url.clone()),
+            target: Some("security".to_string()),
+            retries: Some(0),
+            lockfile: Some(lockfile),
+            cache_file: Some(format!("{}/cache.json", dir)),
+            cache_ttl_seconds: Some(0),
+            ..Default::default()
+        };
+
+        // ^4.0.0 resolves to the safe 4.17.21, but the lockfile pins 4.17.20
+        let result = resolve_updates(
+            vec![pkg("lodash", "^4.0.0")],
+            &options,
+            CheckHooks::default(),
+        )
+        .await
+        .unwrap();
+        assert!(result.updates.is_empty());
+        assert_eq!(result.in_range.len(), 1);
+        let lodash = &result.in_range[0];
+        assert_eq!(lodash.latest, "4.17.21");
+        let fixed: Vec<i64> = lodash
+            .fixed_vulnerabilities
+            .as_ref()
+            .unwrap()
+            .iter()
+            .map(|v| v.id)
+            .collect();
+        assert_eq!(fixed, vec![1106914]);
+
+        let lookup = server
+            .requests()
+            .into_iter()
+            .find(|r| r.method == "POST")
+            .unwrap();
+        let query: serde_json::Value = serde_json::from_slice(&lookup.body).unwrap();
+        assert_eq!(query["lodash"], serde_json::json!(["4.17.20", "4.17.21"]));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_advisories_report_introduced_vulnerabilities() {
+        let server = MockRegistry::fixtures().start().await;
+        let cache_file = format!("{}.json", fixture_dir("advisories"));
+        let options = CheckOptions {
+            registry: Some(server.url.clone()),
+            retries: Some(0),
+            check_advisories: Some(true),
+            cache_file: Some(cache_file.clone()),
+            cache_ttl_seconds: Some(0),
+            ..Default::default()
+        };
+
+        let result = resolve_updates(
+            vec![pkg("relicensed", "^1.0.0")],
+            &options,
+            CheckHooks::default(),
+        )
+        .await
+        .unwrap();
+        let update = &result.updates[0];
+        assert_eq!(update.latest, "2.0.0");
+        assert_eq!(update.fixed_vulnerabilities, Some(vec![]));
+        let introduced = update.introduced_vulnerabilities.as_ref().unwrap();
+        assert_eq!(introduced[0].severity, "moderate");
+
+        let _ = std::fs::remove_file(&cache_file);
+    }
+
+    #[tokio::test]
+    async fn test_fixture_mode_requires_dir() {
+        let options = CheckOptions {
+            fixture_mode: Some("replay".to_string()),
+            ..Default::default()
+        };
+        let result = resolve_updates(vec![], &options, CheckHooks::default()).await;
+        assert!(result.is_err());
     }
 }
@@ -44,9 +44,7 @@ pub fn parse_base_version(range_str: &str) -> Option<Version> {
         .trim();
 
     // Handle x-ranges like "1.x" or "1.2.x"
-    let normalized = version_part
-        .replace(".x", ".0")
-        .replace(".*", ".0");
+    let normalized = version_part.replace(".x", ".0").replace(".*", ".0");
 
     normalized.parse::<Version>().ok()
 }
@@ -69,16 +67,47 @@ pub fn classify_update(current: &Version, new_version: &Version) -> String {
 /// Construct a new range string preserving the original prefix
 pub fn construct_new_range(original_range: &str, new_version: &Version) -> String {
     let prefix = extract_prefix(original_range);
-    let version_str = format!("{}.{}.{}", new_version.major, new_version.minor, new_version.patch);
+    let version_str = format!(
+        "{}.{}.{}",
+        new_version.major, new_version.minor, new_version.patch
+    );
 
     if !new_version.pre_release.is_empty() {
-        let pre: Vec<String> = new_version.pre_release.iter().map(|id| format!("{}", id)).collect();
+        let pre: Vec<String> = new_version
+            .pre_release
+            .iter()
+            .map(|id| format!("{}", id))
+            .collect();
         format!("{}{}-{}", prefix, version_str, pre.join("."))
     } else {
         format!("{}{}", prefix, version_str)
     }
 }
 
+/// Highest stable version satisfying `range`, i.e. what a fresh install of it
+/// would pick
+pub fn max_satisfying(range: &str, versions: &[String]) -> Option<Version> {
+    let range = range.parse::<Range>().ok()?;
+    versions
+        .iter()
+        .filter_map(|v| v.parse::<Version>().ok())
+        .filter(|v| v.pre_release.is_empty() && range.satisfies(v))
+        .max()
+}
+
+/// Whether `version` satisfies `range`; None when either does not parse
+pub fn satisfies(range: &str, version: &str) -> Option<bool> {
+    let range = range.parse::<Range>().ok()?;
+    let version = version.parse::<Version>().ok()?;
+    Some(range.satisfies(&version))
+}
+
+/// The version a fresh install of `range` picks, or its floor when no
+/// published version satisfies it
+pub fn resolved_version(range: &str, versions: &[String]) -> Option<Version> {
+    max_satisfying(range, versions).or_else(|| parse_base_version(range))
+}
+
 /// Resolve the target version from a list of available versions
 pub fn resolve_target_version(
     current_range: &str,
@@ -104,7 +133,9 @@ pub fn resolve_target_version(
     match target {
         "latest" => {
             // Find the highest version
-            parsed_versions.into_iter().rev().next()
+            parsed_versions
+                .into_iter()
+                .next_back()
                 .filter(|v| v > &current_version)
         }
         "minor" => {
@@ -116,14 +147,11 @@ pub fn resolve_target_version(
         }
         "patch" => {
             // Find highest version with same major.minor
-            parsed_versions
-                .into_iter()
-                .rev()
-                .find(|v| {
-                    v.major == current_version.major
-                        && v.minor == current_version.minor
-                        && v > &current_version
-                })
+            parsed_versions.into_iter().rev().find(|v| {
+                v.major == current_version.major
+                    && v.minor == current_version.minor
+                    && v > &current_version
+            })
         }
         "semver" => {
             // Find highest version satisfying the current range
@@ -182,6 +210,20 @@ mod tests {
         assert_eq!(classify_update(&v1, &v4), "patch");
     }
 
+    #[test]
+    fn test_max_satisfying() {
+        let versions: Vec<String> = ["1.0.0", "1.4.2", "1.5.0-beta.1", "2.0.0"]
+            .iter()
+            .map(|v| v.to_string())
+            .collect();
+        assert_eq!(
+            max_satisfying("^1.0.0", &versions),
+            "1.4.2".parse::<Version>().ok()
+        );
+        assert_eq!(max_satisfying("^3.0.0", &versions), None);
+        assert_eq!(max_satisfying("not a range", &versions), None);
+    }
+
     #[test]
     fn test_construct_new_range() {
         let v = "2.0.0".parse::<Version>().unwrap();
@@ -0,0 +1,194 @@
+use std::sync::Mutex;
+use tokio::sync::{AcquireError, Semaphore, SemaphorePermit};
+
+/// Concurrency limit that halves when the registry rate-limits us and creeps
+/// back up towards the configured maximum as requests succeed again
+pub struct AdaptiveLimiter {
+    semaphore: Semaphore,
+    max: usize,
+    state: Mutex<LimitState>,
+}
+
+struct LimitState {
+    limit: usize,
+    /// Permits still out on loan that must be forgotten, not returned, once
+    /// released so the semaphore shrinks to the lowered limit
+    debt: usize,
+    successes: usize,
+    /// Responses still due from the window that was last rate limited; the
+    /// rest of that window's 429s do not lower the limit again
+    settling: usize,
+}
+
+pub struct LimiterPermit<'a> {
+    limiter: &'a AdaptiveLimiter,
+    permit: Option<SemaphorePermit<'a>>,
+}
+
+impl AdaptiveLimiter {
+    pub fn new(max: usize) -> Self {
+        let max = max.max(1);
+        Self {
+            semaphore: Semaphore::new(max),
+            max,
+            state: Mutex::new(LimitState {
+                limit: max,
+                debt: 0,
+                successes: 0,
+                settling: 0,
+            }),
+        }
+    }
+
+    pub async fn acquire(&self) -> Result<LimiterPermit<'_>, AcquireError> {
+        let permit = self.semaphore.acquire().await?;
+        Ok(LimiterPermit {
+            limiter: self,
+            permit: Some(permit),
+        })
+    }
+
+    #[cfg(test)]
+    pub fn limit(&self) -> usize {
+        self.state.lock().unwrap().limit
+    }
+
+    /// Halve the effective concurrency (never below one request), at most
+    /// once per window of requests
+    pub fn on_rate_limited(&self) {
+        let mut state = self.state.lock().unwrap();
+        if state.settling > 0 {
+            state.settling -= 1;
+            return;
+        }
+        let lowered = (state.limit / 2).max(1);
+        let removed = state.limit - lowered;
+        // Up to the old limit of requests may have been sent in this window
+        state.settling = state.limit - 1;
+        state.limit = lowered;
+        state.successes = 0;
+
+        // Shrink right away where permits are idle, the rest as they come back
+        let mut owed = removed;
+        while owed > 0 {
+            match self.semaphore.try_acquire() {
+                Ok(permit) => {
+                    permit.forget();
+                    owed -= 1;
+                }
+                Err(_) => break,
+            }
+        }
+        state.debt += owed;
+    }
+
+    /// Raise the limit by one after a full window of successful requests
+    pub fn on_success(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.settling = state.settling.saturating_sub(1);
+        if state.limit >= self.max {
+            return;
+        }
+        state.successes += 1;
+        if state.successes < state.limit {
+            return;
+        }
+        state.successes = 0;
+        state.limit += 1;
+        if state.debt > 0 {
+            state.debt -= 1;
+        } else {
+            self.semaphore.add_permits(1);
+        }
+    }
+}
+
+impl Drop for LimiterPermit<'_> {
+    fn drop(&mut self) {
+        let Some(permit) = self.permit.take() else {
+            return;
+        };
+        let mut state = self.limiter.state.lock().unwrap();
+        if state.debt > 0 {
+            state.debt -= 1;
+            permit.forget();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_rate_limit_halves_available_permits() {
+        let limiter = AdaptiveLimiter::new(8);
+        limiter.on_rate_limited();
+        assert_eq!(limiter.limit(), 4);
+        assert_eq!(limiter.semaphore.available_permits(), 4);
+
+        // The rest of the window settles without another halving, then the
+        // next 429 halves again
+        for _ in 0..7 {
+            limiter.on_rate_limited();
+        }
+        assert_eq!(limiter.limit(), 4);
+        limiter.on_rate_limited();
+        assert_eq!(limiter.limit(), 2);
+        assert_eq!(limiter.semaphore.available_permits(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_rate_limits_halve_once() {
+        let limiter = AdaptiveLimiter::new(8);
+        let held: Vec<_> = futures::future::join_all((0..8).map(|_| limiter.acquire()))
+            .await
+            .into_iter()
+            .map(|p| p.unwrap())
+            .collect();
+
+        // Every request of the window comes back rate limited
+        for permit in held {
+            limiter.on_rate_limited();
+            drop(permit);
+        }
+        assert_eq!(limiter.limit(), 4);
+        assert_eq!(limiter.semaphore.available_permits(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_held_permits_are_forgotten_on_release() {
+        let limiter = AdaptiveLimiter::new(4);
+        let held: Vec<_> = futures::future::join_all((0..4).map(|_| limiter.acquire()))
+            .await
+            .into_iter()
+            .map(|p| p.unwrap())
+            .collect();
+
+        limiter.on_rate_limited();
+        assert_eq!(limiter.limit(), 2);
+        drop(held);
+        assert_eq!(limiter.semaphore.available_permits(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_successes_restore_limit() {
+        let limiter = AdaptiveLimiter::new(4);
+        limiter.on_rate_limited();
+        assert_eq!(limiter.limit(), 2);
+
+        for _ in 0..2 {
+            limiter.on_success();
+        }
+        assert_eq!(limiter.limit(), 3);
+        for _ in 0..3 {
+            limiter.on_success();
+        }
+        assert_eq!(limiter.limit(), 4);
+        assert_eq!(limiter.semaphore.available_permits(), 4);
+
+        // Already at the maximum
+        limiter.on_success();
+        assert_eq!(limiter.limit(), 4);
+    }
+}
@@ -0,0 +1,271 @@
+use node_semver::{Range, Version};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+
+use crate::json_edit;
+use crate::lockfile::Lockfile;
+use crate::semver_utils;
+use crate::types::{DependencyChain, PackageInfo, RegistryVersionInfo, TransitiveUpdate};
+
+/// An installed package that only the checked package's dependencies pull in
+#[derive(Debug, Clone)]
+pub struct Transitive {
+    pub name: String,
+    pub version: String,
+    pub dependents: Vec<DependencyChain>,
+}
+
+/// Walk the lockfile graph below the direct dependencies of `importer` (or of
+/// each package's own `workspace`),
+/// collecting every indirect package with the shortest chain from each direct
+/// dependency that reaches it
+pub fn collect(lockfile: &Lockfile, importer: &str, direct: &[PackageInfo]) -> Vec<Transitive> {
+    let roots: Vec<usize> = direct
+        .iter()
+        .filter(|pkg| json_edit::section_for_dep_type(&pkg.dep_type).is_some())
+        .filter_map(|pkg| {
+            let importer = pkg.workspace.as_deref().unwrap_or(importer);
+            lockfile.resolve(importer, &pkg.name, &pkg.version_range)
+        })
+        .collect();
+    let direct_ids: HashSet<usize> = roots.iter().copied().collect();
+    let label = |id: usize| {
+        let package = lockfile.package(id);
+        format!("{}@{}", package.name, package.version)
+    };
+
+    let mut found: BTreeMap<usize, Vec<DependencyChain>> = BTreeMap::new();
+    let mut seen_roots = HashSet::new();
+    for &root in &roots {
+        if !seen_roots.insert(root) {
+            continue;
+        }
+        // Breadth-first, so the first edge reaching a package is on a shortest path
+        let mut parents: HashMap<usize, usize> = HashMap::from([(root, root)]);
+        let mut queue = VecDeque::from([root]);
+        while let Some(id) = queue.pop_front() {
+            for dep in &lockfile.package(id).dependencies {
+                let Some(child) = dep.resolved else {
+                    continue;
+                };
+                if parents.contains_key(&child) {
+                    continue;
+                }
+                parents.insert(child, id);
+                queue.push_back(child);
+                if direct_ids.contains(&child) {
+                    continue;
+                }
+                let mut path = vec![label(id)];
+                let mut at = id;
+                while at != root {
+                    at = parents[&at];
+                    path.push(label(at));
+                }
+                path.reverse();
+                found.entry(child).or_default().push(DependencyChain {
+                    path,
+                    range: dep.range.clone(),
+                    allows_latest: None,
+                });
+            }
+        }
+    }
+
+    let mut transitive: Vec<Transitive> = found
+        .into_iter()
+        .map(|(id, dependents)| {
+            let package = lockfile.package(id);
+            Transitive {
+                name: package.name.clone(),
+                version: package.version.clone(),
+                dependents,
+            }
+        })
+        .collect();
+    transitive.sort_by(|a, b| a.name.cmp(&b.name).then_with(|| a.version.cmp(&b.version)));
+    transitive
+}
+
+/// Distinct package names, so each is fetched once however many copies exist
+pub fn names(transitive: &[Transitive]) -> Vec<String> {
+    let mut names: Vec<String> = transitive.iter().map(|t| t.name.clone()).collect();
+    names.dedup();
+    names
+}
+
+/// Keep the packages with a newer version under `target`, noting for each
+/// chain whether its dependent's range already allows it
+pub fn outdated(
+    transitive: Vec<Transitive>,
+    infos: &HashMap<String, RegistryVersionInfo>,
+    target: &str,
+    include_prerelease: bool,
+) -> Vec<TransitiveUpdate> {
+    transitive
+        .into_iter()
+        .filter_map(|t| {
+            let info = infos.get(&t.name)?;
+            let installed = t.version.parse::<Version>().ok()?;
+            let latest = semver_utils::resolve_target_version(
+                &t.version,
+                &info.versions,
+                target,
+                include_prerelease,
+            )?;
+            let dependents =
+                t.dependents
+                    .into_iter()
+                    .map(|chain| DependencyChain {
+                        allows_latest: chain.range.as_deref().map(|range| {
+                            range.parse::<Range>().is_ok_and(|r| r.satisfies(&latest))
+                        }),
+                        ..chain
+                    })
+                    .collect();
+            Some(TransitiveUpdate {
+                update_type: semver_utils::classify_update(&installed, &latest),
+                name: t.name,
+                installed: t.version,
+                latest: latest.to_string(),
+                dependents,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn direct(name: &str, range: &str) -> PackageInfo {
+        PackageInfo {
+            name: name.to_string(),
+            version_range: range.to_string(),
+            dep_type: "prod".to_string(),
+            pointer: None,
+            workspace: None,
+        }
+    }
+
+    fn lockfile() -> Lockfile {
+        let dir = std::env::temp_dir().join(format!("turbo-ncu-transitive-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("package-lock.json");
+        std::fs::write(
+            &path,
+            r#"{
+                "lockfileVersion": 3,
+                "packages": {
+                    "": { "dependencies": { "express": "^4.18.0", "debug": "^4.3.0" } },
+                    "node_modules/express": {
+                        "version": "4.18.2",
+                        "dependencies": { "debug": "2.6.9", "qs": "6.11.0", "body-parser": "1.20.1" }
+                    },
+                    "node_modules/express/node_modules/debug": {
+                        "version": "2.6.9",
+                        "dependencies": { "ms": "2.0.0" }
+                    },
+                    "node_modules/body-parser": {
+                        "version": "1.20.1",
+                        "dependencies": { "qs": "6.11.0" }
+                    },
+                    "node_modules/debug": { "version": "4.3.4", "dependencies": { "ms": "2.1.2" } },
+                    "node_modules/qs": { "version": "6.11.0" },
+                    "node_modules/ms": { "version": "2.1.2" },
+                    "node_modules/express/node_modules/ms": { "version": "2.0.0" }
+                }
+            }"#,
+        )
+        .unwrap();
+        let lockfile = Lockfile::read(&path.to_string_lossy()).unwrap();
+        let _ = std::fs::remove_dir_all(&dir);
+        lockfile
+    }
+
+    #[test]
+    fn test_collects_chains_below_direct_dependencies() {
+        let found = collect(
+            &lockfile(),
+            "",
+            &[direct("express", "^4.18.0"), direct("debug", "^4.3.0")],
+        );
+        let summary: Vec<(String, String, Vec<Vec<String>>)> = found
+            .iter()
+            .map(|t| {
+                let paths = t.dependents.iter().map(|c| c.path.clone()).collect();
+                (t.name.clone(), t.version.clone(), paths)
+            })
+            .collect();
+        let s = |v: &[&str]| v.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        assert_eq!(
+            summary,
+            vec![
+                (
+                    "body-parser".to_string(),
+                    "1.20.1".to_string(),
+                    vec![s(&["express@4.18.2"])]
+                ),
+                // express's nested copy; the hoisted one is a direct dependency
+                (
+                    "debug".to_string(),
+                    "2.6.9".to_string(),
+                    vec![s(&["express@4.18.2"])]
+                ),
+                (
+                    "ms".to_string(),
+                    "2.0.0".to_string(),
+                    vec![s(&["express@4.18.2", "debug@2.6.9"])]
+                ),
+                (
+                    "ms".to_string(),
+                    "2.1.2".to_string(),
+                    vec![s(&["debug@4.3.4"])]
+                ),
+                (
+                    "qs".to_string(),
+                    "6.11.0".to_string(),
+                    vec![s(&["express@4.18.2"])]
+                ),
+            ]
+        );
+        assert_eq!(names(&found), vec!["body-parser", "debug", "ms", "qs"]);
+    }
+
+    #[test]
+    fn test_reports_outdated_with_pinning_dependents() {
+        let found = collect(&lockfile(), "", &[direct("express", "^4.18.0")]);
+        let info = |name: &str, versions: &[&str]| RegistryVersionInfo {
+            name: name.to_string(),
+            versions: versions.iter().map(|v| v.to_string()).collect(),
+            registry: String::new(),
+            details: HashMap::new(),
+        };
+        let infos = HashMap::from([
+            ("qs".to_string(), info("qs", &["6.11.0", "6.13.0"])),
+            ("ms".to_string(), info("ms", &["2.0.0", "2.1.3"])),
+            ("debug".to_string(), info("debug", &["2.6.9"])),
+            ("body-parser".to_string(), info("body-parser", &["1.20.1"])),
+        ]);
+        let updates = outdated(found, &infos, "latest", false);
+        let summary: Vec<(&str, &str, &str, Vec<Option<bool>>)> = updates
+            .iter()
+            .map(|u| {
+                let allows = u.dependents.iter().map(|c| c.allows_latest).collect();
+                (
+                    u.name.as_str(),
+                    u.installed.as_str(),
+                    u.latest.as_str(),
+                    allows,
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("ms", "2.0.0", "2.1.3", vec![Some(false)]),
+                ("qs", "6.11.0", "6.13.0", vec![Some(false)]),
+            ]
+        );
+        assert_eq!(updates[1].update_type, "minor");
+    }
+}
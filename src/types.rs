@@ -1,4 +1,5 @@
 use napi_derive::napi;
+use std::collections::HashMap;
 
 #[napi(object)]
 #[derive(Debug, Clone)]
@@ -6,6 +7,13 @@ pub struct PackageInfo {
     pub name: String,
     pub version_range: String,
     pub dep_type: String,
+    /// JSON pointer to the range in package.json; needed to rewrite entries
+    /// outside the dependency sections, such as overrides
+    pub pointer: Option<String>,
+    /// Directory of the package's manifest relative to the lockfile, when one
+    /// check covers several manifests. Overrides `lockfile_importer` and tells
+    /// the occurrences of divergent ranges apart
+    pub workspace: Option<String>,
 }
 
 #[napi(object)]
@@ -19,6 +27,74 @@ pub struct CheckOptions {
     pub cache_ttl_seconds: Option<u32>,
     pub include_prerelease: Option<bool>,
     pub retries: Option<u32>,
+    /// Proxy for http:// registries; defaults to npm's `proxy` setting, then
+    /// `HTTP_PROXY`
+    pub proxy: Option<String>,
+    /// Proxy for https:// registries; defaults to npm's `https-proxy` setting,
+    /// then `HTTPS_PROXY`, then `proxy`
+    pub https_proxy: Option<String>,
+    /// Comma-separated hosts that bypass the proxy; defaults to npm's `noproxy`
+    /// setting, then `NO_PROXY`
+    pub no_proxy: Option<String>,
+    /// PEM bundle of extra trusted CA certificates; defaults to npm's `cafile`
+    pub ca_file: Option<String>,
+    /// Set to false to skip TLS certificate validation; defaults to npm's
+    /// `strict-ssl`, then true
+    pub strict_ssl: Option<bool>,
+    /// PEM client certificate for mutual TLS; requires `key_file`
+    pub cert_file: Option<String>,
+    /// PEM private key matching `cert_file`
+    pub key_file: Option<String>,
+    /// Registries tried in order after `registry` when it fails
+    pub fallback_registries: Option<Vec<String>>,
+    /// Failures that move on to the next registry: "5xx", "timeout", "404",
+    /// "429". Defaults to ["5xx", "timeout", "404"]
+    pub failover_on: Option<Vec<String>>,
+    /// "record" saves every registry response to `fixture_dir`; "replay"
+    /// serves responses from it without touching the network
+    pub fixture_mode: Option<String>,
+    pub fixture_dir: Option<String>,
+    /// Fetch full packuments instead of the abbreviated install form, so each
+    /// update carries per-version `metadata`. Responses are several times larger
+    pub full_metadata: Option<bool>,
+    /// SPDX identifiers or expressions an upgrade may move to; versions under
+    /// any other (or no) license are skipped. Implies `full_metadata`
+    pub allowed_licenses: Option<Vec<String>>,
+    /// Look up security advisories for the current and target versions via
+    /// the registry's bulk advisory endpoint. Implied by the "security" target,
+    /// which picks the lowest version no advisory affects
+    pub check_advisories: Option<bool>,
+    /// package-lock.json, pnpm-lock.yaml, yarn.lock or bun.lock to read
+    /// installed versions from; updates are then classified against those
+    pub lockfile: Option<String>,
+    /// Directory of the checked package relative to the lockfile, for
+    /// workspaces. Defaults to the root
+    pub lockfile_importer: Option<String>,
+    /// Also report outdated packages installed below the direct dependencies;
+    /// requires `lockfile`
+    pub include_transitive: Option<bool>,
+    /// Align every package several manifests depend on to one version:
+    /// "highest" (the highest version any of them declares), "latest" or
+    /// "pinned" (from `sync_versions`)
+    pub sync: Option<String>,
+    /// Versions for the "pinned" sync policy, by package name
+    pub sync_versions: Option<HashMap<String, String>>,
+    /// Project directory whose node_modules are compared with the declared
+    /// ranges; packages with a `workspace` resolve from that directory below it
+    pub install_root: Option<String>,
+    /// Batch updates of related packages into `groups`: packages with the same
+    /// scope or, with full metadata, the same repository
+    pub group: Option<bool>,
+    /// Packages that are always upgraded together; implies `group`
+    pub group_rules: Option<Vec<GroupRule>>,
+}
+
+#[napi(object)]
+#[derive(Debug, Clone)]
+pub struct GroupRule {
+    pub name: String,
+    /// Package names; `*` matches any run of characters, as in "@babel/*"
+    pub packages: Vec<String>,
 }
 
 #[napi(object)]
@@ -27,20 +103,271 @@ pub struct UpdateResult {
     pub name: String,
     pub current: String,
     pub current_version: String,
+    /// Version the lockfile has installed; only set with `lockfile`
+    pub installed_version: Option<String>,
+    /// Whether the target lies outside the declared range, so the manifest
+    /// must change; otherwise a lockfile refresh (`npm update`) is enough.
+    /// Always set by checks; unset counts as true
+    pub requires_manifest_change: Option<bool>,
     pub latest: String,
     pub new_range: String,
     pub update_type: String,
     pub dep_type: String,
+    /// Location of the range in package.json, copied from the package
+    pub pointer: Option<String>,
+    /// Workspace of the package, copied from it
+    pub workspace: Option<String>,
+    /// Registry that supplied the version list
+    pub registry: Option<String>,
+    /// Details of the target version, when its packument was fetched in full
+    /// (see `full_metadata`)
+    pub metadata: Option<VersionDetails>,
+    /// License of the version the current range resolves to
+    pub license_from: Option<String>,
+    /// License of the target version
+    pub license_to: Option<String>,
+    /// Whether the upgrade changes the license; only set when license metadata
+    /// was fetched
+    pub license_changed: Option<bool>,
+    /// Advisories affecting the current version but not the target; only set
+    /// with `check_advisories`
+    pub fixed_vulnerabilities: Option<Vec<Vulnerability>>,
+    /// Advisories affecting the target version but not the current one
+    pub introduced_vulnerabilities: Option<Vec<Vulnerability>>,
+}
+
+impl UpdateResult {
+    /// `requires_manifest_change`, counting updates that do not say as manifest
+    /// changes
+    pub fn requires_manifest_change(&self) -> bool {
+        self.requires_manifest_change.unwrap_or(true)
+    }
+}
+
+#[cfg(test)]
+impl UpdateResult {
+    /// A "prod" dependency update of `name` from `current` to `new_range`
+    pub fn for_test(name: &str, current: &str, new_range: &str) -> Self {
+        let current_version = crate::semver_utils::parse_base_version(current);
+        let latest = crate::semver_utils::parse_base_version(new_range);
+        let update_type = match (&current_version, &latest) {
+            (Some(from), Some(to)) => crate::semver_utils::classify_update(from, to),
+            _ => "unknown".to_string(),
+        };
+        UpdateResult {
+            name: name.to_string(),
+            current: current.to_string(),
+            current_version: current_version.map(|v| v.to_string()).unwrap_or_default(),
+            installed_version: None,
+            requires_manifest_change: None,
+            latest: latest.map(|v| v.to_string()).unwrap_or_default(),
+            new_range: new_range.to_string(),
+            update_type,
+            dep_type: "prod".to_string(),
+            pointer: None,
+            workspace: None,
+            registry: None,
+            metadata: None,
+            license_from: None,
+            license_to: None,
+            license_changed: None,
+            fixed_vulnerabilities: None,
+            introduced_vulnerabilities: None,
+        }
+    }
+}
+
+#[napi(object)]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Vulnerability {
+    pub id: i64,
+    pub title: String,
+    /// "low", "moderate", "high" or "critical"
+    pub severity: String,
+    pub url: String,
+    /// Affected range, e.g. "<4.17.21"
+    pub vulnerable_versions: String,
 }
 
 #[napi(object)]
 #[derive(Debug, Clone)]
 pub struct CheckResult {
     pub updates: Vec<UpdateResult>,
+    /// Updates the declared range already allows, for packages whose installed
+    /// version is known from `lockfile`; installing them only needs a
+    /// lockfile refresh
+    pub in_range: Vec<UpdateResult>,
     pub cache_hits: u32,
     pub cache_misses: u32,
     pub fetch_time_ms: f64,
     pub total_time_ms: f64,
+    /// True when the check was aborted before every package was fetched
+    pub cancelled: bool,
+    /// Outdated indirect dependencies; only filled with `includeTransitive`
+    pub transitive: Vec<TransitiveUpdate>,
+    /// Packages declared with different ranges across the checked manifests
+    pub divergences: Vec<Divergence>,
+    /// Differences between node_modules and the declared ranges; only filled
+    /// with `install_root`
+    pub installed: Vec<InstalledIssue>,
+    /// Updates of related packages, moved to one shared version; only filled
+    /// with `group` or `group_rules`
+    pub groups: Vec<UpdateGroup>,
+}
+
+#[napi(object)]
+#[derive(Debug, Clone)]
+pub struct UpdateGroup {
+    /// Rule name, scope (e.g. "@babel") or repository URL
+    pub name: String,
+    /// "rule", "repository" or "scope"
+    pub source: String,
+    /// Version every member moves to; unset when they share none, in which
+    /// case each keeps its own target
+    pub version: Option<String>,
+    /// Whether some member cannot reach the highest target of the group, so
+    /// all of them move to a lower common version
+    pub fell_back: bool,
+    /// Updates of the members, also listed in `updates` or `in_range`
+    pub updates: Vec<UpdateResult>,
+}
+
+#[napi(object)]
+#[derive(Debug, Clone)]
+pub struct InstalledIssue {
+    pub name: String,
+    /// "missing", "mismatched" (installed outside the declared range) or
+    /// "extraneous" (installed, but nothing checked depends on it)
+    pub kind: String,
+    /// Declared range; not set for extraneous packages
+    pub range: Option<String>,
+    /// Version found on disk; not set for missing packages
+    pub installed: Option<String>,
+    pub dep_type: Option<String>,
+    pub workspace: Option<String>,
+    /// Directory the package is installed in, or would be
+    pub path: String,
+}
+
+#[napi(object)]
+#[derive(Debug, Clone)]
+pub struct Divergence {
+    pub name: String,
+    pub occurrences: Vec<Occurrence>,
+    /// Version every occurrence is aligned to; only set with `sync`
+    pub agreed: Option<String>,
+}
+
+#[napi(object)]
+#[derive(Debug, Clone)]
+pub struct Occurrence {
+    pub workspace: Option<String>,
+    pub range: String,
+    pub dep_type: String,
+}
+
+#[napi(object)]
+#[derive(Debug, Clone)]
+pub struct TransitiveUpdate {
+    pub name: String,
+    pub installed: String,
+    pub latest: String,
+    pub update_type: String,
+    /// How the installed copy is reached from the checked package's direct
+    /// dependencies
+    pub dependents: Vec<DependencyChain>,
+}
+
+#[napi(object)]
+#[derive(Debug, Clone)]
+pub struct DependencyChain {
+    /// `name@version` of each package from a direct dependency down to the one
+    /// that depends on this package
+    pub path: Vec<String>,
+    /// Range that last package declares; pnpm lockfiles do not record it
+    pub range: Option<String>,
+    /// Whether that range allows `latest`; when false, the first package of
+    /// `path` has to be upgraded to get it
+    pub allows_latest: Option<bool>,
+}
+
+#[napi(object)]
+#[derive(Debug, Clone)]
+pub struct ProgressEvent {
+    /// One of "cache-hit", "start", "retry", "success" or "failure"
+    pub kind: String,
+    pub name: String,
+    pub completed: u32,
+    pub total: u32,
+    pub attempt: u32,
+    pub bytes: u32,
+}
+
+/// Per-version metadata only present in full packuments
+#[napi(object)]
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct VersionDetails {
+    /// Publish time as an ISO 8601 timestamp
+    pub published: Option<String>,
+    /// Repository URL
+    pub repository: Option<String>,
+    pub homepage: Option<String>,
+    /// SPDX expression, or the legacy `licenses` list joined with " OR "
+    pub license: Option<String>,
+    /// Deprecation message
+    pub deprecated: Option<String>,
+    pub engines: Option<HashMap<String, String>>,
+    pub peer_dependencies: Option<HashMap<String, String>>,
+    pub unpacked_size: Option<u32>,
+    pub integrity: Option<String>,
+}
+
+/// One dependency entry of a package.json
+#[napi(object)]
+#[derive(Debug, Clone, PartialEq)]
+pub struct ManifestDependency {
+    /// Key in the manifest; for aliases this is the alias, not the package, and
+    /// for overrides the full selector such as "foo>bar" or "pkg@^2"
+    pub name: String,
+    /// Value as written, e.g. "^1.2.3" or "npm:strip-ansi@^6.0.1"
+    pub specifier: String,
+    /// "prod", "dev", "peer", "optional", "override", "bundle" or "devEngines"
+    pub dep_type: String,
+    /// JSON pointer to the specifier in the manifest
+    pub pointer: String,
+    /// "registry", "alias", "git", "file", "link", "workspace", "catalog",
+    /// "tag" or "url"
+    pub kind: String,
+    /// Registry package to look up, when checkable
+    pub package_name: Option<String>,
+    /// Semver range to resolve, when checkable
+    pub range: Option<String>,
+    /// Whether `check_updates` can look this dependency up
+    pub checkable: bool,
+}
+
+#[napi(object)]
+#[derive(Debug, Clone)]
+pub struct Manifest {
+    pub name: Option<String>,
+    pub version: Option<String>,
+    /// Every dependency, in section then source order
+    pub dependencies: Vec<ManifestDependency>,
+}
+
+/// One entry of a pnpm catalog
+#[napi(object)]
+#[derive(Debug, Clone)]
+pub struct CatalogEntry {
+    /// Catalog name; "default" for the top-level `catalog:`
+    pub catalog: String,
+    pub name: String,
+    pub specifier: String,
+    /// Location in pnpm-workspace.yaml, e.g. "/catalogs/react17/react"; pass it
+    /// on as the package's `pointer` so upgrades can be written back
+    pub pointer: String,
+    /// Names of the workspaces that reference this entry
+    pub consumers: Vec<String>,
 }
 
 // Internal types not exposed via napi
@@ -48,6 +375,12 @@ pub struct CheckResult {
 pub struct RegistryVersionInfo {
     pub name: String,
     pub versions: Vec<String>,
+    /// Base URL of the registry that answered
+    #[serde(default)]
+    pub registry: String,
+    /// Details by version; empty unless a full packument was fetched
+    #[serde(default)]
+    pub details: HashMap<String, VersionDetails>,
 }
 
 impl Default for CheckOptions {
@@ -61,6 +394,235 @@ impl Default for CheckOptions {
             cache_ttl_seconds: Some(600),
             include_prerelease: Some(false),
             retries: Some(3),
+            proxy: None,
+            https_proxy: None,
+            no_proxy: None,
+            ca_file: None,
+            strict_ssl: None,
+            cert_file: None,
+            key_file: None,
+            fallback_registries: None,
+            failover_on: None,
+            fixture_mode: None,
+            fixture_dir: None,
+            full_metadata: Some(false),
+            allowed_licenses: None,
+            check_advisories: Some(false),
+            lockfile: None,
+            lockfile_importer: None,
+            include_transitive: Some(false),
+            sync: None,
+            sync_versions: None,
+            install_root: None,
+            group: Some(false),
+            group_rules: None,
         }
     }
 }
+
+#[napi(object)]
+#[derive(Debug, Clone)]
+pub struct WorkspaceGraph {
+    /// Directory discovery started from
+    pub root: String,
+    /// Which configurations declared workspaces: "npm" (package.json
+    /// `workspaces`, also used by yarn and bun), "pnpm", "lerna", "nx" or "rush"
+    pub sources: Vec<String>,
+    /// The root package, when it has a package.json, and every workspace,
+    /// ordered by path
+    pub packages: Vec<WorkspacePackage>,
+    /// Dependencies of one workspace on another
+    pub edges: Vec<WorkspaceEdge>,
+}
+
+#[napi(object)]
+#[derive(Debug, Clone)]
+pub struct WorkspacePackage {
+    /// Package name, or its path when the manifest has none
+    pub name: String,
+    /// Directory relative to the root with `/` separators; "" for the root
+    pub path: String,
+    pub dir: String,
+    pub manifest_path: String,
+    pub version: Option<String>,
+}
+
+#[napi(object)]
+#[derive(Debug, Clone)]
+pub struct WorkspaceEdge {
+    pub from: String,
+    pub to: String,
+    /// Value as written, e.g. "workspace:^" or "^1.2.0"
+    pub specifier: String,
+    pub dep_type: String,
+    /// JSON pointer to the specifier in the dependent's manifest
+    pub pointer: String,
+    /// Whether the specifier allows the workspace's current version, so the
+    /// package manager links it instead of installing from the registry
+    pub consistent: bool,
+}
+
+#[napi(object)]
+#[derive(Debug, Clone)]
+pub struct GlobalPackage {
+    pub name: String,
+    /// Version installed on disk
+    pub version: String,
+    /// "npm", "pnpm", "yarn" or "bun"
+    pub manager: String,
+    /// Directory the package is installed in
+    pub path: String,
+}
+
+#[napi(object)]
+#[derive(Debug, Clone, Default)]
+pub struct DoctorOptions {
+    /// Command that installs dependencies; defaults to "npm install"
+    pub install_command: Option<String>,
+    /// Command that runs the tests; defaults to "npm test"
+    pub test_command: Option<String>,
+    /// Directory the commands run in; defaults to the manifest's
+    pub cwd: Option<String>,
+    /// How upgrades are retried when applying all of them fails: "iterate"
+    /// (one at a time, in order) or "bisect" (halving each failing group).
+    /// Defaults to "iterate"
+    pub strategy: Option<String>,
+}
+
+#[napi(object)]
+#[derive(Debug, Clone)]
+pub struct DoctorReport {
+    /// Upgrades kept in the manifest
+    pub passed: Vec<UpdateResult>,
+    /// Upgrades reverted because the install or the tests failed with them
+    pub failed: Vec<DoctorFailure>,
+    /// Install and test runs made, the baseline included
+    pub runs: u32,
+}
+
+#[napi(object)]
+#[derive(Debug, Clone)]
+pub struct DoctorFailure {
+    pub update: UpdateResult,
+    /// Combined stdout and stderr of the failing run
+    pub output: String,
+}
+
+#[napi(object)]
+#[derive(Debug, Clone)]
+pub struct DoctorStep {
+    /// "baseline", "all", "single", "group" or "restore" (the final install
+    /// of the kept upgrades)
+    pub kind: String,
+    /// Packages tried in this run, on top of the upgrades kept so far
+    pub packages: Vec<String>,
+    /// Unset when the run starts
+    pub passed: Option<bool>,
+}
+
+/// The parts of a `CheckResult` an update plan is built from; a whole
+/// `CheckResult` can be passed
+#[napi(object)]
+#[derive(Debug, Clone)]
+pub struct PlanInput {
+    pub updates: Vec<UpdateResult>,
+    pub groups: Option<Vec<UpdateGroup>>,
+}
+
+#[napi(object)]
+#[derive(Debug, Clone, Default)]
+pub struct PlanOptions {
+    /// Manifest of updates without a `workspace`, relative to the plan's
+    /// root; defaults to "package.json". Workspace updates use
+    /// `<workspace>/package.json`
+    pub manifest: Option<String>,
+    /// pnpm-workspace.yaml holding catalog updates; defaults to
+    /// "pnpm-workspace.yaml"
+    pub catalog: Option<String>,
+    /// Prefix of the suggested branch names; defaults to "turbo-ncu/"
+    pub branch_prefix: Option<String>,
+}
+
+/// A versioned, self-contained description of the manifest edits a check
+/// suggests, split into batches that can each become one pull request
+#[napi(object)]
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdatePlan {
+    /// Format version of the document; readers reject versions they do not know
+    pub version: u32,
+    /// One batch per update group and per ungrouped package, ordered by id
+    pub batches: Vec<PlanBatch>,
+}
+
+#[napi(object)]
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlanBatch {
+    /// Stable identifier, e.g. "lodash" or "group-babel"
+    pub id: String,
+    /// Name of the update group, for batches of one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
+    /// Suggested branch name
+    pub branch: String,
+    /// Suggested commit and pull request title
+    pub commit_title: String,
+    pub packages: Vec<PlanPackage>,
+    pub edits: Vec<PlanEdit>,
+}
+
+#[napi(object)]
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlanPackage {
+    pub name: String,
+    /// Version upgraded to
+    pub version: String,
+    pub update_type: String,
+    /// npm page of `version`
+    pub npm_url: String,
+    /// Source repository; only known with full metadata
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub repository_url: Option<String>,
+    /// Release notes of the repository, for GitHub and GitLab repositories
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub releases_url: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub homepage: Option<String>,
+}
+
+#[napi(object)]
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlanEdit {
+    /// File to edit, relative to the plan's root with `/` separators
+    pub path: String,
+    /// JSON pointer to the value, also used for pnpm-workspace.yaml catalogs
+    pub pointer: String,
+    /// Value the edit expects to find
+    pub old_value: String,
+    pub new_value: String,
+}
+
+#[napi(object)]
+#[derive(Debug, Clone, Default)]
+pub struct ApplyPlanOptions {
+    /// Directory the plan's paths are relative to; defaults to the working
+    /// directory
+    pub root: Option<String>,
+    /// Ids of the batches to apply; defaults to all of them
+    pub batches: Option<Vec<String>>,
+}
+
+#[napi(object)]
+#[derive(Debug, Clone)]
+pub struct AppliedPlan {
+    /// Edits written
+    pub applied: u32,
+    /// Edits whose new value was already in place
+    pub unchanged: u32,
+    /// Edits skipped because the file holds neither their old nor their new
+    /// value
+    pub conflicts: Vec<PlanEdit>,
+}
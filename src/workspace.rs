@@ -0,0 +1,566 @@
+use node_semver::{Range, Version};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use crate::lockfile::strip_trailing_commas;
+use crate::manifest::{self, Specifier};
+use crate::types::{WorkspaceEdge, WorkspaceGraph, WorkspacePackage};
+
+/// Directories never searched for workspaces
+const SKIPPED_DIRS: [&str; 2] = ["node_modules", ".git"];
+
+/// Find the workspaces of the monorepo at `root` and the dependencies between
+/// them. Patterns come from package.json `workspaces`, pnpm-workspace.yaml,
+/// lerna.json and nx.json, and projects from rush.json; workspaces that declare
+/// `workspaces` of their own are searched in turn
+pub fn discover(root: &str) -> Result<WorkspaceGraph, String> {
+    let root_dir = Path::new(root);
+    let mut dirs: Vec<PathBuf> = Vec::new();
+    let mut sources: Vec<String> = Vec::new();
+    let mut searched: HashSet<PathBuf> = HashSet::new();
+    let mut queue = vec![root_dir.to_path_buf()];
+
+    while let Some(base) = queue.pop() {
+        if !searched.insert(base.clone()) {
+            continue;
+        }
+        let is_root = base == root_dir;
+        let mut patterns = package_json_patterns(&base)?;
+        if !patterns.is_empty() && is_root {
+            sources.push("npm".to_string());
+        }
+        if is_root {
+            for (source, found) in [
+                ("pnpm", pnpm_patterns(&base)?),
+                ("lerna", lerna_patterns(&base)?),
+                ("nx", nx_patterns(&base)?),
+            ] {
+                if !found.is_empty() {
+                    sources.push(source.to_string());
+                    patterns.extend(found);
+                }
+            }
+        }
+
+        let mut found = expand_patterns(&base, &patterns);
+        if is_root {
+            let projects = rush_projects(&base)?;
+            if !projects.is_empty() {
+                sources.push("rush".to_string());
+                found.extend(projects);
+            }
+        }
+        for dir in found {
+            if dir != base
+                && dir != root_dir
+                && dir.join("package.json").is_file()
+                && !dirs.contains(&dir)
+            {
+                queue.push(dir.clone());
+                dirs.push(dir);
+            }
+        }
+    }
+
+    let mut packages = Vec::new();
+    let mut manifests = Vec::new();
+    if root_dir.join("package.json").is_file() {
+        dirs.push(root_dir.to_path_buf());
+    }
+    for dir in dirs {
+        let manifest_path = dir.join("package.json").to_string_lossy().to_string();
+        let manifest = manifest::read_manifest(&manifest_path)?;
+        let path = relative_path(root_dir, &dir);
+        packages.push(WorkspacePackage {
+            name: manifest.name.clone().unwrap_or_else(|| path.clone()),
+            path,
+            dir: dir.to_string_lossy().to_string(),
+            manifest_path,
+            version: manifest.version.clone(),
+        });
+        manifests.push(manifest);
+    }
+
+    let versions: BTreeMap<&str, Option<&str>> = packages
+        .iter()
+        .map(|p| (p.name.as_str(), p.version.as_deref()))
+        .collect();
+    let mut edges = Vec::new();
+    for (package, manifest) in packages.iter().zip(&manifests) {
+        for dep in &manifest.dependencies {
+            let Some(&version) = versions.get(dep.name.as_str()) else {
+                continue;
+            };
+            if !matches!(dep.dep_type.as_str(), "prod" | "dev" | "peer" | "optional") {
+                continue;
+            }
+            let Some(consistent) = consistent(&dep.specifier, version) else {
+                continue;
+            };
+            edges.push(WorkspaceEdge {
+                from: package.name.clone(),
+                to: dep.name.clone(),
+                specifier: dep.specifier.clone(),
+                dep_type: dep.dep_type.clone(),
+                pointer: dep.pointer.clone(),
+                consistent,
+            });
+        }
+    }
+
+    packages.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(WorkspaceGraph {
+        root: root_dir.to_string_lossy().to_string(),
+        sources,
+        packages,
+        edges,
+    })
+}
+
+/// Whether a reference to a workspace at `version` resolves to that workspace.
+/// `workspace:` ranges and plain ranges must allow its version; `*`, `^` and
+/// `~` always do. None for references that point elsewhere (git, tags, aliases)
+fn consistent(specifier: &str, version: Option<&str>) -> Option<bool> {
+    let range = match Specifier::parse(specifier) {
+        Specifier::Workspace => {
+            let range = specifier.trim().trim_start_matches("workspace:");
+            if matches!(range, "*" | "^" | "~" | "") || range.contains(['/', '@']) {
+                return Some(true);
+            }
+            range.to_string()
+        }
+        Specifier::Registry { range } => range,
+        Specifier::Link | Specifier::File => return Some(true),
+        _ => return None,
+    };
+    let version = version.and_then(|v| v.parse::<Version>().ok());
+    Some(match (range.parse::<Range>(), version) {
+        (Ok(range), Some(version)) => range.satisfies(&version),
+        // A workspace without a version only matches an unconstrained range
+        (_, None) => matches!(range.trim(), "*" | "x"),
+        (Err(_), _) => false,
+    })
+}
+
+fn relative_path(root: &Path, dir: &Path) -> String {
+    let relative = dir.strip_prefix(root).unwrap_or(dir);
+    relative
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn read_json(path: &Path, jsonc: bool) -> Result<Option<Value>, String> {
+    if !path.is_file() {
+        return Ok(None);
+    }
+    let display = path.display();
+    let mut text =
+        std::fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", display, e))?;
+    if jsonc {
+        text = strip_trailing_commas(&strip_comments(&text));
+    }
+    serde_json::from_str(&text)
+        .map(Some)
+        .map_err(|e| format!("Failed to parse {}: {}", display, e))
+}
+
+fn strings(value: Option<&Value>) -> Vec<String> {
+    value
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(|v| v.as_str().map(String::from))
+        .collect()
+}
+
+/// `workspaces` as an array, or yarn's `{ packages: [...] }`
+fn package_json_patterns(dir: &Path) -> Result<Vec<String>, String> {
+    let Some(manifest) = read_json(&dir.join("package.json"), false)? else {
+        return Ok(Vec::new());
+    };
+    let workspaces = manifest.get("workspaces");
+    Ok(strings(
+        workspaces.and_then(|w| w.get("packages")).or(workspaces),
+    ))
+}
+
+fn pnpm_patterns(dir: &Path) -> Result<Vec<String>, String> {
+    let path = dir.join("pnpm-workspace.yaml");
+    if !path.is_file() {
+        return Ok(Vec::new());
+    }
+    let text = std::fs::read_to_string(&path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let root: serde_yaml::Value = serde_yaml::from_str(&text)
+        .map_err(|e| format!("Failed to parse {}: {}", path.display(), e))?;
+    Ok(root
+        .get("packages")
+        .and_then(serde_yaml::Value::as_sequence)
+        .into_iter()
+        .flatten()
+        .filter_map(|v| v.as_str().map(String::from))
+        .collect())
+}
+
+/// lerna.json `packages`, defaulting to `packages/*` as lerna does
+fn lerna_patterns(dir: &Path) -> Result<Vec<String>, String> {
+    let Some(lerna) = read_json(&dir.join("lerna.json"), false)? else {
+        return Ok(Vec::new());
+    };
+    match lerna.get("packages") {
+        Some(packages) => Ok(strings(Some(packages))),
+        None => Ok(vec!["packages/*".to_string()]),
+    }
+}
+
+/// Project directories of nx's `workspaceLayout`
+fn nx_patterns(dir: &Path) -> Result<Vec<String>, String> {
+    let Some(nx) = read_json(&dir.join("nx.json"), true)? else {
+        return Ok(Vec::new());
+    };
+    let Some(layout) = nx.get("workspaceLayout") else {
+        return Ok(Vec::new());
+    };
+    Ok(["appsDir", "libsDir"]
+        .iter()
+        .filter_map(|key| layout.get(key)?.as_str())
+        .map(|dir| format!("{}/**", dir.trim_end_matches('/')))
+        .collect())
+}
+
+/// rush.json lists each project's folder explicitly
+fn rush_projects(dir: &Path) -> Result<Vec<PathBuf>, String> {
+    let Some(rush) = read_json(&dir.join("rush.json"), true)? else {
+        return Ok(Vec::new());
+    };
+    Ok(rush
+        .get("projects")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(|project| project.get("projectFolder")?.as_str())
+        .map(|folder| dir.join(folder))
+        .collect())
+}
+
+/// Directories under `base` matched by `patterns`, minus those matched by a
+/// `!`-negated pattern, in pattern order
+fn expand_patterns(base: &Path, patterns: &[String]) -> Vec<PathBuf> {
+    let mut included: Vec<PathBuf> = Vec::new();
+    let mut excluded: HashSet<PathBuf> = HashSet::new();
+    for pattern in patterns {
+        let (negated, pattern) = match pattern.strip_prefix('!') {
+            Some(rest) => (true, rest),
+            None => (false, pattern.as_str()),
+        };
+        for pattern in expand_braces(pattern) {
+            let segments: Vec<&str> = pattern
+                .split('/')
+                .filter(|s| !s.is_empty() && *s != ".")
+                .collect();
+            let mut matches = Vec::new();
+            walk(base.to_path_buf(), &segments, &mut matches);
+            if negated {
+                excluded.extend(matches);
+            } else {
+                for dir in matches {
+                    if !included.contains(&dir) {
+                        included.push(dir);
+                    }
+                }
+            }
+        }
+    }
+    included.retain(|dir| !excluded.contains(dir));
+    included
+}
+
+fn walk(dir: PathBuf, segments: &[&str], matches: &mut Vec<PathBuf>) {
+    let Some((segment, rest)) = segments.split_first() else {
+        matches.push(dir);
+        return;
+    };
+    if *segment == "**" {
+        walk(dir.clone(), rest, matches);
+        for child in subdirectories(&dir) {
+            walk(child, segments, matches);
+        }
+    } else if *segment == ".." || !segment.contains(['*', '?', '[']) {
+        let child = dir.join(segment);
+        if child.is_dir() {
+            walk(child, rest, matches);
+        }
+    } else {
+        for child in subdirectories(&dir) {
+            let name = child.file_name().unwrap_or_default().to_string_lossy();
+            if wildcard_match(segment, &name) {
+                walk(child, rest, matches);
+            }
+        }
+    }
+}
+
+fn subdirectories(dir: &Path) -> Vec<PathBuf> {
+    let mut children: Vec<PathBuf> = std::fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter(|entry| entry.file_type().is_ok_and(|t| t.is_dir()))
+        .filter(|entry| {
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            !name.starts_with('.') && !SKIPPED_DIRS.contains(&name.as_ref())
+        })
+        .map(|entry| entry.path())
+        .collect();
+    children.sort();
+    children
+}
+
+/// Match one path segment against `*`, `?` and `[...]` wildcards
+pub fn wildcard_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    fn matches(p: &[char], n: &[char]) -> bool {
+        match p.first() {
+            None => n.is_empty(),
+            Some('*') => (0..=n.len()).any(|i| matches(&p[1..], &n[i..])),
+            Some('?') => !n.is_empty() && matches(&p[1..], &n[1..]),
+            Some('[') => {
+                let Some(close) = p.iter().position(|c| *c == ']') else {
+                    return n.first() == Some(&'[') && matches(&p[1..], &n[1..]);
+                };
+                let Some(c) = n.first() else {
+                    return false;
+                };
+                let class = &p[1..close];
+                let (negate, class) = match class.first() {
+                    Some('!' | '^') => (true, &class[1..]),
+                    _ => (false, class),
+                };
+                let mut hit = false;
+                let mut i = 0;
+                while i < class.len() {
+                    if i + 2 < class.len() && class[i + 1] == '-' {
+                        hit |= (class[i]..=class[i + 2]).contains(c);
+                        i += 3;
+                    } else {
+                        hit |= class[i] == *c;
+                        i += 1;
+                    }
+                }
+                hit != negate && matches(&p[close + 1..], &n[1..])
+            }
+            Some(c) => n.first() == Some(c) && matches(&p[1..], &n[1..]),
+        }
+    }
+    matches(&pattern, &name)
+}
+
+/// Expand the first `{a,b}` group, recursively
+fn expand_braces(pattern: &str) -> Vec<String> {
+    let (Some(open), Some(close)) = (pattern.find('{'), pattern.find('}')) else {
+        return vec![pattern.to_string()];
+    };
+    if close < open {
+        return vec![pattern.to_string()];
+    }
+    pattern[open + 1..close]
+        .split(',')
+        .flat_map(|alt| {
+            expand_braces(&format!(
+                "{}{}{}",
+                &pattern[..open],
+                alt,
+                &pattern[close + 1..]
+            ))
+        })
+        .collect()
+}
+
+/// Remove `//` and `/* */` comments outside strings, as allowed in rush.json
+/// and nx.json
+fn strip_comments(text: &str) -> String {
+    let mut output = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    let mut in_string = false;
+    let mut escaped = false;
+    while let Some(c) = chars.next() {
+        if in_string {
+            in_string = c != '"' || escaped;
+            escaped = c == '\\' && !escaped;
+            output.push(c);
+            continue;
+        }
+        match (c, chars.peek()) {
+            ('/', Some('/')) => {
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        output.push('\n');
+                        break;
+                    }
+                }
+            }
+            ('/', Some('*')) => {
+                chars.next();
+                let mut previous = ' ';
+                for c in chars.by_ref() {
+                    if previous == '*' && c == '/' {
+                        break;
+                    }
+                    previous = c;
+                }
+            }
+            _ => {
+                in_string = c == '"';
+                output.push(c);
+            }
+        }
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Fixture(PathBuf);
+
+    impl Fixture {
+        fn new(name: &str) -> Self {
+            let dir = std::env::temp_dir().join(format!(
+                "turbo-ncu-workspace-{}-{}",
+                name,
+                std::process::id()
+            ));
+            let _ = std::fs::remove_dir_all(&dir);
+            std::fs::create_dir_all(&dir).unwrap();
+            Fixture(dir)
+        }
+
+        fn write(&self, path: &str, text: &str) -> &Self {
+            let path = self.0.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, text).unwrap();
+            self
+        }
+
+        fn root(&self) -> String {
+            self.0.to_string_lossy().to_string()
+        }
+    }
+
+    impl Drop for Fixture {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn paths(graph: &WorkspaceGraph) -> Vec<&str> {
+        graph.packages.iter().map(|p| p.path.as_str()).collect()
+    }
+
+    #[test]
+    fn test_npm_workspaces_with_negation_and_nesting() {
+        let fixture = Fixture::new("npm");
+        fixture
+            .write(
+                "package.json",
+                r#"{ "name": "root", "workspaces": ["packages/*", "!packages/legacy", "tools"] }"#,
+            )
+            .write(
+                "packages/a/package.json",
+                r#"{ "name": "a", "version": "1.2.0", "dependencies": { "b": "^2.0.0" } }"#,
+            )
+            .write(
+                "packages/b/package.json",
+                r#"{ "name": "b", "version": "1.0.0", "devDependencies": { "a": "workspace:^" } }"#,
+            )
+            .write("packages/legacy/package.json", r#"{ "name": "legacy" }"#)
+            .write("packages/no-manifest/README.md", "")
+            .write(
+                "packages/a/node_modules/x/package.json",
+                r#"{ "name": "x" }"#,
+            )
+            .write(
+                "tools/package.json",
+                r#"{ "name": "tools", "workspaces": { "packages": ["plugins/*"] } }"#,
+            )
+            .write(
+                "tools/plugins/p/package.json",
+                r#"{ "name": "p", "dependencies": { "a": "workspace:~1.2.0", "left-pad": "^1.0.0" } }"#,
+            );
+
+        let graph = discover(&fixture.root()).unwrap();
+        assert_eq!(graph.sources, vec!["npm"]);
+        assert_eq!(
+            paths(&graph),
+            vec!["", "packages/a", "packages/b", "tools", "tools/plugins/p"]
+        );
+        let a = &graph.packages[1];
+        assert_eq!(
+            (a.name.as_str(), a.version.as_deref()),
+            ("a", Some("1.2.0"))
+        );
+        assert!(a.manifest_path.ends_with("packages/a/package.json"));
+
+        let edges: Vec<(&str, &str, &str, bool)> = graph
+            .edges
+            .iter()
+            .map(|e| {
+                (
+                    e.from.as_str(),
+                    e.to.as_str(),
+                    e.specifier.as_str(),
+                    e.consistent,
+                )
+            })
+            .collect();
+        assert_eq!(
+            edges,
+            vec![
+                ("a", "b", "^2.0.0", false),
+                ("b", "a", "workspace:^", true),
+                ("p", "a", "workspace:~1.2.0", true),
+            ]
+        );
+        assert_eq!(graph.edges[0].pointer, "/dependencies/b");
+    }
+
+    #[test]
+    fn test_pnpm_lerna_and_rush_sources() {
+        let fixture = Fixture::new("tools");
+        fixture
+            .write("pnpm-workspace.yaml", "packages:\n  - 'apps/**'\n  - '!apps/ignored/**'\n")
+            .write("apps/web/package.json", r#"{ "name": "web" }"#)
+            .write("apps/ignored/old/package.json", r#"{ "name": "old" }"#)
+            .write("lerna.json", r#"{ "version": "independent" }"#)
+            .write("packages/lib/package.json", r#"{ "name": "lib" }"#)
+            .write(
+                "rush.json",
+                "/* rush */\n{\n  // projects\n  \"projects\": [\n    { \"packageName\": \"svc\", \"projectFolder\": \"services/svc\" },\n  ]\n}\n",
+            )
+            .write("services/svc/package.json", r#"{ "name": "svc" }"#);
+
+        let graph = discover(&fixture.root()).unwrap();
+        assert_eq!(graph.sources, vec!["pnpm", "lerna", "rush"]);
+        assert_eq!(
+            paths(&graph),
+            vec!["apps/web", "packages/lib", "services/svc"]
+        );
+    }
+
+    #[test]
+    fn test_wildcards() {
+        assert!(wildcard_match("*-plugin", "eslint-plugin"));
+        assert!(wildcard_match("pkg-?", "pkg-a"));
+        assert!(wildcard_match("[a-c]*", "beta"));
+        assert!(!wildcard_match("[!a-c]*", "beta"));
+        assert_eq!(expand_braces("{apps,libs}/*"), vec!["apps/*", "libs/*"]);
+        assert_eq!(consistent("^1.0.0", None), Some(false));
+        assert_eq!(consistent("github:a/b", Some("1.0.0")), None);
+    }
+}